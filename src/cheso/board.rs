@@ -2,13 +2,79 @@ use crate::cheso::color::Color;
 use crate::cheso::movement::{Movement, KING_TRANSLATIONS, KNIGHT_TRANSLATIONS, PROMOTION_PIECES};
 use crate::cheso::piece::Piece;
 use crate::cheso::square::Square;
-use std::collections::HashMap;
+use crate::cheso::zobrist::ZOBRIST;
+use std::collections::BTreeMap;
+
+/// Diagonal directions.
+///
+/// Functions to walk the board as a bishop.
+static DIAGONAL_DIRECTIONS: &[fn(&Square) -> Option<Square>] = &[
+    Square::northeast,
+    Square::northwest,
+    Square::southeast,
+    Square::southwest,
+];
+
+/// Orthogonal directions.
+///
+/// Functions to walk the board as a rook.
+static ORTHOGONAL_DIRECTIONS: &[fn(&Square) -> Option<Square>] =
+    &[Square::north, Square::south, Square::east, Square::west];
+
+/// Irreversible state of a position.
+///
+/// Before making a movement, the irreversible state of the position is stored, so it can be restored when unmaking it.
+#[derive(Copy, Clone)]
+struct State {
+    white_kingside_castling: bool,
+    white_queenside_castling: bool,
+    black_kingside_castling: bool,
+    black_queenside_castling: bool,
+    en_passant: u8,
+    reversible_moves: u8,
+    fullmove_number: u16,
+    hash: u64,
+}
+
+/// Kind of pseudo-legal movements to generate.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Generation {
+    /// All movements.
+    All,
+    /// Captures and promotions.
+    Tactical,
+    /// Movements that are neither captures nor promotions.
+    Quiet,
+}
+
+/// List of pseudo-legal movements being generated, keeping only those of a kind.
+struct MoveList {
+    kind: Generation,
+    moves: Vec<Movement>,
+}
+
+impl MoveList {
+    /// Add a movement to the list, if it is of the kind being generated.
+    fn push(&mut self, movement: Movement) {
+        let keep: bool = match self.kind {
+            Generation::All => true,
+            Generation::Tactical => !movement.is_quiet(),
+            Generation::Quiet => movement.is_quiet(),
+        };
+        if keep {
+            self.moves.push(movement);
+        }
+    }
+}
 
 /// Board representation.
-/// Pieces are stored as maps from squares to pieces (each color in its own hashmap).
+/// Pieces are stored as ordered maps from squares to pieces (each color in its own map),
+/// so they are always enumerated in square order (a1, b1, ..., h1, a2, ..., h8).
+/// The en passant field stores the file (from 1 to 8) of a pawn that has just moved two squares, or 0 otherwise.
+#[derive(Clone)]
 pub struct Board {
-    pub white_pieces: HashMap<Square, Piece>,
-    pub black_pieces: HashMap<Square, Piece>,
+    pub white_pieces: BTreeMap<Square, Piece>,
+    pub black_pieces: BTreeMap<Square, Piece>,
     pub side_to_move: Color,
     pub white_kingside_castling: bool,
     pub white_queenside_castling: bool,
@@ -16,13 +82,25 @@ pub struct Board {
     pub black_queenside_castling: bool,
     pub en_passant: u8,
     pub reversible_moves: u8,
+    pub fullmove_number: u16,
+    hash: u64,
+    history: Vec<State>,
+}
+
+/// Position of the initial board in Forsyth-Edwards Notation.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
 }
 
 impl Board {
     /// Make an initial board.
     pub fn new() -> Board {
-        let mut white_pieces: HashMap<Square, Piece> = HashMap::new();
-        let mut black_pieces: HashMap<Square, Piece> = HashMap::new();
+        let mut white_pieces: BTreeMap<Square, Piece> = BTreeMap::new();
+        let mut black_pieces: BTreeMap<Square, Piece> = BTreeMap::new();
         white_pieces.insert(Square::new(1, 1), Piece::Rook);
         white_pieces.insert(Square::new(1, 2), Piece::Knight);
         white_pieces.insert(Square::new(1, 3), Piece::Bishop);
@@ -55,26 +133,214 @@ impl Board {
         black_pieces.insert(Square::new(7, 6), Piece::Pawn);
         black_pieces.insert(Square::new(7, 7), Piece::Pawn);
         black_pieces.insert(Square::new(7, 8), Piece::Pawn);
-        Board {
-            white_pieces: white_pieces,
-            black_pieces: black_pieces,
-            side_to_move: Color::White,
+        let mut board: Board = Board {
+            white_pieces,
+            black_pieces,
             white_kingside_castling: true,
             white_queenside_castling: true,
             black_kingside_castling: true,
             black_queenside_castling: true,
+            ..Board::empty()
+        };
+        board.hash = board.compute_hash();
+        board
+    }
+
+    /// Make an empty board, with white to move and no castling rights.
+    fn empty() -> Board {
+        Board {
+            white_pieces: BTreeMap::new(),
+            black_pieces: BTreeMap::new(),
+            side_to_move: Color::White,
+            white_kingside_castling: false,
+            white_queenside_castling: false,
+            black_kingside_castling: false,
+            black_queenside_castling: false,
             en_passant: 0,
             reversible_moves: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Make a board from a list of pieces, without castling rights nor en passant.
+    ///
+    /// The position is not validated (a square can be given twice, and kings can be missing).
+    pub fn from_pieces(pieces: &[(Piece, Color, Square)], side_to_move: Color) -> Board {
+        let mut board: Board = Board {
+            side_to_move,
+            ..Board::empty()
+        };
+        for (piece, color, square) in pieces {
+            match color {
+                Color::White => board.white_pieces.insert(*square, *piece),
+                Color::Black => board.black_pieces.insert(*square, *piece),
+            };
+        }
+        board.hash = board.compute_hash();
+        board
+    }
+
+    /// Make a board from a position in Forsyth-Edwards Notation (FEN).
+    ///
+    /// The halfmove clock and fullmove number fields are optional.
+    /// Each side must have exactly one king.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(format!("wrong number of fields in FEN '{}'", fen));
+        }
+        let mut board: Board = Board::empty();
+        // piece placement
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != 8 {
+            return Err(format!("wrong number of ranks in FEN '{}'", fen));
+        }
+        for (row, text) in rows.iter().enumerate() {
+            let rank: isize = 8 - row as isize;
+            let mut file: isize = 1;
+            for letter in text.chars() {
+                match (letter.to_digit(10), Piece::from_char(letter)) {
+                    (Some(empty @ 1..=8), _) => file += empty as isize,
+                    (None, Some(piece)) if file <= 8 => {
+                        let color: Color = if letter.is_ascii_uppercase() {
+                            Color::White
+                        } else {
+                            Color::Black
+                        };
+                        board.put_piece(piece, color, &Square::new(rank, file));
+                        file += 1;
+                    }
+                    _ => return Err(format!("invalid rank '{}' in FEN '{}'", text, fen)),
+                }
+            }
+            if file != 9 {
+                return Err(format!("invalid rank '{}' in FEN '{}'", text, fen));
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            let kings: usize = board
+                .get_pieces(color)
+                .values()
+                .filter(|piece| **piece == Piece::King)
+                .count();
+            if kings != 1 {
+                return Err(format!("each side must have one king in FEN '{}'", fen));
+            }
+        }
+        // side to move
+        board.side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            side => return Err(format!("invalid side to move '{}' in FEN '{}'", side, fen)),
+        };
+        // castling rights
+        if fields[2] != "-" {
+            for letter in fields[2].chars() {
+                match letter {
+                    'K' => board.white_kingside_castling = true,
+                    'Q' => board.white_queenside_castling = true,
+                    'k' => board.black_kingside_castling = true,
+                    'q' => board.black_queenside_castling = true,
+                    _ => {
+                        return Err(format!(
+                            "invalid castling rights '{}' in FEN '{}'",
+                            fields[2], fen
+                        ))
+                    }
+                }
+            }
+        }
+        // en passant (the square behind a pawn of the side not to move)
+        if fields[3] != "-" {
+            let rank: isize = match board.side_to_move {
+                Color::White => 6,
+                Color::Black => 3,
+            };
+            match Square::parse(fields[3]) {
+                Some(square) if square.rank() == rank => {
+                    board.en_passant = square.file() as u8;
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid en passant square '{}' in FEN '{}'",
+                        fields[3], fen
+                    ))
+                }
+            }
+        }
+        // clocks
+        if let Some(halfmove) = fields.get(4) {
+            board.reversible_moves = halfmove
+                .parse()
+                .map_err(|_| format!("invalid halfmove clock '{}' in FEN '{}'", halfmove, fen))?;
         }
+        if let Some(fullmove) = fields.get(5) {
+            board.fullmove_number = fullmove
+                .parse()
+                .map_err(|_| format!("invalid fullmove number '{}' in FEN '{}'", fullmove, fen))?;
+        }
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+
+    /// Get the current position in Forsyth-Edwards Notation (FEN).
+    pub fn to_fen(&self) -> String {
+        let mut fen: String = String::new();
+        for rank in (1..=8).rev() {
+            let mut empty: usize = 0;
+            for file in 1..=8 {
+                match self.get_square(&Square::new(rank, file)) {
+                    None => empty += 1,
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(match color {
+                            Color::White => piece.to_char().to_ascii_uppercase(),
+                            Color::Black => piece.to_char(),
+                        });
+                    }
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 1 {
+                fen.push('/');
+            }
+        }
+        fen.push_str(match self.side_to_move {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+        let castling: String = ['K', 'Q', 'k', 'q']
+            .iter()
+            .zip(self.castling())
+            .filter(|(_, right)| *right)
+            .map(|(letter, _)| *letter)
+            .collect();
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+        match self.get_en_passant_square() {
+            Some(square) => fen.push_str(&format!(" {}", square)),
+            None => fen.push_str(" -"),
+        }
+        fen.push_str(&format!(
+            " {} {}",
+            self.reversible_moves, self.fullmove_number
+        ));
+        fen
     }
 
     /// Get the possible piece (and its color) located in a square of the current position.
     pub fn get_square(&self, square: &Square) -> Option<(Piece, Color)> {
         match self.white_pieces.get(square) {
-            None => match self.black_pieces.get(&square) {
-                None => None,
-                Some(piece) => Some((*piece, Color::Black)),
-            },
+            None => self
+                .black_pieces
+                .get(square)
+                .map(|piece| (*piece, Color::Black)),
             Some(piece) => Some((*piece, Color::White)),
         }
     }
@@ -84,14 +350,27 @@ impl Board {
         self.get_square(square).is_none()
     }
 
-    /// Get the list of pieces to move in the current position.
-    pub fn get_pieces_to_move(&self) -> &HashMap<Square, Piece> {
-        match self.side_to_move {
+    /// Get the list of pieces of a color in the current position.
+    pub fn get_pieces(&self, color: Color) -> &BTreeMap<Square, Piece> {
+        match color {
             Color::White => &self.white_pieces,
             Color::Black => &self.black_pieces,
         }
     }
 
+    /// Get the list of pieces to move in the current position.
+    pub fn get_pieces_to_move(&self) -> &BTreeMap<Square, Piece> {
+        self.get_pieces(self.side_to_move)
+    }
+
+    /// Get the square of the king of a color, if any.
+    pub fn get_king_square(&self, color: Color) -> Option<Square> {
+        self.get_pieces(color)
+            .iter()
+            .find(|(_, piece)| **piece == Piece::King)
+            .map(|(square, _)| *square)
+    }
+
     /// Get the castling rights (queenside, kingside) of the side to move.
     pub fn get_castling_rights(&self) -> (bool, bool) {
         match self.side_to_move {
@@ -100,27 +379,623 @@ impl Board {
         }
     }
 
+    /// Get the square a pawn can move to when capturing en passant, if any.
+    pub fn get_en_passant_square(&self) -> Option<Square> {
+        match (self.en_passant, self.side_to_move) {
+            (0, _) => None,
+            (file, Color::White) => Some(Square::new(6, file as isize)),
+            (file, Color::Black) => Some(Square::new(3, file as isize)),
+        }
+    }
+
+    /// Get the Zobrist hash of the current position.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Compute the Zobrist hash of the current position from scratch.
+    fn compute_hash(&self) -> u64 {
+        let mut hash: u64 = 0;
+        for (square, piece) in &self.white_pieces {
+            hash ^= ZOBRIST.piece(*piece, Color::White, square);
+        }
+        for (square, piece) in &self.black_pieces {
+            hash ^= ZOBRIST.piece(*piece, Color::Black, square);
+        }
+        for (index, right) in self.castling().iter().enumerate() {
+            if *right {
+                hash ^= ZOBRIST.castling(index);
+            }
+        }
+        if self.en_passant != 0 {
+            hash ^= ZOBRIST.en_passant(self.en_passant);
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= ZOBRIST.side();
+        }
+        hash
+    }
+
+    /// Get all castling rights (white kingside, white queenside, black kingside, black queenside).
+    fn castling(&self) -> [bool; 4] {
+        [
+            self.white_kingside_castling,
+            self.white_queenside_castling,
+            self.black_kingside_castling,
+            self.black_queenside_castling,
+        ]
+    }
+
+    /// Remove a castling right, updating the hash of the position.
+    fn remove_castling(&mut self, index: usize) {
+        let right: &mut bool = match index {
+            0 => &mut self.white_kingside_castling,
+            1 => &mut self.white_queenside_castling,
+            2 => &mut self.black_kingside_castling,
+            _ => &mut self.black_queenside_castling,
+        };
+        if *right {
+            *right = false;
+            self.hash ^= ZOBRIST.castling(index);
+        }
+    }
+
+    /// Remove the castling rights lost by moving from (or to) a square.
+    ///
+    /// Moving the king loses both castling rights, and moving a rook (or capturing it) loses its castling right.
+    fn update_castling_rights(&mut self, square: &Square) {
+        match (square.rank(), square.file()) {
+            (1, 5) => {
+                self.remove_castling(0);
+                self.remove_castling(1);
+            }
+            (1, 8) => self.remove_castling(0),
+            (1, 1) => self.remove_castling(1),
+            (8, 5) => {
+                self.remove_castling(2);
+                self.remove_castling(3);
+            }
+            (8, 8) => self.remove_castling(2),
+            (8, 1) => self.remove_castling(3),
+            _ => (),
+        }
+    }
+
+    /// Put a piece in a square, updating the hash of the position.
+    fn put_piece(&mut self, piece: Piece, color: Color, square: &Square) {
+        match color {
+            Color::White => self.white_pieces.insert(*square, piece),
+            Color::Black => self.black_pieces.insert(*square, piece),
+        };
+        self.hash ^= ZOBRIST.piece(piece, color, square);
+    }
+
+    /// Remove a piece from a square, updating the hash of the position.
+    fn remove_piece(&mut self, piece: Piece, color: Color, square: &Square) {
+        match color {
+            Color::White => self.white_pieces.remove(square),
+            Color::Black => self.black_pieces.remove(square),
+        };
+        self.hash ^= ZOBRIST.piece(piece, color, square);
+    }
+
+    /// Get the squares of the rook (from, to) when the king castles.
+    fn castling_rook_squares(movement: &Movement) -> (Square, Square) {
+        let rank: isize = movement.from.rank();
+        if movement.to.file() > movement.from.file() {
+            (Square::new(rank, 8), Square::new(rank, 6))
+        } else {
+            (Square::new(rank, 1), Square::new(rank, 4))
+        }
+    }
+
+    /// Check if a movement of a piece is castling.
+    fn is_castling(piece: Piece, movement: &Movement) -> bool {
+        piece == Piece::King && (movement.to.file() - movement.from.file()).abs() == 2
+    }
+
     /// Make a movement in the current position.
-    pub fn make_move(&self, movement: &Movement) -> () {}
+    ///
+    /// The movement must be pseudo-legal in the current position.
+    /// The irreversible state of the position is saved, so the movement can be unmade later.
+    pub fn make_move(&mut self, movement: &Movement) {
+        let color: Color = self.side_to_move;
+        let piece: Piece = self.get_pieces_to_move()[&movement.from];
+        let en_passant_square: Option<Square> = self.get_en_passant_square();
+        self.history.push(self.state());
+        if self.en_passant != 0 {
+            self.hash ^= ZOBRIST.en_passant(self.en_passant);
+            self.en_passant = 0;
+        }
+        // capture
+        if let Some(captured) = movement.capture {
+            if piece == Piece::Pawn && Some(movement.to) == en_passant_square {
+                let square: Square = Square::new(movement.from.rank(), movement.to.file());
+                self.remove_piece(captured, color.opposite(), &square);
+            } else {
+                self.remove_piece(captured, color.opposite(), &movement.to);
+            }
+        }
+        // movement (and promotion)
+        self.remove_piece(piece, color, &movement.from);
+        self.put_piece(movement.promotion.unwrap_or(piece), color, &movement.to);
+        // castling
+        if Board::is_castling(piece, movement) {
+            let (rook_from, rook_to) = Board::castling_rook_squares(movement);
+            self.remove_piece(Piece::Rook, color, &rook_from);
+            self.put_piece(Piece::Rook, color, &rook_to);
+        }
+        // two squares
+        if piece == Piece::Pawn && (movement.to.rank() - movement.from.rank()).abs() == 2 {
+            self.en_passant = movement.from.file() as u8;
+            self.hash ^= ZOBRIST.en_passant(self.en_passant);
+        }
+        self.update_castling_rights(&movement.from);
+        self.update_castling_rights(&movement.to);
+        if piece == Piece::Pawn || movement.is_capture() {
+            self.reversible_moves = 0;
+        } else {
+            self.reversible_moves = self.reversible_moves.saturating_add(1);
+        }
+        if color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = color.opposite();
+        self.hash ^= ZOBRIST.side();
+    }
 
     // Unmake a movement in the current position.
-    pub fn unmake_move(&self, movement: &Movement) -> () {}
+    //
+    // The movement must be the last movement made in the current position.
+    pub fn unmake_move(&mut self, movement: &Movement) {
+        let state: State = self
+            .history
+            .pop()
+            .expect("unmake_move called without a previous make_move");
+        self.side_to_move = self.side_to_move.opposite();
+        let color: Color = self.side_to_move;
+        let piece: Piece = match movement.promotion {
+            Some(_) => Piece::Pawn,
+            None => self.get_pieces_to_move()[&movement.to],
+        };
+        // movement (and promotion)
+        self.remove_piece(movement.promotion.unwrap_or(piece), color, &movement.to);
+        self.put_piece(piece, color, &movement.from);
+        // castling
+        if Board::is_castling(piece, movement) {
+            let (rook_from, rook_to) = Board::castling_rook_squares(movement);
+            self.remove_piece(Piece::Rook, color, &rook_to);
+            self.put_piece(Piece::Rook, color, &rook_from);
+        }
+        // capture
+        if let Some(captured) = movement.capture {
+            self.en_passant = state.en_passant;
+            if piece == Piece::Pawn && Some(movement.to) == self.get_en_passant_square() {
+                let square: Square = Square::new(movement.from.rank(), movement.to.file());
+                self.put_piece(captured, color.opposite(), &square);
+            } else {
+                self.put_piece(captured, color.opposite(), &movement.to);
+            }
+        }
+        self.restore(&state);
+    }
+
+    /// Make a null movement (pass the turn to the opponent) in the current position.
+    ///
+    /// Null movements are not legal, but they are useful for the search to prove that a position is good enough.
+    /// Positions before a null movement are not taken into account to detect repetitions.
+    pub fn make_null_move(&mut self) {
+        self.history.push(self.state());
+        if self.en_passant != 0 {
+            self.hash ^= ZOBRIST.en_passant(self.en_passant);
+            self.en_passant = 0;
+        }
+        self.reversible_moves = 0;
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= ZOBRIST.side();
+    }
+
+    /// Unmake a null movement in the current position.
+    pub fn unmake_null_move(&mut self) {
+        let state: State = self
+            .history
+            .pop()
+            .expect("unmake_null_move called without a previous make_null_move");
+        self.side_to_move = self.side_to_move.opposite();
+        self.restore(&state);
+    }
+
+    /// Get the irreversible state of the current position.
+    fn state(&self) -> State {
+        State {
+            white_kingside_castling: self.white_kingside_castling,
+            white_queenside_castling: self.white_queenside_castling,
+            black_kingside_castling: self.black_kingside_castling,
+            black_queenside_castling: self.black_queenside_castling,
+            en_passant: self.en_passant,
+            reversible_moves: self.reversible_moves,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+        }
+    }
+
+    /// Restore the irreversible state of a position.
+    fn restore(&mut self, state: &State) {
+        self.white_kingside_castling = state.white_kingside_castling;
+        self.white_queenside_castling = state.white_queenside_castling;
+        self.black_kingside_castling = state.black_kingside_castling;
+        self.black_queenside_castling = state.black_queenside_castling;
+        self.en_passant = state.en_passant;
+        self.reversible_moves = state.reversible_moves;
+        self.fullmove_number = state.fullmove_number;
+        self.hash = state.hash;
+    }
+
+    /// Check if the current position is a repetition of a previous one.
+    ///
+    /// Only positions since the last irreversible movement (with the same side to move) are taken into account.
+    pub fn is_repetition(&self) -> bool {
+        let limit: usize = std::cmp::min(self.reversible_moves as usize, self.history.len());
+        (4..=limit)
+            .step_by(2)
+            .any(|ply| self.history[self.history.len() - ply].hash == self.hash)
+    }
+
+    /// Check if the current position is drawn by the fifty-move rule, repetition or insufficient material.
+    pub fn is_draw(&self) -> bool {
+        self.reversible_moves >= 100 || self.is_repetition() || self.is_insufficient_material()
+    }
+
+    /// Check if neither side has enough material to checkmate (only kings and at most one minor piece).
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors: usize = 0;
+        for piece in self.white_pieces.values().chain(self.black_pieces.values()) {
+            match piece {
+                Piece::King => (),
+                Piece::Knight | Piece::Bishop => minors += 1,
+                _ => return false,
+            }
+        }
+        minors <= 1
+    }
+
+    /// Check if a color has any piece other than pawns and the king.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        self.get_pieces(color)
+            .values()
+            .any(|piece| *piece != Piece::Pawn && *piece != Piece::King)
+    }
+
+    /// Check if a square is attacked by any piece of a color.
+    pub fn is_attacked(&self, square: &Square, by: Color) -> bool {
+        let pieces: &BTreeMap<Square, Piece> = self.get_pieces(by);
+        let is_piece = |to: Option<Square>, kind: Piece| -> bool {
+            to.and_then(|to| pieces.get(&to)) == Some(&kind)
+        };
+        // pawns
+        let pawn_rank: isize = match by {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        if is_piece(square.translate(pawn_rank, -1), Piece::Pawn)
+            || is_piece(square.translate(pawn_rank, 1), Piece::Pawn)
+        {
+            return true;
+        }
+        // knights and kings
+        if KNIGHT_TRANSLATIONS
+            .iter()
+            .any(|(rank, file)| is_piece(square.translate(*rank, *file), Piece::Knight))
+            || KING_TRANSLATIONS
+                .iter()
+                .any(|(rank, file)| is_piece(square.translate(*rank, *file), Piece::King))
+        {
+            return true;
+        }
+        // sliders
+        let slider = |directions: &[fn(&Square) -> Option<Square>], kind: Piece| -> bool {
+            directions
+                .iter()
+                .any(|update| match self.first_piece(square, *update, &[]) {
+                    Some((_, piece, color)) => {
+                        color == by && (piece == kind || piece == Piece::Queen)
+                    }
+                    None => false,
+                })
+        };
+        slider(DIAGONAL_DIRECTIONS, Piece::Bishop) || slider(ORTHOGONAL_DIRECTIONS, Piece::Rook)
+    }
+
+    /// Get the first piece found walking from a square in a direction, ignoring some squares.
+    fn first_piece(
+        &self,
+        from: &Square,
+        update: fn(&Square) -> Option<Square>,
+        ignored: &[Square],
+    ) -> Option<(Square, Piece, Color)> {
+        let mut to: Option<Square> = update(from);
+        while let Some(square) = to {
+            if !ignored.contains(&square) {
+                if let Some((piece, color)) = self.get_square(&square) {
+                    return Some((square, piece, color));
+                }
+            }
+            to = update(&square);
+        }
+        None
+    }
+
+    /// Get the least valuable piece of a color attacking a square, ignoring the pieces located in some squares.
+    ///
+    /// Ignoring squares allows to discover attacks behind pieces (x-rays), as in a sequence of exchanges.
+    pub fn get_least_valuable_attacker(
+        &self,
+        square: &Square,
+        by: Color,
+        ignored: &[Square],
+    ) -> Option<(Square, Piece)> {
+        let pieces: &BTreeMap<Square, Piece> = self.get_pieces(by);
+        let mut best: Option<(Square, Piece)> = None;
+        let mut consider = |from: Square, piece: Piece| {
+            if !ignored.contains(&from) && best.is_none_or(|(_, other)| piece < other) {
+                best = Some((from, piece));
+            }
+        };
+        let pawn_rank: isize = match by {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        for file in [-1, 1] {
+            if let Some(from) = square.translate(pawn_rank, file) {
+                if pieces.get(&from) == Some(&Piece::Pawn) {
+                    consider(from, Piece::Pawn);
+                }
+            }
+        }
+        for (translations, kind) in [
+            (KNIGHT_TRANSLATIONS, Piece::Knight),
+            (KING_TRANSLATIONS, Piece::King),
+        ] {
+            for (rank, file) in translations {
+                if let Some(from) = square.translate(*rank, *file) {
+                    if pieces.get(&from) == Some(&kind) {
+                        consider(from, kind);
+                    }
+                }
+            }
+        }
+        for (directions, kind) in [
+            (DIAGONAL_DIRECTIONS, Piece::Bishop),
+            (ORTHOGONAL_DIRECTIONS, Piece::Rook),
+        ] {
+            for update in directions {
+                if let Some((from, piece, color)) = self.first_piece(square, *update, ignored) {
+                    if color == by && (piece == kind || piece == Piece::Queen) {
+                        consider(from, piece);
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Check if the king of a color is in check.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.get_king_square(color)
+            .is_some_and(|square| self.is_attacked(&square, color.opposite()))
+    }
+
+    /// Check if the side to move is in check.
+    pub fn in_check(&self) -> bool {
+        self.is_in_check(self.side_to_move)
+    }
+
+    /// Generate the list of legal movements.
+    /// Pseudo-legal movements are made and unmade, discarding those which leave the king in check.
+    pub fn gen_legal_moves(&mut self) -> Vec<Movement> {
+        let color: Color = self.side_to_move;
+        self.gen_moves()
+            .into_iter()
+            .filter(|movement| {
+                self.make_move(movement);
+                let legal: bool = !self.is_in_check(color);
+                self.unmake_move(movement);
+                legal
+            })
+            .collect()
+    }
+
+    /// Parse a legal movement in long algebraic notation, as used by the UCI protocol (e.g. `e2e4` or `e7e8q`).
+    pub fn parse_move(&mut self, text: &str) -> Option<Movement> {
+        self.gen_legal_moves()
+            .into_iter()
+            .find(|movement| movement.to_string() == text)
+    }
+
+    /// Parse a legal movement in standard algebraic notation (e.g. `e4`, `Nbd7`, `exd8=Q+` or `O-O`).
+    ///
+    /// Check and annotation suffixes are ignored, and ambiguous movements are rejected.
+    pub fn parse_san(&mut self, text: &str) -> Option<Movement> {
+        let text: &str = text.trim_end_matches(['+', '#', '!', '?']);
+        let castling: Option<isize> = match text {
+            "O-O" | "0-0" => Some(7),
+            "O-O-O" | "0-0-0" => Some(3),
+            _ => None,
+        };
+        if let Some(file) = castling {
+            let king: Square = self.get_king_square(self.side_to_move)?;
+            return self.gen_legal_moves().into_iter().find(|movement| {
+                movement.from == king && movement.to == Square::new(king.rank(), file)
+            });
+        }
+        let mut chars: Vec<char> = text
+            .chars()
+            .filter(|c| *c != 'x' && *c != '-' && *c != '=')
+            .collect();
+        let piece: Piece = match chars.first() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let piece: Piece = Piece::from_char(*c)?;
+                chars.remove(0);
+                piece
+            }
+            _ => Piece::Pawn,
+        };
+        let promotion: Option<Piece> = match chars.last() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let promotion: Piece = Piece::from_char(*c)?;
+                chars.pop();
+                Some(promotion)
+            }
+            _ => None,
+        };
+        if chars.len() < 2 {
+            return None;
+        }
+        let to: Square = Square::parse(&chars[chars.len() - 2..].iter().collect::<String>())?;
+        let hints: &[char] = &chars[..chars.len() - 2];
+        let candidates: Vec<Movement> = self
+            .gen_legal_moves()
+            .into_iter()
+            .filter(|movement| {
+                movement.to == to
+                    && movement.promotion == promotion
+                    && self.get_square(&movement.from).map(|(piece, _)| piece) == Some(piece)
+                    && hints.iter().all(|hint| match hint {
+                        'a'..='h' => movement.from.file() == (*hint as u8 - b'a') as isize + 1,
+                        '1'..='8' => movement.from.rank() == (*hint as u8 - b'1') as isize + 1,
+                        _ => false,
+                    })
+            })
+            .collect();
+        match candidates.as_slice() {
+            [movement] => Some(*movement),
+            _ => None,
+        }
+    }
+
+    /// Write a legal movement in standard algebraic notation, with the check or mate suffix.
+    pub fn to_san(&mut self, movement: &Movement) -> String {
+        let piece: Piece = self
+            .get_square(&movement.from)
+            .map_or(Piece::Pawn, |(piece, _)| piece);
+        let mut text: String = String::new();
+        if piece == Piece::King && (movement.to.file() - movement.from.file()).abs() == 2 {
+            text.push_str(if movement.to.file() > movement.from.file() {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            let from: String = movement.from.to_string();
+            if piece == Piece::Pawn {
+                if movement.is_capture() {
+                    text.push_str(&from[..1]);
+                }
+            } else {
+                text.push(piece.to_char().to_ascii_uppercase());
+                // disambiguate from other pieces of the same kind moving to the same square
+                let others: Vec<Movement> = self
+                    .gen_legal_moves()
+                    .into_iter()
+                    .filter(|other| {
+                        other.to == movement.to
+                            && other.from != movement.from
+                            && self.get_square(&other.from).map(|(piece, _)| piece) == Some(piece)
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    if others
+                        .iter()
+                        .all(|other| other.from.file() != movement.from.file())
+                    {
+                        text.push_str(&from[..1]);
+                    } else if others
+                        .iter()
+                        .all(|other| other.from.rank() != movement.from.rank())
+                    {
+                        text.push_str(&from[1..]);
+                    } else {
+                        text.push_str(&from);
+                    }
+                }
+            }
+            if movement.is_capture() {
+                text.push('x');
+            }
+            text.push_str(&movement.to.to_string());
+            if let Some(promotion) = movement.promotion {
+                text.push('=');
+                text.push(promotion.to_char().to_ascii_uppercase());
+            }
+        }
+        self.make_move(movement);
+        if self.in_check() {
+            text.push(if self.gen_legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        self.unmake_move(movement);
+        text
+    }
 
     /// Generate the list of pseudo-legal movements.
     /// Pieces obey normal rules of movement, but they are not checked to see if they will leave the king in check.
+    /// Movements are always generated in the same order: by origin square, and then by direction.
     pub fn gen_moves(&self) -> Vec<Movement> {
-        let mut moves: Vec<Movement> = Vec::new();
-        for (square, piece) in self.get_pieces_to_move() {
-            match piece {
-                Piece::Pawn => self.gen_pawn_moves(square, &mut moves),
-                Piece::Knight => self.gen_knight_moves(square, &mut moves),
-                Piece::Bishop => self.gen_bishop_moves(square, &mut moves),
-                Piece::Rook => self.gen_rook_moves(square, &mut moves),
-                Piece::Queen => self.gen_queen_moves(square, &mut moves),
-                Piece::King => self.gen_king_moves(square, &mut moves),
+        self.gen_moves_of_kind(Generation::All)
+    }
+
+    /// Generate the list of pseudo-legal captures and promotions, in the same order as `gen_moves`.
+    pub fn gen_captures(&self) -> Vec<Movement> {
+        self.gen_moves_of_kind(Generation::Tactical)
+    }
+
+    /// Generate the list of pseudo-legal quiet movements, in the same order as `gen_moves`.
+    pub fn gen_quiets(&self) -> Vec<Movement> {
+        self.gen_moves_of_kind(Generation::Quiet)
+    }
+
+    /// Check if a movement is pseudo-legal in the position.
+    ///
+    /// Only the movements of the piece on the origin square are generated, so it is cheaper than `gen_moves`.
+    pub fn is_pseudo_legal(&self, movement: &Movement) -> bool {
+        let mut moves: MoveList = MoveList {
+            kind: Generation::All,
+            moves: Vec::new(),
+        };
+        match self.get_square(&movement.from) {
+            Some((piece, color)) if color == self.side_to_move => {
+                self.gen_piece_moves(&movement.from, piece, &mut moves);
+                moves.moves.contains(movement)
             }
+            _ => false,
+        }
+    }
+
+    /// Generate the list of pseudo-legal movements of a kind.
+    fn gen_moves_of_kind(&self, kind: Generation) -> Vec<Movement> {
+        let mut moves: MoveList = MoveList {
+            kind,
+            moves: Vec::new(),
+        };
+        for (square, piece) in self.get_pieces_to_move() {
+            self.gen_piece_moves(square, *piece, &mut moves);
+        }
+        moves.moves
+    }
+
+    /// Populate the list of movements from a square with the movements of a piece.
+    fn gen_piece_moves(&self, square: &Square, piece: Piece, moves: &mut MoveList) {
+        match piece {
+            Piece::Pawn => self.gen_pawn_moves(square, moves),
+            Piece::Knight => self.gen_knight_moves(square, moves),
+            Piece::Bishop => self.gen_bishop_moves(square, moves),
+            Piece::Rook => self.gen_rook_moves(square, moves),
+            Piece::Queen => self.gen_queen_moves(square, moves),
+            Piece::King => self.gen_king_moves(square, moves),
         }
-        moves
     }
 
     /// Populate the list of movements from a square as a pawn.
@@ -129,102 +1004,67 @@ impl Board {
     /// Pawns can move two squares if they have not yet moved.
     /// Pawns capture one square diagonally in a forward direction.
     /// Pawns can make a special move (capture en passant).
-    fn gen_pawn_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    fn gen_pawn_moves(&self, from: &Square, moves: &mut MoveList) {
         // forward
-        match from.forward(self.side_to_move) {
-            None => (),
-            Some(square) => {
-                if self.is_empty_square(&square) {
-                    // promotion
-                    if square.is_last_rank(self.side_to_move) {
-                        for i in 0..PROMOTION_PIECES.len() {
-                            moves.push(Movement {
-                                from: *from,
-                                to: square,
-                                capture: None,
-                                promotion: Some(PROMOTION_PIECES[i]),
-                            });
+        if let Some(square) = from.forward(self.side_to_move) {
+            if self.is_empty_square(&square) {
+                self.push_pawn_move(from, &square, None, moves);
+                // two squares
+                if from.is_pawn_rank(self.side_to_move) {
+                    if let Some(square) = from.forward2(self.side_to_move) {
+                        if self.is_empty_square(&square) {
+                            self.push_pawn_move(from, &square, None, moves);
                         }
-                    } else {
-                        moves.push(Movement {
-                            from: *from,
-                            to: square,
-                            capture: None,
-                            promotion: None,
-                        });
-                    }
-                }
-            }
-        }
-        // two squares
-        if from.is_pawn_rank(self.side_to_move) {
-            match from.forward2(self.side_to_move) {
-                None => (),
-                Some(square) => {
-                    if self.is_empty_square(&square) {
-                        moves.push(Movement {
-                            from: *from,
-                            to: square,
-                            capture: None,
-                            promotion: None,
-                        });
                     }
                 }
             }
         }
         // capture (left)
-        match from.pawn_left_capture(self.side_to_move) {
-            None => (),
-            Some(square) => match self.get_square(&square) {
-                Some((piece, color)) if color != self.side_to_move => {
-                    // promotion
-                    if square.is_last_rank(self.side_to_move) {
-                        for i in 0..PROMOTION_PIECES.len() {
-                            moves.push(Movement {
-                                from: *from,
-                                to: square,
-                                capture: Some(piece),
-                                promotion: Some(PROMOTION_PIECES[i]),
-                            });
-                        }
-                    } else {
-                        moves.push(Movement {
-                            from: *from,
-                            to: square,
-                            capture: Some(piece),
-                            promotion: None,
-                        });
-                    }
-                }
-                _ => (),
-            },
-        }
+        self.gen_pawn_capture(from, from.pawn_left_capture(self.side_to_move), moves);
         // capture (right)
-        match from.pawn_right_capture(self.side_to_move) {
-            None => (),
-            Some(square) => match self.get_square(&square) {
+        self.gen_pawn_capture(from, from.pawn_right_capture(self.side_to_move), moves);
+    }
+
+    /// Populate the list of movements with a pawn capture (including en passant).
+    fn gen_pawn_capture(&self, from: &Square, to: Option<Square>, moves: &mut MoveList) {
+        if let Some(square) = to {
+            match self.get_square(&square) {
                 Some((piece, color)) if color != self.side_to_move => {
-                    // promotion
-                    if square.is_last_rank(self.side_to_move) {
-                        for i in 0..PROMOTION_PIECES.len() {
-                            moves.push(Movement {
-                                from: *from,
-                                to: square,
-                                capture: Some(piece),
-                                promotion: Some(PROMOTION_PIECES[i]),
-                            });
-                        }
-                    } else {
-                        moves.push(Movement {
-                            from: *from,
-                            to: square,
-                            capture: Some(piece),
-                            promotion: None,
-                        });
-                    }
+                    self.push_pawn_move(from, &square, Some(piece), moves);
+                }
+                None if Some(square) == self.get_en_passant_square() => {
+                    self.push_pawn_move(from, &square, Some(Piece::Pawn), moves);
                 }
                 _ => (),
-            },
+            }
+        }
+    }
+
+    /// Push a pawn movement, generating all possible promotions when it reaches the last rank.
+    fn push_pawn_move(
+        &self,
+        from: &Square,
+        to: &Square,
+        capture: Option<Piece>,
+        moves: &mut MoveList,
+    ) {
+        // promotion
+        if to.is_last_rank(self.side_to_move) {
+            for piece in PROMOTION_PIECES {
+                moves.push(Movement {
+                    from: *from,
+                    to: *to,
+                    capture,
+                    promotion: Some(*piece),
+                });
+            }
+        } else {
+            moves.push(Movement {
+                from: *from,
+                to: *to,
+                capture,
+                promotion: None,
+            });
         }
     }
 
@@ -232,7 +1072,7 @@ impl Board {
     ///
     /// Knights move two squares in a horizontal or vertical direction, then move one square horizontally or vertically.
     /// Knights are the only piece able to jump over other pieces.
-    fn gen_knight_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    fn gen_knight_moves(&self, from: &Square, moves: &mut MoveList) {
         self.gen_moves_from_array(from, KNIGHT_TRANSLATIONS, moves);
     }
 
@@ -240,7 +1080,7 @@ impl Board {
     ///
     /// Bishops move diagonally any number of squares.
     /// Bishops are unable to jump over pieces.
-    fn gen_bishop_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    fn gen_bishop_moves(&self, from: &Square, moves: &mut MoveList) {
         self.gen_moves_until_collision(from, Square::northeast, moves);
         self.gen_moves_until_collision(from, Square::northwest, moves);
         self.gen_moves_until_collision(from, Square::southeast, moves);
@@ -251,7 +1091,7 @@ impl Board {
     ///
     /// Rooks move horizontally or vertically any number of squares.
     /// Rooks are unable to jump over pieces.
-    fn gen_rook_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    fn gen_rook_moves(&self, from: &Square, moves: &mut MoveList) {
         self.gen_moves_until_collision(from, Square::north, moves);
         self.gen_moves_until_collision(from, Square::south, moves);
         self.gen_moves_until_collision(from, Square::east, moves);
@@ -262,7 +1102,7 @@ impl Board {
     ///
     /// Queens move diagonally, horizontally, or vertically any number of squares.
     /// Queens are unable to jump over pieces.
-    fn gen_queen_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    fn gen_queen_moves(&self, from: &Square, moves: &mut MoveList) {
         self.gen_bishop_moves(from, moves);
         self.gen_rook_moves(from, moves);
     }
@@ -271,8 +1111,51 @@ impl Board {
     ///
     /// Kings move one square in any direction.
     /// Kings are unable to jump over pieces.
-    fn gen_king_moves(&self, from: &Square, moves: &mut Vec<Movement>) -> () {
+    /// Kings can make a special move (castling) with a rook, if neither has moved yet.
+    fn gen_king_moves(&self, from: &Square, moves: &mut MoveList) {
         self.gen_moves_from_array(from, KING_TRANSLATIONS, moves);
+        self.gen_castling_moves(from, moves);
+    }
+
+    /// Populate the list of movements with castling.
+    ///
+    /// The king and the rook must be on their initial squares, squares between them must be empty,
+    /// and the king can not be in check nor pass through a square attacked by an enemy piece.
+    /// Whether the king ends in check is left to the legality test, as for any other movement.
+    fn gen_castling_moves(&self, from: &Square, moves: &mut MoveList) {
+        let color: Color = self.side_to_move;
+        let rank: isize = match color {
+            Color::White => 1,
+            Color::Black => 8,
+        };
+        let (queenside, kingside) = self.get_castling_rights();
+        if moves.kind == Generation::Tactical
+            || *from != Square::new(rank, 5)
+            || !(queenside || kingside)
+            || self.in_check()
+        {
+            return;
+        }
+        let is_empty = |file: isize| self.is_empty_square(&Square::new(rank, file));
+        let is_rook =
+            |file: isize| self.get_square(&Square::new(rank, file)) == Some((Piece::Rook, color));
+        let is_safe = |file: isize| !self.is_attacked(&Square::new(rank, file), color.opposite());
+        if kingside && is_rook(8) && is_empty(6) && is_empty(7) && is_safe(6) {
+            moves.push(Movement {
+                from: *from,
+                to: Square::new(rank, 7),
+                capture: None,
+                promotion: None,
+            });
+        }
+        if queenside && is_rook(1) && is_empty(4) && is_empty(3) && is_empty(2) && is_safe(4) {
+            moves.push(Movement {
+                from: *from,
+                to: Square::new(rank, 3),
+                capture: None,
+                promotion: None,
+            });
+        }
     }
 
     /// Populate the list of movements from an array of translations.
@@ -284,13 +1167,12 @@ impl Board {
         &self,
         from: &Square,
         translations: &[(isize, isize)],
-        moves: &mut Vec<Movement>,
-    ) -> () {
-        for i in 0..translations.len() {
-            let (rank, file) = translations[i];
-            let to: Option<Square> = from.translate(rank, file);
-            match to {
-                Some(square) => match self.get_square(&square) {
+        moves: &mut MoveList,
+    ) {
+        for (rank, file) in translations {
+            let to: Option<Square> = from.translate(*rank, *file);
+            if let Some(square) = to {
+                match self.get_square(&square) {
                     None => moves.push(Movement {
                         from: *from,
                         to: square,
@@ -304,8 +1186,7 @@ impl Board {
                         promotion: None,
                     }),
                     _ => (),
-                },
-                _ => (),
+                }
             }
         }
     }
@@ -319,8 +1200,8 @@ impl Board {
         &self,
         from: &Square,
         update: fn(&Square) -> Option<Square>,
-        moves: &mut Vec<Movement>,
-    ) -> () {
+        moves: &mut MoveList,
+    ) {
         let mut to: Option<Square> = Some(*from);
         loop {
             to = to.as_ref().and_then(update);
@@ -351,3 +1232,211 @@ impl Board {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheso::search::{Limits, SearchResult, Searcher};
+
+    /// Get a square from its coordinates (e.g. `e4`).
+    fn square(text: &str) -> Square {
+        let bytes: &[u8] = text.as_bytes();
+        Square::new((bytes[1] - b'0') as isize, (bytes[0] - b'a') as isize + 1)
+    }
+
+    /// Count the leaf nodes of the tree of legal movements of a depth.
+    fn perft(board: &mut Board, depth: u32) -> u64 {
+        let moves: Vec<Movement> = board.gen_legal_moves();
+        if depth <= 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes: u64 = 0;
+        for movement in moves {
+            board.make_move(&movement);
+            nodes += perft(board, depth - 1);
+            board.unmake_move(&movement);
+        }
+        nodes
+    }
+
+    #[test]
+    fn perft_initial_position() {
+        let mut board: Board = Board::new();
+        for (depth, nodes) in [(1, 20), (2, 400), (3, 8902), (4, 197281)] {
+            assert_eq!(perft(&mut board, depth), nodes, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn perft_from_fen() {
+        let positions: [(&str, [u64; 3]); 3] = [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                [48, 2039, 97862],
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", [14, 191, 2812]),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                [6, 264, 9467],
+            ),
+        ];
+        for (fen, counts) in positions {
+            let mut board: Board = Board::from_fen(fen).unwrap();
+            for (depth, nodes) in (1..).zip(counts) {
+                assert_eq!(perft(&mut board, depth), nodes, "{} depth {}", fen, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn from_fen_checks_en_passant_rank() {
+        let board: Board =
+            Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")
+                .unwrap();
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        assert!(
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1").is_err()
+        );
+        assert!(
+            Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR b KQkq e6 0 1").is_err()
+        );
+        assert!(
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").is_ok()
+        );
+    }
+
+    #[test]
+    fn make_and_unmake_keep_the_hash() {
+        let mut board: Board = Board::new();
+        let initial: u64 = board.hash();
+        let mut played: Vec<Movement> = Vec::new();
+        let mut random: u64 = 0x9e3779b97f4a7c15;
+        for _ in 0..300 {
+            let moves: Vec<Movement> = board.gen_legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            random ^= random << 13;
+            random ^= random >> 7;
+            random ^= random << 17;
+            let movement: Movement = moves[(random % moves.len() as u64) as usize];
+            let before: u64 = board.hash();
+            board.make_move(&movement);
+            assert_eq!(board.hash(), board.compute_hash());
+            board.unmake_move(&movement);
+            assert_eq!(board.hash(), before);
+            board.make_move(&movement);
+            played.push(movement);
+        }
+        for movement in played.iter().rev() {
+            board.unmake_move(movement);
+        }
+        assert_eq!(board.hash(), initial);
+    }
+
+    #[test]
+    fn gen_captures_and_quiets_split_moves() {
+        // promotions, en passant, castling and captures
+        let mut board: Board = Board::new();
+        for (from, to) in [
+            ("b2", "b4"),
+            ("h7", "h6"),
+            ("b4", "b5"),
+            ("h6", "h5"),
+            ("b5", "b6"),
+            ("h5", "h4"),
+            ("b6", "a7"),
+            ("h8", "h7"),
+            ("e2", "e4"),
+            ("h7", "h6"),
+            ("e4", "e5"),
+            ("h6", "h5"),
+            ("g1", "f3"),
+            ("h5", "h6"),
+            ("f1", "c4"),
+            ("d7", "d5"),
+        ] {
+            let movement: Movement = board
+                .gen_legal_moves()
+                .into_iter()
+                .find(|movement| movement.from == square(from) && movement.to == square(to))
+                .unwrap();
+            board.make_move(&movement);
+        }
+        let moves: Vec<Movement> = board.gen_moves();
+        let (captures, quiets) = (board.gen_captures(), board.gen_quiets());
+        assert_eq!(captures.len() + quiets.len(), moves.len());
+        assert!(captures.iter().all(|movement| !movement.is_quiet()));
+        assert!(quiets.iter().all(Movement::is_quiet));
+        assert!(moves.iter().all(|movement| board.is_pseudo_legal(movement)
+            && (captures.contains(movement) || quiets.contains(movement))));
+        let rook: Movement = Movement {
+            from: Square::new(1, 1),
+            to: Square::new(3, 2),
+            capture: None,
+            promotion: None,
+        };
+        assert!(!board.is_pseudo_legal(&rook));
+    }
+
+    #[test]
+    fn gen_moves_in_square_order() {
+        let moves: Vec<(Square, Square)> = Board::new()
+            .gen_moves()
+            .iter()
+            .map(|movement| (movement.from, movement.to))
+            .collect();
+        let mut expected: Vec<(Square, Square)> = vec![
+            (Square::new(1, 2), Square::new(3, 3)),
+            (Square::new(1, 2), Square::new(3, 1)),
+            (Square::new(1, 7), Square::new(3, 8)),
+            (Square::new(1, 7), Square::new(3, 6)),
+        ];
+        for file in 1..=8 {
+            expected.push((Square::new(2, file), Square::new(3, file)));
+            expected.push((Square::new(2, file), Square::new(4, file)));
+        }
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn search_is_reproducible() {
+        let limits: Limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+        let search =
+            || -> SearchResult { Searcher::new(1).search(&mut Board::new(), &limits, &mut |_| ()) };
+        let (first, second) = (search(), search());
+        assert_eq!(first.pv, second.pv);
+        assert_eq!(first.score, second.score);
+        assert_eq!(first.nodes, second.nodes);
+        // the same results in every run: the first of equal movements is the first in square order
+        let best = |fen: &str| -> Vec<String> {
+            let mut board: Board = Board::from_fen(fen).unwrap();
+            let result: SearchResult = Searcher::new(1).search(&mut board, &limits, &mut |_| ());
+            result.pv.iter().map(Movement::to_string).collect()
+        };
+        assert_eq!(best("6k1/5ppp/8/8/8/8/8/R3R1K1 w - - 0 1"), vec!["a1a8"]);
+        assert_eq!(
+            best("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"),
+            vec!["d2d4", "e5d4", "f3d4", "g8f6"]
+        );
+        let moves: Vec<String> = Board::from_fen("4k3/8/8/8/8/8/3P4/R3K1N1 w Q - 0 1")
+            .unwrap()
+            .gen_moves()
+            .iter()
+            .map(Movement::to_string)
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                "a1a2", "a1a3", "a1a4", "a1a5", "a1a6", "a1a7", "a1a8", "a1b1", "a1c1", "a1d1",
+                "e1f1", "e1e2", "e1d1", "e1f2", "e1c1", "g1h3", "g1f3", "g1e2", "d2d3", "d2d4"
+            ]
+        );
+    }
+}
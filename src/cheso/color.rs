@@ -2,7 +2,7 @@
 ///
 /// The player who moves first is referred to as White, and the player who moves second is referred to as Black.
 /// This enumeration is mainly used to indicate the color of pieces and the side to move.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum Color {
     White,
     Black,
@@ -16,4 +16,9 @@ impl Color {
             Color::White => Color::Black,
         }
     }
+
+    /// Get the index of the color (0 for white, 1 for black).
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
 }
@@ -0,0 +1,710 @@
+use crate::cheso::board::Board;
+use crate::cheso::book::{decode_move, encode_move, polyglot_key};
+use crate::cheso::movement::Movement;
+use crate::cheso::pattern::Pattern;
+use crate::cheso::pgn::{PgnGame, PgnReader, PgnWriter};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Games of a database, in PGN export format.
+const GAMES_FILE: &str = "games.pgn";
+
+/// Records of the games (offset in the games file, result and headers).
+const RECORDS_FILE: &str = "games.dat";
+
+/// Index of the positions of the games, sorted by key.
+const POSITIONS_FILE: &str = "positions.dat";
+
+/// Number of games whose positions are in the index (4 bytes, big endian).
+const INDEXED_FILE: &str = "indexed.dat";
+
+/// Size of an entry of the index of positions, in bytes.
+const POSITION_SIZE: usize = 16;
+
+/// Movement of a position entry for the last position of a game.
+const NO_MOVE: u16 = u16::MAX;
+
+/// Number of position entries kept in memory before merging them into the index.
+const MAX_PENDING: usize = 1 << 22;
+
+/// Identifier of a game of a database (its number, from 0).
+pub type GameId = u32;
+
+/// Record of a game: where its text is, with its result and the headers used to filter games.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameRecord {
+    pub offset: u64,
+    pub length: u32,
+    pub result: String,
+    pub white: String,
+    pub black: String,
+    pub date: String,
+    pub event: String,
+    pub eco: String,
+}
+
+/// Entry of the index of positions: a position of a game, by its Polyglot key,
+/// with the movement played from it (in Polyglot encoding).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PositionEntry {
+    pub key: u64,
+    pub game: GameId,
+    pub ply: u16,
+    pub movement: u16,
+}
+
+/// Filter of games by their headers.
+///
+/// Player names match any part of the name, ignoring case, and ECO codes match by prefix (e.g. `B2`).
+/// Dates (`YYYY.MM.DD`) are compared on the length of the bounds, so `2020` includes the whole year,
+/// and unknown parts of the dates of games count as zeros.
+#[derive(Clone, Debug, Default)]
+pub struct GameFilter {
+    pub player: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub eco: Option<String>,
+}
+
+/// Statistics of a movement played from a position: number of games and their results.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveStatistics {
+    pub movement: Movement,
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+/// Offline database of games.
+///
+/// A directory with the games in PGN, a record per game, and an index of the positions of their main lines,
+/// sorted by key so that a position is found by binary search in the file, without loading the index.
+/// Positions of new games are kept in memory until they are flushed, merging them into the index
+/// (at the latest when the database is dropped). Games added but not indexed, e.g. after a crash,
+/// are indexed again when the database is opened.
+pub struct Database {
+    directory: PathBuf,
+    records: Vec<GameRecord>,
+    pending: Vec<PositionEntry>,
+    games_length: u64,
+}
+
+impl GameRecord {
+    /// Make the record of a game.
+    fn new(game: &PgnGame, offset: u64, length: u32) -> GameRecord {
+        let tag = |name: &str| -> String { game.tag(name).unwrap_or_default().to_string() };
+        GameRecord {
+            offset,
+            length,
+            result: game.result.clone(),
+            white: tag("White"),
+            black: tag("Black"),
+            date: tag("Date"),
+            event: tag("Event"),
+            eco: tag("ECO"),
+        }
+    }
+
+    /// Write the record: offset and length, then each text with its length.
+    fn write(&self, output: &mut impl Write) -> std::io::Result<()> {
+        output.write_all(&self.offset.to_be_bytes())?;
+        output.write_all(&self.length.to_be_bytes())?;
+        for text in [
+            &self.result,
+            &self.white,
+            &self.black,
+            &self.date,
+            &self.event,
+            &self.eco,
+        ] {
+            output.write_all(&(text.len() as u16).to_be_bytes())?;
+            output.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a record, or `None` at the end of the input.
+    fn read(input: &mut impl Read) -> std::io::Result<Option<GameRecord>> {
+        let mut offset: [u8; 8] = [0; 8];
+        match input.read_exact(&mut offset) {
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut length: [u8; 4] = [0; 4];
+        input.read_exact(&mut length)?;
+        let mut texts: Vec<String> = Vec::new();
+        for _ in 0..6 {
+            let mut size: [u8; 2] = [0; 2];
+            input.read_exact(&mut size)?;
+            let mut bytes: Vec<u8> = vec![0; u16::from_be_bytes(size) as usize];
+            input.read_exact(&mut bytes)?;
+            texts.push(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        let mut texts = texts.into_iter();
+        let mut next = || texts.next().unwrap_or_default();
+        Ok(Some(GameRecord {
+            offset: u64::from_be_bytes(offset),
+            length: u32::from_be_bytes(length),
+            result: next(),
+            white: next(),
+            black: next(),
+            date: next(),
+            event: next(),
+            eco: next(),
+        }))
+    }
+}
+
+impl PositionEntry {
+    /// Write the entry as 16 bytes (big endian).
+    fn to_bytes(self) -> [u8; POSITION_SIZE] {
+        let mut bytes: [u8; POSITION_SIZE] = [0; POSITION_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.game.to_be_bytes());
+        bytes[12..14].copy_from_slice(&self.ply.to_be_bytes());
+        bytes[14..16].copy_from_slice(&self.movement.to_be_bytes());
+        bytes
+    }
+
+    /// Read an entry from its 16 bytes (big endian).
+    fn from_bytes(bytes: &[u8; POSITION_SIZE]) -> PositionEntry {
+        PositionEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            game: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+            ply: u16::from_be_bytes(bytes[12..14].try_into().unwrap()),
+            movement: u16::from_be_bytes(bytes[14..16].try_into().unwrap()),
+        }
+    }
+
+    /// Read the next entry, or `None` at the end of the input.
+    fn read(input: &mut impl Read) -> std::io::Result<Option<PositionEntry>> {
+        let mut bytes: [u8; POSITION_SIZE] = [0; POSITION_SIZE];
+        match input.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(PositionEntry::from_bytes(&bytes))),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl GameFilter {
+    /// Check if a game matches the filter.
+    pub fn matches(&self, record: &GameRecord) -> bool {
+        let contains = |name: &str, part: &Option<String>| -> bool {
+            part.as_ref()
+                .is_none_or(|part| name.to_lowercase().contains(&part.to_lowercase()))
+        };
+        let date: String = record.date.replace('?', "0");
+        let prefix = |bound: &str| -> String { date.chars().take(bound.len()).collect() };
+        contains(&record.white, &self.white)
+            && contains(&record.black, &self.black)
+            && (contains(&record.white, &self.player) || contains(&record.black, &self.player))
+            && self
+                .date_from
+                .as_ref()
+                .is_none_or(|from| prefix(from) >= *from)
+            && self.date_to.as_ref().is_none_or(|to| prefix(to) <= *to)
+            && self
+                .eco
+                .as_ref()
+                .is_none_or(|eco| record.eco.starts_with(eco.as_str()))
+    }
+}
+
+impl Database {
+    /// Open a database, creating its directory if needed.
+    pub fn open(directory: &Path) -> Result<Database, String> {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| format!("cannot create {}: {}", directory.display(), error))?;
+        let mut database: Database = Database {
+            directory: directory.to_path_buf(),
+            records: Vec::new(),
+            pending: Vec::new(),
+            games_length: 0,
+        };
+        if let Ok(file) = File::open(database.path(RECORDS_FILE)) {
+            let mut input: BufReader<File> = BufReader::new(file);
+            while let Some(record) =
+                GameRecord::read(&mut input).map_err(|error| database.error(RECORDS_FILE, error))?
+            {
+                database.records.push(record);
+            }
+        }
+        database.games_length =
+            std::fs::metadata(database.path(GAMES_FILE)).map_or(0, |metadata| metadata.len());
+        let indexed: usize = match std::fs::read(database.path(INDEXED_FILE)) {
+            Ok(bytes) => bytes
+                .try_into()
+                .map(|bytes: [u8; 4]| u32::from_be_bytes(bytes) as usize)
+                .map_err(|_| format!("{}: invalid count", database.path(INDEXED_FILE).display()))?,
+            Err(_) => 0,
+        };
+        if indexed < database.records.len() {
+            let mut file: File = File::open(database.path(GAMES_FILE))
+                .map_err(|error| database.error(GAMES_FILE, error))?;
+            for id in indexed as GameId..database.records.len() as GameId {
+                let game: PgnGame = database.read_game(&mut file, id)?;
+                let entries: Vec<PositionEntry> = Database::entries(&game, id)?;
+                database.pending.extend(entries);
+            }
+        }
+        Ok(database)
+    }
+
+    /// Get the path of a file of the database.
+    fn path(&self, name: &str) -> PathBuf {
+        self.directory.join(name)
+    }
+
+    /// Make an error about a file of the database.
+    fn error(&self, name: &str, error: std::io::Error) -> String {
+        format!("{}: {}", self.path(name).display(), error)
+    }
+
+    /// Get the number of games.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Check if there are no games.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Get the record of a game.
+    pub fn record(&self, id: GameId) -> Option<&GameRecord> {
+        self.records.get(id as usize)
+    }
+
+    /// Get the records of the games, by identifier.
+    pub fn records(&self) -> &[GameRecord] {
+        &self.records
+    }
+
+    /// Read a game.
+    pub fn game(&self, id: GameId) -> Result<PgnGame, String> {
+        let mut file: File =
+            File::open(self.path(GAMES_FILE)).map_err(|error| self.error(GAMES_FILE, error))?;
+        self.read_game(&mut file, id)
+    }
+
+    /// Read a game from the games file.
+    fn read_game(&self, file: &mut File, id: GameId) -> Result<PgnGame, String> {
+        let record: &GameRecord = self.record(id).ok_or(format!("no game {}", id))?;
+        let mut text: Vec<u8> = vec![0; record.length as usize];
+        file.seek(SeekFrom::Start(record.offset))
+            .and_then(|_| file.read_exact(&mut text))
+            .map_err(|error| self.error(GAMES_FILE, error))?;
+        match PgnReader::new(text.as_slice()).next() {
+            Some(game) => game.map_err(|error| format!("game {}: {}", id, error)),
+            None => Err(format!("game {}: empty", id)),
+        }
+    }
+
+    /// Add a game, indexing the positions of its main line.
+    ///
+    /// The game and its record are written at once, and its positions are kept until the next flush.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<GameId, String> {
+        let id: GameId = self.records.len() as GameId;
+        let entries: Vec<PositionEntry> = Database::entries(game, id)?;
+        let text: String = PgnWriter::new().write(game)?;
+        let record: GameRecord = GameRecord::new(game, self.games_length, text.len() as u32);
+        let mut bytes: Vec<u8> = Vec::new();
+        record
+            .write(&mut bytes)
+            .map_err(|error| self.error(RECORDS_FILE, error))?;
+        self.append(GAMES_FILE, text.as_bytes())?;
+        // the offsets of the next games must stay right even if the record can not be written
+        self.games_length += text.len() as u64;
+        self.append(RECORDS_FILE, &bytes)?;
+        self.records.push(record);
+        self.pending.extend(entries);
+        if self.pending.len() >= MAX_PENDING {
+            self.flush()?;
+        }
+        Ok(id)
+    }
+
+    /// Get the position entries of the main line of a game.
+    fn entries(game: &PgnGame, id: GameId) -> Result<Vec<PositionEntry>, String> {
+        let mut board: Board = game.board()?;
+        let mut entries: Vec<PositionEntry> = Vec::new();
+        for (ply, node) in game.moves.iter().enumerate() {
+            entries.push(PositionEntry {
+                key: polyglot_key(&board),
+                game: id,
+                ply: ply as u16,
+                movement: encode_move(&board, &node.movement),
+            });
+            board.make_move(&node.movement);
+        }
+        entries.push(PositionEntry {
+            key: polyglot_key(&board),
+            game: id,
+            ply: game.moves.len() as u16,
+            movement: NO_MOVE,
+        });
+        Ok(entries)
+    }
+
+    /// Append bytes to a file of the database.
+    fn append(&self, name: &str, bytes: &[u8]) -> Result<(), String> {
+        File::options()
+            .create(true)
+            .append(true)
+            .open(self.path(name))
+            .and_then(|mut file| file.write_all(bytes))
+            .map_err(|error| self.error(name, error))
+    }
+
+    /// Merge the positions of the new games into the index, then write the number of indexed games.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut pending: Vec<PositionEntry> = std::mem::take(&mut self.pending);
+        pending.sort();
+        let merged: PathBuf = self.path("positions.tmp");
+        let write_error = |error: std::io::Error| self.error("positions.tmp", error);
+        let read_error = |error: std::io::Error| self.error(POSITIONS_FILE, error);
+        let mut output: BufWriter<File> =
+            BufWriter::new(File::create(&merged).map_err(write_error)?);
+        let mut old: Box<dyn Read> = match File::open(self.path(POSITIONS_FILE)) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(_) => Box::new(std::io::empty()),
+        };
+        // entries already in the index (of games indexed again after a crash) are written once
+        let mut last: Option<PositionEntry> = None;
+        let mut write = |entry: PositionEntry| -> Result<(), String> {
+            if last != Some(entry) {
+                output.write_all(&entry.to_bytes()).map_err(write_error)?;
+                last = Some(entry);
+            }
+            Ok(())
+        };
+        let mut current: Option<PositionEntry> =
+            PositionEntry::read(&mut old).map_err(read_error)?;
+        for entry in pending {
+            while let Some(previous) = current.filter(|previous| *previous <= entry) {
+                write(previous)?;
+                current = PositionEntry::read(&mut old).map_err(read_error)?;
+            }
+            write(entry)?;
+        }
+        while let Some(previous) = current {
+            write(previous)?;
+            current = PositionEntry::read(&mut old).map_err(read_error)?;
+        }
+        output.flush().map_err(write_error)?;
+        std::fs::rename(&merged, self.path(POSITIONS_FILE))
+            .map_err(|error| self.error(POSITIONS_FILE, error))?;
+        std::fs::write(
+            self.path(INDEXED_FILE),
+            (self.records.len() as u32).to_be_bytes(),
+        )
+        .map_err(|error| self.error(INDEXED_FILE, error))
+    }
+
+    /// Find the entries of a position, in the index and among the positions not flushed yet.
+    pub fn positions(&self, board: &Board) -> Result<Vec<PositionEntry>, String> {
+        let key: u64 = polyglot_key(board);
+        let mut entries: Vec<PositionEntry> = self
+            .pending
+            .iter()
+            .filter(|entry| entry.key == key)
+            .copied()
+            .collect();
+        let mut file: File = match File::open(self.path(POSITIONS_FILE)) {
+            Ok(file) => file,
+            Err(_) => return Ok(entries),
+        };
+        let error = |error: std::io::Error| self.error(POSITIONS_FILE, error);
+        let count: u64 = file.metadata().map_err(error)?.len() / POSITION_SIZE as u64;
+        // binary search of the first entry of the key
+        let (mut low, mut high): (u64, u64) = (0, count);
+        while low < high {
+            let middle: u64 = (low + high) / 2;
+            file.seek(SeekFrom::Start(middle * POSITION_SIZE as u64))
+                .map_err(error)?;
+            match PositionEntry::read(&mut file).map_err(error)? {
+                Some(entry) if entry.key < key => low = middle + 1,
+                _ => high = middle,
+            }
+        }
+        file.seek(SeekFrom::Start(low * POSITION_SIZE as u64))
+            .map_err(error)?;
+        let mut input: BufReader<File> = BufReader::new(file);
+        while let Some(entry) = PositionEntry::read(&mut input).map_err(error)? {
+            if entry.key != key {
+                break;
+            }
+            entries.push(entry);
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Get the games reaching a position and matching a filter, with the first ply at which they reach it.
+    pub fn games_reaching(
+        &self,
+        board: &Board,
+        filter: &GameFilter,
+    ) -> Result<Vec<(GameId, u16)>, String> {
+        let mut games: Vec<(GameId, u16)> = Vec::new();
+        for entry in self.positions(board)? {
+            let matches: bool = self
+                .record(entry.game)
+                .is_some_and(|record| filter.matches(record));
+            if matches && games.last().is_none_or(|(game, _)| *game != entry.game) {
+                games.push((entry.game, entry.ply));
+            }
+        }
+        Ok(games)
+    }
+
+    /// Get the statistics of the movements played from a position in the games matching a filter,
+    /// the most played first.
+    pub fn explore(
+        &self,
+        board: &Board,
+        filter: &GameFilter,
+    ) -> Result<Vec<MoveStatistics>, String> {
+        let mut statistics: HashMap<u16, MoveStatistics> = HashMap::new();
+        let mut seen: HashSet<(GameId, u16)> = HashSet::new();
+        let mut board: Board = board.clone();
+        for entry in self.positions(&board)? {
+            let record: &GameRecord = match self.record(entry.game) {
+                Some(record) if filter.matches(record) => record,
+                _ => continue,
+            };
+            if entry.movement == NO_MOVE || !seen.insert((entry.game, entry.movement)) {
+                continue;
+            }
+            let movement: Movement = match statistics.get(&entry.movement) {
+                Some(statistics) => statistics.movement,
+                None => match decode_move(&mut board, entry.movement) {
+                    Some(movement) => movement,
+                    None => continue,
+                },
+            };
+            let statistics: &mut MoveStatistics =
+                statistics.entry(entry.movement).or_insert(MoveStatistics {
+                    movement,
+                    games: 0,
+                    white_wins: 0,
+                    draws: 0,
+                    black_wins: 0,
+                });
+            statistics.games += 1;
+            match record.result.as_str() {
+                "1-0" => statistics.white_wins += 1,
+                "0-1" => statistics.black_wins += 1,
+                "1/2-1/2" => statistics.draws += 1,
+                _ => (),
+            }
+        }
+        let mut statistics: Vec<MoveStatistics> = statistics.into_values().collect();
+        statistics.sort_by(|a, b| {
+            b.games
+                .cmp(&a.games)
+                .then(a.movement.to_string().cmp(&b.movement.to_string()))
+        });
+        Ok(statistics)
+    }
+
+    /// Get the positions matching a pattern in the main lines of the games matching a filter,
+    /// replaying the games.
+    ///
+    /// Each run of consecutive matching positions of a game gives its first ply.
+    pub fn find(
+        &self,
+        pattern: &Pattern,
+        filter: &GameFilter,
+    ) -> Result<Vec<(GameId, u16)>, String> {
+        let mut file: File =
+            File::open(self.path(GAMES_FILE)).map_err(|error| self.error(GAMES_FILE, error))?;
+        let mut found: Vec<(GameId, u16)> = Vec::new();
+        for id in self.search(filter) {
+            let game: PgnGame = self.read_game(&mut file, id)?;
+            let mut board: Board = game.board()?;
+            let mut matched: bool = false;
+            for ply in 0..=game.moves.len() {
+                let matches: bool = pattern.matches(&board);
+                if matches && !matched {
+                    found.push((id, ply as u16));
+                }
+                matched = matches;
+                if let Some(node) = game.moves.get(ply) {
+                    board.make_move(&node.movement);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Get the games matching a filter.
+    pub fn search(&self, filter: &GameFilter) -> Vec<GameId> {
+        (0..self.records.len() as GameId)
+            .filter(|id| {
+                self.record(*id)
+                    .is_some_and(|record| filter.matches(record))
+            })
+            .collect()
+    }
+}
+
+impl Drop for Database {
+    /// Flush the positions of the new games, ignoring errors (they are indexed again on the next opening).
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Games of the test database: two games reach the position after 1. e4 e5 2. Nf3 by transposition.
+    const GAMES: &str = "\
+[White \"Anna\"]\n[Black \"Bruno\"]\n[Date \"2020.05.01\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n\
+[White \"Bruno\"]\n[Black \"Carla\"]\n[Date \"2021.03.04\"]\n[Result \"1/2-1/2\"]\n\n1. Nf3 e5 2. e4 d6 1/2-1/2\n\n\
+[White \"Carla\"]\n[Black \"Anna\"]\n[Date \"2022.11.12\"]\n[Result \"0-1\"]\n\n1. d4 d5 0-1\n";
+
+    /// Play movements in SAN from the initial position.
+    fn play(moves: &[&str]) -> Board {
+        let mut board: Board = Board::new();
+        for text in moves {
+            let movement: Movement = board.parse_san(text).unwrap();
+            board.make_move(&movement);
+        }
+        board
+    }
+
+    /// Get the statistics of the movements from a position as (SAN, games, white wins, draws, black wins).
+    fn explore(database: &Database, board: &Board) -> Vec<(String, u32, u32, u32, u32)> {
+        let mut board: Board = board.clone();
+        database
+            .explore(&board, &GameFilter::default())
+            .unwrap()
+            .iter()
+            .map(|statistics| {
+                (
+                    board.to_san(&statistics.movement),
+                    statistics.games,
+                    statistics.white_wins,
+                    statistics.draws,
+                    statistics.black_wins,
+                )
+            })
+            .collect()
+    }
+
+    /// Check the counts of the test database.
+    fn check(database: &Database) {
+        assert_eq!(database.len(), 3);
+        let start: Vec<(String, u32, u32, u32, u32)> = explore(database, &Board::new());
+        assert_eq!(
+            start,
+            vec![
+                (String::from("d4"), 1, 0, 0, 1),
+                (String::from("e4"), 1, 1, 0, 0),
+                (String::from("Nf3"), 1, 0, 1, 0),
+            ]
+        );
+        let transposed: Board = play(&["e4", "e5", "Nf3"]);
+        assert_eq!(
+            database
+                .games_reaching(&transposed, &GameFilter::default())
+                .unwrap(),
+            vec![(0, 3), (1, 3)]
+        );
+        assert_eq!(
+            explore(database, &transposed),
+            vec![
+                (String::from("Nc6"), 1, 1, 0, 0),
+                (String::from("d6"), 1, 0, 1, 0)
+            ]
+        );
+        let filter: GameFilter = GameFilter {
+            player: Some(String::from("anna")),
+            date_from: Some(String::from("2021")),
+            ..GameFilter::default()
+        };
+        assert_eq!(database.search(&filter), vec![2]);
+        assert_eq!(database.game(1).unwrap().tag("White"), Some("Bruno"));
+    }
+
+    #[test]
+    fn import_explore_and_reopen() {
+        let directory: PathBuf =
+            std::env::temp_dir().join(format!("cheso-db-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut database: Database = Database::open(&directory).unwrap();
+        for game in PgnReader::new(GAMES.as_bytes()) {
+            database.add_game(&game.unwrap()).unwrap();
+        }
+        // positions not flushed yet are found too
+        check(&database);
+        database.flush().unwrap();
+        check(&database);
+        check(&Database::open(&directory).unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn games_not_flushed_are_indexed() {
+        let directory: PathBuf =
+            std::env::temp_dir().join(format!("cheso-db-flush-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        // dropping the database flushes its positions
+        let mut database: Database = Database::open(&directory).unwrap();
+        for game in PgnReader::new(GAMES.as_bytes()).take(2) {
+            database.add_game(&game.unwrap()).unwrap();
+        }
+        drop(database);
+        let database: Database = Database::open(&directory).unwrap();
+        assert!(database.pending.is_empty());
+        assert_eq!(
+            database
+                .games_reaching(&play(&["e4", "e5", "Nf3"]), &GameFilter::default())
+                .unwrap(),
+            vec![(0, 3), (1, 3)]
+        );
+        // a game added without flushing (as in a crash) is indexed again on opening
+        let mut database: Database = Database::open(&directory).unwrap();
+        for game in PgnReader::new(GAMES.as_bytes()).skip(2) {
+            database.add_game(&game.unwrap()).unwrap();
+        }
+        std::mem::forget(database);
+        let database: Database = Database::open(&directory).unwrap();
+        assert_eq!(database.pending.len(), 3);
+        check(&database);
+        drop(database);
+        let database: Database = Database::open(&directory).unwrap();
+        assert!(database.pending.is_empty());
+        check(&database);
+        drop(database);
+        // a crash after merging the index but before writing the count indexes the games again, without duplicates
+        let length = || {
+            std::fs::metadata(directory.join(POSITIONS_FILE))
+                .unwrap()
+                .len()
+        };
+        let indexed: u64 = length();
+        std::fs::remove_file(directory.join(INDEXED_FILE)).unwrap();
+        let database: Database = Database::open(&directory).unwrap();
+        assert_eq!(
+            database.pending.len() as u64,
+            indexed / POSITION_SIZE as u64
+        );
+        drop(database);
+        assert_eq!(length(), indexed);
+        check(&Database::open(&directory).unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+use crate::cheso::board::Board;
+use crate::cheso::book::polyglot_key;
+use crate::cheso::movement::Movement;
+use crate::cheso::pgn::PgnGame;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Openings of the ECO classification: code, opening, variation (empty for none) and movements in SAN.
+///
+/// The table is a selection of about 230 common lines, not the full A00-E99 classification: a game leaving it
+/// keeps the opening of its deepest known position. A position reached by several lines gets the first one.
+#[rustfmt::skip]
+const OPENINGS: &[(&str, &str, &str, &str)] = &[
+    ("A00", "Polish Opening", "", "b4"),
+    ("A00", "Grob Opening", "", "g4"),
+    ("A00", "Van Geet Opening", "", "Nc3"),
+    ("A00", "Hungarian Opening", "", "g3"),
+    ("A00", "Anderssen Opening", "", "a3"),
+    ("A00", "Van 't Kruijs Opening", "", "e3"),
+    ("A00", "Mieses Opening", "", "d3"),
+    ("A01", "Nimzo-Larsen Attack", "", "b3"),
+    ("A02", "Bird Opening", "", "f4"),
+    ("A02", "Bird Opening", "From's Gambit", "f4 e5"),
+    ("A03", "Bird Opening", "Dutch Variation", "f4 d5"),
+    ("A04", "Reti Opening", "", "Nf3"),
+    ("A05", "Reti Opening", "", "Nf3 Nf6"),
+    ("A06", "Reti Opening", "", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "", "Nf3 d5 g3"),
+    ("A09", "Reti Opening", "", "Nf3 d5 c4"),
+    ("A10", "English Opening", "", "c4"),
+    ("A13", "English Opening", "Agincourt Defense", "c4 e6"),
+    ("A15", "English Opening", "Anglo-Indian Defense", "c4 Nf6"),
+    ("A16", "English Opening", "Anglo-Indian Defense", "c4 Nf6 Nc3"),
+    ("A20", "English Opening", "King's English Variation", "c4 e5"),
+    ("A21", "English Opening", "King's English Variation", "c4 e5 Nc3"),
+    ("A22", "English Opening", "King's English Variation", "c4 e5 Nc3 Nf6"),
+    ("A25", "English Opening", "Sicilian Reversed", "c4 e5 Nc3 Nc6"),
+    ("A30", "English Opening", "Symmetrical Variation", "c4 c5"),
+    ("A34", "English Opening", "Symmetrical Variation", "c4 c5 Nc3"),
+    ("A40", "Queen's Pawn Game", "", "d4"),
+    ("A40", "Englund Gambit", "", "d4 e5"),
+    ("A41", "Queen's Pawn Game", "", "d4 d6"),
+    ("A43", "Benoni Defense", "Old Benoni", "d4 c5"),
+    ("A45", "Indian Defense", "", "d4 Nf6"),
+    ("A45", "Trompowsky Attack", "", "d4 Nf6 Bg5"),
+    ("A46", "Indian Defense", "", "d4 Nf6 Nf3"),
+    ("A48", "Indian Defense", "East Indian Defense", "d4 Nf6 Nf3 g6"),
+    ("A50", "Indian Defense", "", "d4 Nf6 c4"),
+    ("A51", "Budapest Defense", "", "d4 Nf6 c4 e5"),
+    ("A53", "Old Indian Defense", "", "d4 Nf6 c4 d6"),
+    ("A56", "Benoni Defense", "", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Benoni Defense", "Modern Variation", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defense", "", "d4 f5"),
+    ("A81", "Dutch Defense", "", "d4 f5 g3"),
+    ("A83", "Dutch Defense", "Staunton Gambit", "d4 f5 e4"),
+    ("A84", "Dutch Defense", "", "d4 f5 c4"),
+    ("A87", "Dutch Defense", "Leningrad Variation", "d4 f5 c4 Nf6 g3 g6 Bg2 Bg7 Nf3"),
+    ("A90", "Dutch Defense", "Classical Variation", "d4 f5 c4 Nf6 g3 e6 Bg2"),
+    ("B00", "Nimzowitsch Defense", "", "e4 Nc6"),
+    ("B00", "Owen Defense", "", "e4 b6"),
+    ("B00", "St. George Defense", "", "e4 a6"),
+    ("B00", "King's Pawn Game", "", "e4"),
+    ("B01", "Scandinavian Defense", "", "e4 d5"),
+    ("B01", "Scandinavian Defense", "Mieses-Kotroc Variation", "e4 d5 exd5 Qxd5"),
+    ("B01", "Scandinavian Defense", "Modern Variation", "e4 d5 exd5 Nf6"),
+    ("B02", "Alekhine Defense", "", "e4 Nf6"),
+    ("B03", "Alekhine Defense", "", "e4 Nf6 e5 Nd5 d4"),
+    ("B04", "Alekhine Defense", "Modern Variation", "e4 Nf6 e5 Nd5 d4 d6 Nf3"),
+    ("B06", "Modern Defense", "", "e4 g6"),
+    ("B07", "Pirc Defense", "", "e4 d6 d4 Nf6"),
+    ("B08", "Pirc Defense", "Classical Variation", "e4 d6 d4 Nf6 Nc3 g6 Nf3"),
+    ("B09", "Pirc Defense", "Austrian Attack", "e4 d6 d4 Nf6 Nc3 g6 f4"),
+    ("B10", "Caro-Kann Defense", "", "e4 c6"),
+    ("B12", "Caro-Kann Defense", "", "e4 c6 d4 d5"),
+    ("B12", "Caro-Kann Defense", "Advance Variation", "e4 c6 d4 d5 e5"),
+    ("B13", "Caro-Kann Defense", "Exchange Variation", "e4 c6 d4 d5 exd5 cxd5"),
+    ("B13", "Caro-Kann Defense", "Panov Attack", "e4 c6 d4 d5 exd5 cxd5 c4"),
+    ("B15", "Caro-Kann Defense", "", "e4 c6 d4 d5 Nc3"),
+    ("B15", "Caro-Kann Defense", "", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nf6"),
+    ("B16", "Caro-Kann Defense", "Bronstein-Larsen Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nf6 Nxf6+ gxf6"),
+    ("B17", "Caro-Kann Defense", "Karpov Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7"),
+    ("B18", "Caro-Kann Defense", "Classical Variation", "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5"),
+    ("B20", "Sicilian Defense", "", "e4 c5"),
+    ("B21", "Sicilian Defense", "Smith-Morra Gambit", "e4 c5 d4 cxd4 c3"),
+    ("B22", "Sicilian Defense", "Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense", "Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defense", "", "e4 c5 Nf3"),
+    ("B27", "Sicilian Defense", "Hyperaccelerated Dragon", "e4 c5 Nf3 g6"),
+    ("B30", "Sicilian Defense", "Old Sicilian", "e4 c5 Nf3 Nc6"),
+    ("B30", "Sicilian Defense", "Rossolimo Variation", "e4 c5 Nf3 Nc6 Bb5"),
+    ("B32", "Sicilian Defense", "Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4"),
+    ("B33", "Sicilian Defense", "Four Knights Variation", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6"),
+    ("B33", "Sicilian Defense", "Sveshnikov Variation", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5"),
+    ("B34", "Sicilian Defense", "Accelerated Dragon", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6"),
+    ("B36", "Sicilian Defense", "Accelerated Dragon, Maroczy Bind", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6 c4"),
+    ("B40", "Sicilian Defense", "French Variation", "e4 c5 Nf3 e6"),
+    ("B41", "Sicilian Defense", "Kan Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6"),
+    ("B44", "Sicilian Defense", "Taimanov Variation", "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6"),
+    ("B50", "Sicilian Defense", "Modern Variations", "e4 c5 Nf3 d6"),
+    ("B51", "Sicilian Defense", "Moscow Variation", "e4 c5 Nf3 d6 Bb5+"),
+    ("B53", "Sicilian Defense", "Chekhover Variation", "e4 c5 Nf3 d6 d4 cxd4 Qxd4"),
+    ("B54", "Sicilian Defense", "Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    ("B55", "Sicilian Defense", "Prins Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 f3"),
+    ("B56", "Sicilian Defense", "Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3"),
+    ("B56", "Sicilian Defense", "Classical Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6"),
+    ("B57", "Sicilian Defense", "Sozin Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6 Bc4"),
+    ("B60", "Sicilian Defense", "Richter-Rauzer Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6 Bg5"),
+    ("B70", "Sicilian Defense", "Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+    ("B72", "Sicilian Defense", "Dragon Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3"),
+    ("B75", "Sicilian Defense", "Dragon Variation, Yugoslav Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3"),
+    ("B80", "Sicilian Defense", "Scheveningen Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6"),
+    ("B90", "Sicilian Defense", "Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+    ("B90", "Sicilian Defense", "Najdorf Variation, English Attack", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3"),
+    ("B92", "Sicilian Defense", "Najdorf Variation, Opocensky Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2"),
+    ("B94", "Sicilian Defense", "Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5"),
+    ("B96", "Sicilian Defense", "Najdorf Variation", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5 e6 f4"),
+    ("C00", "French Defense", "", "e4 e6"),
+    ("C01", "French Defense", "Exchange Variation", "e4 e6 d4 d5 exd5 exd5"),
+    ("C02", "French Defense", "Advance Variation", "e4 e6 d4 d5 e5"),
+    ("C03", "French Defense", "Tarrasch Variation", "e4 e6 d4 d5 Nd2"),
+    ("C05", "French Defense", "Tarrasch Variation, Closed", "e4 e6 d4 d5 Nd2 Nf6"),
+    ("C07", "French Defense", "Tarrasch Variation, Open", "e4 e6 d4 d5 Nd2 c5"),
+    ("C10", "French Defense", "Paulsen Variation", "e4 e6 d4 d5 Nc3"),
+    ("C10", "French Defense", "Rubinstein Variation", "e4 e6 d4 d5 Nc3 dxe4"),
+    ("C11", "French Defense", "Classical Variation", "e4 e6 d4 d5 Nc3 Nf6"),
+    ("C11", "French Defense", "Steinitz Variation", "e4 e6 d4 d5 Nc3 Nf6 e5"),
+    ("C12", "French Defense", "MacCutcheon Variation", "e4 e6 d4 d5 Nc3 Nf6 Bg5 Bb4"),
+    ("C13", "French Defense", "Classical Variation", "e4 e6 d4 d5 Nc3 Nf6 Bg5"),
+    ("C15", "French Defense", "Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4"),
+    ("C16", "French Defense", "Winawer Variation, Advance Variation", "e4 e6 d4 d5 Nc3 Bb4 e5"),
+    ("C17", "French Defense", "Winawer Variation, Advance Variation", "e4 e6 d4 d5 Nc3 Bb4 e5 c5"),
+    ("C18", "French Defense", "Winawer Variation", "e4 e6 d4 d5 Nc3 Bb4 e5 c5 a3"),
+    ("C20", "King's Pawn Game", "", "e4 e5"),
+    ("C21", "Center Game", "", "e4 e5 d4 exd4"),
+    ("C22", "Center Game", "", "e4 e5 d4 exd4 Qxd4"),
+    ("C23", "Bishop's Opening", "", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "", "e4 e5 Nc3"),
+    ("C26", "Vienna Game", "Falkbeer Variation", "e4 e5 Nc3 Nf6"),
+    ("C29", "Vienna Game", "Vienna Gambit", "e4 e5 Nc3 Nf6 f4"),
+    ("C30", "King's Gambit", "", "e4 e5 f4"),
+    ("C30", "King's Gambit Declined", "Classical Variation", "e4 e5 f4 Bc5"),
+    ("C31", "King's Gambit Declined", "Falkbeer Countergambit", "e4 e5 f4 d5"),
+    ("C33", "King's Gambit Accepted", "", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "", "e4 e5 Nf3"),
+    ("C40", "Latvian Gambit", "", "e4 e5 Nf3 f5"),
+    ("C41", "Philidor Defense", "", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "", "e4 e5 Nf3 Nf6"),
+    ("C43", "Petrov's Defense", "Modern Attack", "e4 e5 Nf3 Nf6 d4"),
+    ("C44", "King's Pawn Game", "", "e4 e5 Nf3 Nc6"),
+    ("C44", "Ponziani Opening", "", "e4 e5 Nf3 Nc6 c3"),
+    ("C44", "Scotch Game", "", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Opening", "", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C47", "Four Knights Game", "Scotch Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 d4"),
+    ("C48", "Four Knights Game", "Spanish Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5"),
+    ("C49", "Four Knights Game", "Symmetrical Variation", "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5 Bb4"),
+    ("C50", "Italian Game", "", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Italian Game", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Italian Game", "Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C53", "Italian Game", "Classical Variation", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+    ("C54", "Italian Game", "Classical Variation", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3 Nf6 d4"),
+    ("C55", "Italian Game", "Two Knights Defense", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C57", "Italian Game", "Two Knights Defense, Knight Attack", "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5"),
+    ("C60", "Ruy Lopez", "", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C60", "Ruy Lopez", "Cozio Defense", "e4 e5 Nf3 Nc6 Bb5 Nge7"),
+    ("C61", "Ruy Lopez", "Bird Variation", "e4 e5 Nf3 Nc6 Bb5 Nd4"),
+    ("C62", "Ruy Lopez", "Steinitz Defense", "e4 e5 Nf3 Nc6 Bb5 d6"),
+    ("C63", "Ruy Lopez", "Schliemann Defense", "e4 e5 Nf3 Nc6 Bb5 f5"),
+    ("C64", "Ruy Lopez", "Classical Variation", "e4 e5 Nf3 Nc6 Bb5 Bc5"),
+    ("C65", "Ruy Lopez", "Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C67", "Ruy Lopez", "Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4"),
+    ("C68", "Ruy Lopez", "Exchange Variation", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    ("C70", "Ruy Lopez", "Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+    ("C77", "Ruy Lopez", "Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6"),
+    ("C78", "Ruy Lopez", "Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O"),
+    ("C80", "Ruy Lopez", "Open", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4"),
+    ("C84", "Ruy Lopez", "Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7"),
+    ("C88", "Ruy Lopez", "Closed", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3"),
+    ("C89", "Ruy Lopez", "Marshall Attack", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5"),
+    ("D00", "Queen's Pawn Game", "", "d4 d5"),
+    ("D00", "Blackmar-Diemer Gambit", "", "d4 d5 e4"),
+    ("D02", "Queen's Pawn Game", "Zukertort Variation", "d4 d5 Nf3"),
+    ("D02", "London System", "", "d4 d5 Nf3 Nf6 Bf4"),
+    ("D04", "Queen's Pawn Game", "Colle System", "d4 d5 Nf3 Nf6 e3"),
+    ("D06", "Queen's Gambit", "", "d4 d5 c4"),
+    ("D07", "Queen's Gambit Declined", "Chigorin Defense", "d4 d5 c4 Nc6"),
+    ("D08", "Queen's Gambit Declined", "Albin Countergambit", "d4 d5 c4 e5"),
+    ("D10", "Slav Defense", "", "d4 d5 c4 c6"),
+    ("D11", "Slav Defense", "Modern Line", "d4 d5 c4 c6 Nf3"),
+    ("D12", "Slav Defense", "Quiet Variation", "d4 d5 c4 c6 Nf3 Nf6 e3 Bf5"),
+    ("D15", "Slav Defense", "Three Knights Variation", "d4 d5 c4 c6 Nf3 Nf6 Nc3"),
+    ("D16", "Slav Defense", "Alapin Variation", "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4"),
+    ("D17", "Slav Defense", "Czech Variation", "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4 Bf5"),
+    ("D18", "Slav Defense", "Dutch Variation", "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4 Bf5 e3"),
+    ("D20", "Queen's Gambit Accepted", "", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "", "d4 d5 c4 e6"),
+    ("D31", "Queen's Gambit Declined", "", "d4 d5 c4 e6 Nc3"),
+    ("D32", "Tarrasch Defense", "", "d4 d5 c4 e6 Nc3 c5"),
+    ("D35", "Queen's Gambit Declined", "", "d4 d5 c4 e6 Nc3 Nf6"),
+    ("D35", "Queen's Gambit Declined", "Exchange Variation", "d4 d5 c4 e6 Nc3 Nf6 cxd5 exd5"),
+    ("D37", "Queen's Gambit Declined", "", "d4 d5 c4 e6 Nc3 Nf6 Nf3"),
+    ("D38", "Queen's Gambit Declined", "Ragozin Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 Bb4"),
+    ("D40", "Queen's Gambit Declined", "Semi-Tarrasch Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c5"),
+    ("D43", "Semi-Slav Defense", "", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+    ("D43", "Semi-Slav Defense", "Moscow Variation", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 Bg5"),
+    ("D44", "Semi-Slav Defense", "Botvinnik Variation", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 Bg5 dxc4"),
+    ("D45", "Semi-Slav Defense", "", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 e3"),
+    ("D46", "Semi-Slav Defense", "", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 e3 Nbd7 Bd3"),
+    ("D47", "Semi-Slav Defense", "Meran Variation", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6 e3 Nbd7 Bd3 dxc4 Bxc4 b5"),
+    ("D50", "Queen's Gambit Declined", "", "d4 d5 c4 e6 Nc3 Nf6 Bg5"),
+    ("D53", "Queen's Gambit Declined", "", "d4 d5 c4 e6 Nc3 Nf6 Bg5 Be7"),
+    ("D70", "Neo-Grunfeld Defense", "", "d4 Nf6 c4 g6 f3 d5"),
+    ("D80", "Grunfeld Defense", "", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("D85", "Grunfeld Defense", "Exchange Variation", "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5"),
+    ("D90", "Grunfeld Defense", "Three Knights Variation", "d4 Nf6 c4 g6 Nc3 d5 Nf3"),
+    ("E00", "Indian Defense", "", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "", "d4 Nf6 c4 e6 g3"),
+    ("E10", "Indian Defense", "", "d4 Nf6 c4 e6 Nf3"),
+    ("E11", "Bogo-Indian Defense", "", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+    ("E12", "Queen's Indian Defense", "", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E15", "Queen's Indian Defense", "Fianchetto Variation", "d4 Nf6 c4 e6 Nf3 b6 g3"),
+    ("E20", "Nimzo-Indian Defense", "", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E21", "Nimzo-Indian Defense", "Three Knights Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Nf3"),
+    ("E24", "Nimzo-Indian Defense", "Samisch Variation", "d4 Nf6 c4 e6 Nc3 Bb4 a3"),
+    ("E30", "Nimzo-Indian Defense", "Leningrad Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Bg5"),
+    ("E32", "Nimzo-Indian Defense", "Classical Variation", "d4 Nf6 c4 e6 Nc3 Bb4 Qc2"),
+    ("E40", "Nimzo-Indian Defense", "Rubinstein Variation", "d4 Nf6 c4 e6 Nc3 Bb4 e3"),
+    ("E60", "King's Indian Defense", "", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defense", "", "d4 Nf6 c4 g6 Nc3"),
+    ("E62", "King's Indian Defense", "Fianchetto Variation", "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 d6 g3"),
+    ("E70", "King's Indian Defense", "Normal Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4"),
+    ("E71", "King's Indian Defense", "Makogonov Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 h3"),
+    ("E73", "King's Indian Defense", "Averbakh Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Be2 O-O Bg5"),
+    ("E76", "King's Indian Defense", "Four Pawns Attack", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4"),
+    ("E80", "King's Indian Defense", "Samisch Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3"),
+    ("E90", "King's Indian Defense", "Normal Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3"),
+    ("E91", "King's Indian Defense", "Orthodox Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2"),
+    ("E92", "King's Indian Defense", "Orthodox Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5"),
+    ("E97", "King's Indian Defense", "Mar del Plata Variation", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6"),
+];
+
+/// Opening of the ECO classification.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    pub variation: Option<&'static str>,
+}
+
+impl Opening {
+    /// Set the `ECO`, `Opening` and `Variation` tags of a game (removing the variation if there is none).
+    pub fn set_tags(&self, game: &mut PgnGame) {
+        game.set_tag("ECO", self.eco);
+        game.set_tag("Opening", self.name);
+        match self.variation {
+            Some(variation) => game.set_tag("Variation", variation),
+            None => game.remove_tag("Variation"),
+        }
+    }
+}
+
+static TABLE: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+
+/// Get the openings by the Polyglot key of their position, building the table on first use.
+fn table() -> &'static HashMap<u64, Opening> {
+    TABLE.get_or_init(build_table)
+}
+
+/// Build the table of openings, replaying their movements.
+fn build_table() -> HashMap<u64, Opening> {
+    let mut table: HashMap<u64, Opening> = HashMap::new();
+    for (eco, name, variation, moves) in OPENINGS {
+        let mut board: Board = Board::new();
+        let legal: bool = moves
+            .split_whitespace()
+            .all(|text| match board.parse_san(text) {
+                Some(movement) => {
+                    board.make_move(&movement);
+                    true
+                }
+                None => false,
+            });
+        if legal {
+            table.entry(polyglot_key(&board)).or_insert(Opening {
+                eco,
+                name,
+                variation: match variation.is_empty() {
+                    true => None,
+                    false => Some(variation),
+                },
+            });
+        }
+    }
+    table
+}
+
+/// Get the opening of a position, if it is in the table.
+pub fn opening(board: &Board) -> Option<Opening> {
+    table().get(&polyglot_key(board)).copied()
+}
+
+/// Classify a line: the opening of its deepest position in the table, so that transpositions are recognized.
+pub fn classify(board: &Board, moves: &[Movement]) -> Option<Opening> {
+    let mut board: Board = board.clone();
+    let mut found: Option<Opening> = opening(&board);
+    for movement in moves {
+        board.make_move(movement);
+        found = opening(&board).or(found);
+    }
+    found
+}
+
+/// Classify the main line of a game.
+pub fn classify_game(game: &PgnGame) -> Result<Option<Opening>, String> {
+    let moves: Vec<Movement> = game.moves.iter().map(|node| node.movement).collect();
+    Ok(classify(&game.board()?, &moves))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheso::pgn::PgnReader;
+
+    /// Classify a line of movements in SAN from the initial position.
+    fn classify_line(line: &str) -> Option<Opening> {
+        let mut board: Board = Board::new();
+        let mut moves: Vec<Movement> = Vec::new();
+        for text in line.split_whitespace() {
+            let movement: Movement = board.parse_san(text).unwrap();
+            board.make_move(&movement);
+            moves.push(movement);
+        }
+        classify(&Board::new(), &moves)
+    }
+
+    #[test]
+    fn table_lines_are_legal() {
+        assert_eq!(table().len(), OPENINGS.len());
+    }
+
+    #[test]
+    fn classify_transpositions() {
+        let opening: Opening = classify_line("Nf3 Nf6 c4 e6 d4 d5 Nc3 c6").unwrap();
+        assert_eq!((opening.eco, opening.name), ("D43", "Semi-Slav Defense"));
+        assert_eq!(opening.variation, None);
+        assert_eq!(classify_line("c4 e6 d4 Nf6 Nc3 Bb4").unwrap().eco, "E20");
+        assert_eq!(
+            classify_line("c4 e6 d4 Nf6 Nc3 Bb4 Qc2").unwrap().eco,
+            "E32"
+        );
+        // the deepest known position is kept after leaving the table
+        let opening: Opening = classify_line("e4 c5 a3 a6 h3").unwrap();
+        assert_eq!(opening.eco, "B20");
+        assert!(classify_line("").is_none());
+    }
+
+    #[test]
+    fn classify_games() {
+        let text: &str = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 *\n\n\
+                          [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd2 *\n";
+        let games: Vec<PgnGame> = PgnReader::new(text.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        let mut game: PgnGame = games[0].clone();
+        classify_game(&game).unwrap().unwrap().set_tags(&mut game);
+        assert_eq!(game.tag("ECO"), Some("C50"));
+        assert_eq!(game.tag("Opening"), Some("Italian Game"));
+        assert_eq!(game.tag("Variation"), Some("Giuoco Piano"));
+        // a classification without variation removes the previous one
+        let opening: Opening = classify_line("e4 c5").unwrap();
+        assert_eq!(opening.variation, None);
+        opening.set_tags(&mut game);
+        assert_eq!(game.tag("ECO"), Some("B20"));
+        assert_eq!(game.tag("Variation"), None);
+        assert!(classify_game(&games[1]).unwrap().is_none());
+    }
+}
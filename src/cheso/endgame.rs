@@ -0,0 +1,469 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::piece::Piece;
+use crate::cheso::square::Square;
+use std::sync::OnceLock;
+
+/// Score (in centipawns) of a position known to be won, above any material balance but below mate scores.
+pub const KNOWN_WIN: i32 = 10000;
+
+/// Scale factor that leaves an evaluation unchanged.
+pub const SCALE_NORMAL: i32 = 64;
+
+/// Number of positions of the KPK bitbase: side to move, both kings, and the pawn on files a-d and ranks 2-7.
+const KPK_SIZE: usize = 2 * 64 * 64 * 24;
+
+/// Material signature of a position.
+///
+/// Number of pieces of each type (by piece index) for each color (by color index).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Material {
+    counts: [[u8; 6]; 2],
+}
+
+impl Material {
+    /// Compute the material signature of a board from its piece maps.
+    pub fn of(board: &Board) -> Material {
+        let mut material: Material = Material::default();
+        for color in [Color::White, Color::Black] {
+            for piece in board.get_pieces(color).values() {
+                material.counts[color.index()][piece.index()] += 1;
+            }
+        }
+        material
+    }
+
+    /// Get the number of pieces of a type of a color.
+    pub fn count(&self, color: Color, piece: Piece) -> u8 {
+        self.counts[color.index()][piece.index()]
+    }
+
+    /// Check if a color has exactly the given pieces besides its king.
+    fn is(&self, color: Color, pieces: &[Piece]) -> bool {
+        let mut counts: [u8; 6] = [0; 6];
+        counts[Piece::King.index()] = 1;
+        for piece in pieces {
+            counts[piece.index()] += 1;
+        }
+        self.counts[color.index()] == counts
+    }
+
+    /// Check if the only pieces of a color besides its king and its pawns are bishops.
+    fn only_bishops(&self, color: Color) -> bool {
+        [Piece::Knight, Piece::Rook, Piece::Queen]
+            .iter()
+            .all(|piece| self.count(color, *piece) == 0)
+    }
+}
+
+/// Evaluate a known ending, from the point of view of the side to move.
+///
+/// The ending is recognized by its material signature; `None` is returned if it has no specialized evaluation.
+pub fn evaluate(board: &Board, material: &Material) -> Option<i32> {
+    for strong in [Color::White, Color::Black] {
+        let weak: Color = strong.opposite();
+        let score: i32 = if material.is(strong, &[Piece::Pawn]) && material.is(weak, &[]) {
+            kpk(board, strong)?
+        } else if material.is(strong, &[Piece::Bishop, Piece::Knight]) && material.is(weak, &[]) {
+            kbnk(board, strong)?
+        } else if material.is(strong, &[Piece::Rook]) && material.is(weak, &[Piece::Pawn]) {
+            krkp(board, strong)?
+        } else {
+            continue;
+        };
+        return Some(match board.side_to_move == strong {
+            true => score,
+            false => -score,
+        });
+    }
+    None
+}
+
+/// Get the scale factor (out of `SCALE_NORMAL`) to apply to the evaluation of a drawish ending.
+pub fn scale(board: &Board, material: &Material) -> i32 {
+    if wrong_bishop(board, material, Color::White) || wrong_bishop(board, material, Color::Black) {
+        return 0;
+    }
+    if opposite_bishops(board, material) {
+        let pawns: i32 = material.count(Color::White, Piece::Pawn) as i32
+            - material.count(Color::Black, Piece::Pawn) as i32;
+        return match pawns.abs() {
+            0 | 1 => SCALE_NORMAL / 4,
+            _ => SCALE_NORMAL / 2,
+        };
+    }
+    SCALE_NORMAL
+}
+
+/// Find the square of a piece of a color (the first one, if there are several).
+fn find(board: &Board, color: Color, piece: Piece) -> Option<Square> {
+    board
+        .get_pieces(color)
+        .iter()
+        .find(|(_, other)| **other == piece)
+        .map(|(square, _)| *square)
+}
+
+/// Get a square as seen by a color, so that its pawns always move towards the eighth rank.
+fn relative(square: Square, color: Color) -> Square {
+    Square::new(square.relative_rank(color), square.file())
+}
+
+/// King and pawn against king, using the bitbase.
+fn kpk(board: &Board, strong: Color) -> Option<i32> {
+    let weak: Color = strong.opposite();
+    let mut king: Square = relative(find(board, strong, Piece::King)?, strong);
+    let mut other: Square = relative(find(board, weak, Piece::King)?, strong);
+    let mut pawn: Square = relative(find(board, strong, Piece::Pawn)?, strong);
+    // the bitbase only stores pawns on files a-d
+    if pawn.file() > 4 {
+        king = Square::new(king.rank(), 9 - king.file());
+        other = Square::new(other.rank(), 9 - other.file());
+        pawn = Square::new(pawn.rank(), 9 - pawn.file());
+    }
+    let index: usize = kpk_index(
+        board.side_to_move == strong,
+        king.index(),
+        other.index(),
+        pawn.index(),
+    );
+    match kpk_bitbase()[index / 64] >> (index % 64) & 1 {
+        1 => Some(KNOWN_WIN + Piece::Pawn.value() + 10 * pawn.rank() as i32),
+        _ => Some(0),
+    }
+}
+
+/// King, bishop and knight against king.
+///
+/// The defending king is driven to a corner of the color of the bishop, where the mate can be given.
+fn kbnk(board: &Board, strong: Color) -> Option<i32> {
+    let king: Square = find(board, strong, Piece::King)?;
+    let other: Square = find(board, strong.opposite(), Piece::King)?;
+    let bishop: Square = find(board, strong, Piece::Bishop)?;
+    // from 0 on the long diagonal of the other color to 7 in the corners of the color of the bishop
+    let corner: isize = match bishop.is_light() {
+        true => (other.rank() - other.file()).abs(),
+        false => (other.rank() + other.file() - 9).abs(),
+    };
+    Some(KNOWN_WIN + 50 * corner as i32 + 20 * (7 - king.distance(&other)) as i32)
+}
+
+/// King and rook against king and pawn.
+fn krkp(board: &Board, strong: Color) -> Option<i32> {
+    let weak: Color = strong.opposite();
+    let king: Square = relative(find(board, strong, Piece::King)?, strong);
+    let other: Square = relative(find(board, weak, Piece::King)?, strong);
+    let rook: Square = relative(find(board, strong, Piece::Rook)?, strong);
+    let pawn: Square = relative(find(board, weak, Piece::Pawn)?, strong);
+    let stop: Square = Square::new(pawn.rank() - 1, pawn.file());
+    let queening: Square = Square::new(1, pawn.file());
+    let strong_tempo: isize = (board.side_to_move == strong) as isize;
+    let rook_value: isize = Piece::Rook.value() as isize;
+    let score: isize = if king.file() == pawn.file() && king.rank() < pawn.rank() {
+        // the king blocks the pawn
+        rook_value - king.distance(&pawn)
+    } else if other.distance(&pawn) >= 3 + 1 - strong_tempo && other.distance(&rook) >= 3 {
+        // the defending king is too far from its pawn and the rook
+        rook_value - king.distance(&pawn)
+    } else if other.rank() <= 3
+        && other.distance(&pawn) == 1
+        && king.rank() >= 4
+        && king.distance(&pawn) > 2 + strong_tempo
+    {
+        // the pawn is far advanced and supported by its king
+        80 - 8 * king.distance(&pawn)
+    } else {
+        200 - 8 * (king.distance(&stop) - other.distance(&stop) - pawn.distance(&queening))
+    };
+    Some(score as i32)
+}
+
+/// Check for a bishop and rook pawns against a bare king holding a promotion corner of the wrong color.
+fn wrong_bishop(board: &Board, material: &Material, strong: Color) -> bool {
+    if !material.is(strong.opposite(), &[])
+        || !material.only_bishops(strong)
+        || material.count(strong, Piece::Bishop) != 1
+    {
+        return false;
+    }
+    let mut pawns = board
+        .get_pieces(strong)
+        .iter()
+        .filter(|(_, piece)| **piece == Piece::Pawn)
+        .map(|(square, _)| square.file());
+    let file: isize = match pawns.next() {
+        Some(file @ (1 | 8)) => file,
+        _ => return false,
+    };
+    if !pawns.all(|other| other == file) {
+        return false;
+    }
+    let queening: Square = Square::new(relative(Square::new(8, file), strong).rank(), file);
+    match (
+        find(board, strong, Piece::Bishop),
+        find(board, strong.opposite(), Piece::King),
+    ) {
+        (Some(bishop), Some(king)) => {
+            bishop.is_light() != queening.is_light() && king.distance(&queening) <= 1
+        }
+        _ => false,
+    }
+}
+
+/// Check for an ending with only one bishop of each side, on squares of opposite colors, and pawns.
+fn opposite_bishops(board: &Board, material: &Material) -> bool {
+    if [Color::White, Color::Black]
+        .iter()
+        .any(|color| material.count(*color, Piece::Bishop) != 1 || !material.only_bishops(*color))
+    {
+        return false;
+    }
+    match (
+        find(board, Color::White, Piece::Bishop),
+        find(board, Color::Black, Piece::Bishop),
+    ) {
+        (Some(white), Some(black)) => white.is_light() != black.is_light(),
+        _ => false,
+    }
+}
+
+/// Outcome of a KPK position while building the bitbase.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+static KPK: OnceLock<Vec<u64>> = OnceLock::new();
+
+/// Get the KPK bitbase, building it on first use.
+///
+/// One bit per position, set if the side with the pawn wins.
+fn kpk_bitbase() -> &'static [u64] {
+    KPK.get_or_init(build_kpk)
+}
+
+/// Get the index of a KPK position, with square indices seen from the side with the pawn (on files a-d).
+fn kpk_index(strong_to_move: bool, king: usize, other: usize, pawn: usize) -> usize {
+    let pawn: usize = (pawn / 8 - 1) * 4 + pawn % 8;
+    (((!strong_to_move as usize) * 64 + other) * 64 + king) * 24 + pawn
+}
+
+/// Get the KPK position of an index: side to move, king of the strong side, other king and pawn.
+fn kpk_position(index: usize) -> (bool, usize, usize, usize) {
+    let pawn: usize = index % 24;
+    (
+        index / (24 * 64 * 64) == 0,
+        index / 24 % 64,
+        index / (24 * 64) % 64,
+        (pawn / 4 + 1) * 8 + pawn % 4,
+    )
+}
+
+/// Check if a (white) pawn attacks a square.
+fn pawn_attacks(pawn: usize, square: usize) -> bool {
+    let pawn: Square = Square::from_index(pawn);
+    [pawn.northwest(), pawn.northeast()].contains(&Some(Square::from_index(square)))
+}
+
+/// Build the KPK bitbase by iterating over all positions until none can be classified anymore.
+fn build_kpk() -> Vec<u64> {
+    let king_moves: Vec<Vec<usize>> = (0..64)
+        .map(|square| {
+            let square: Square = Square::from_index(square);
+            [
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ]
+            .iter()
+            .filter_map(|(rank, file)| square.translate(*rank, *file))
+            .map(|square| square.index())
+            .collect()
+        })
+        .collect();
+    let distance = |a: usize, b: usize| Square::from_index(a).distance(&Square::from_index(b));
+    let mut outcomes: Vec<Outcome> = (0..KPK_SIZE)
+        .map(|index| {
+            let (strong_to_move, king, other, pawn) = kpk_position(index);
+            if distance(king, other) <= 1
+                || king == pawn
+                || other == pawn
+                || (strong_to_move && pawn_attacks(pawn, other))
+            {
+                Outcome::Invalid
+            } else if strong_to_move
+                && pawn / 8 == 6
+                && king != pawn + 8
+                && (distance(other, pawn + 8) > 1 || distance(king, pawn + 8) == 1)
+            {
+                // the pawn promotes safely
+                Outcome::Win
+            } else if !strong_to_move
+                && (king_moves[other]
+                    .iter()
+                    .all(|square| distance(*square, king) <= 1 || pawn_attacks(pawn, *square))
+                    || (distance(other, pawn) == 1 && distance(king, pawn) > 1))
+            {
+                // stalemate, or the pawn is captured
+                Outcome::Draw
+            } else {
+                Outcome::Unknown
+            }
+        })
+        .collect();
+    let mut changed: bool = true;
+    while changed {
+        changed = false;
+        for index in 0..KPK_SIZE {
+            if outcomes[index] != Outcome::Unknown {
+                continue;
+            }
+            let (strong_to_move, king, other, pawn) = kpk_position(index);
+            let children: Vec<Outcome> = match strong_to_move {
+                true => {
+                    let mut children: Vec<Outcome> = king_moves[king]
+                        .iter()
+                        .map(|square| outcomes[kpk_index(false, *square, other, pawn)])
+                        .collect();
+                    if pawn / 8 < 6 {
+                        children.push(outcomes[kpk_index(false, king, other, pawn + 8)]);
+                    }
+                    if pawn / 8 == 1 && pawn + 8 != king && pawn + 8 != other {
+                        children.push(outcomes[kpk_index(false, king, other, pawn + 16)]);
+                    }
+                    children
+                }
+                false => king_moves[other]
+                    .iter()
+                    .map(|square| outcomes[kpk_index(true, king, *square, pawn)])
+                    .collect(),
+            };
+            let (good, bad) = match strong_to_move {
+                true => (Outcome::Win, Outcome::Draw),
+                false => (Outcome::Draw, Outcome::Win),
+            };
+            let outcome: Outcome = if children.contains(&good) {
+                good
+            } else if children.contains(&Outcome::Unknown) {
+                Outcome::Unknown
+            } else {
+                bad
+            };
+            if outcome != Outcome::Unknown {
+                outcomes[index] = outcome;
+                changed = true;
+            }
+        }
+    }
+    let mut bits: Vec<u64> = vec![0; KPK_SIZE.div_ceil(64)];
+    for (index, outcome) in outcomes.iter().enumerate() {
+        if *outcome == Outcome::Win {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluate a known ending, from the point of view of white.
+    fn evaluate_white(fen: &str) -> i32 {
+        let board: Board = Board::from_fen(fen).unwrap();
+        let score: i32 = evaluate(&board, &Material::of(&board)).unwrap();
+        match board.side_to_move {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+
+    #[test]
+    fn kpk_wins() {
+        // king on the sixth rank in front of its pawn, whoever moves
+        assert!(evaluate_white("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") > KNOWN_WIN);
+        assert!(evaluate_white("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") > KNOWN_WIN);
+        // the king escorts the pawn from the seventh rank (Kd6, then Kd7)
+        assert!(evaluate_white("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1") > KNOWN_WIN);
+        // the king can not catch the pawn
+        assert!(evaluate_white("8/8/8/8/P7/8/8/k6K w - - 0 1") > KNOWN_WIN);
+        // black pawn on the h-file, mirrored and flipped
+        assert!(evaluate_white("8/8/8/8/8/6k1/7p/4K3 b - - 0 1") < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn kpk_draws() {
+        // stalemate with the pawn on the seventh rank
+        assert_eq!(evaluate_white("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), 0);
+        // the defending king keeps the opposition
+        assert_eq!(evaluate_white("8/4k3/4P3/4K3/8/8/8/8 w - - 0 1"), 0);
+        // the defending king reaches the corner in front of a rook pawn
+        assert_eq!(evaluate_white("k7/8/8/8/8/8/P7/7K w - - 0 1"), 0);
+        // the pawn is captured at once
+        assert_eq!(evaluate_white("8/8/8/8/8/3k4/3P4/7K b - - 0 1"), 0);
+    }
+
+    /// Get the scale factor of a position.
+    fn scale_of(fen: &str) -> i32 {
+        let board: Board = Board::from_fen(fen).unwrap();
+        scale(&board, &Material::of(&board))
+    }
+
+    #[test]
+    fn kbnk_drives_the_king_to_a_corner_of_the_bishop() {
+        // with a light-squared bishop, a8 and h1 are the mating corners
+        let light_corner: i32 = evaluate_white("k7/8/3K4/8/8/8/8/5BN1 w - - 0 1");
+        let dark_corner: i32 = evaluate_white("7k/8/4K3/8/8/8/8/5BN1 w - - 0 1");
+        let center: i32 = evaluate_white("8/8/8/3k4/8/3K4/8/5BN1 w - - 0 1");
+        assert!(dark_corner > KNOWN_WIN);
+        assert!(light_corner > dark_corner && light_corner > center);
+        // and h8 with a dark-squared bishop, for black
+        let dark_corner: i32 = evaluate_white("7K/8/4k3/8/8/8/8/2b3n1 b - - 0 1");
+        let light_corner: i32 = evaluate_white("K7/8/3k4/8/8/8/8/2b3n1 b - - 0 1");
+        assert!(dark_corner < light_corner && light_corner < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn krkp_blocked_and_advanced_pawns() {
+        // the king in front of the pawn wins it
+        assert!(evaluate_white("7k/8/8/8/3p4/8/3K4/R7 w - - 0 1") > 400);
+        // the pawn on the second rank is supported and the king is far: about a draw
+        let advanced: i32 = evaluate_white("R6K/8/8/8/8/2k5/3p4/8 b - - 0 1");
+        assert!((0..100).contains(&advanced));
+    }
+
+    #[test]
+    fn wrong_bishop_is_a_draw() {
+        // the dark-squared bishop can not control a8, nor h1 for black
+        assert_eq!(scale_of("k7/8/8/8/8/P7/8/2B1K3 w - - 0 1"), 0);
+        assert_eq!(scale_of("8/8/8/8/8/7p/6K1/k5b1 w - - 0 1"), 0);
+        // the right bishop, or a king too far from the corner
+        assert_eq!(scale_of("k7/8/8/8/8/P7/8/4KB2 w - - 0 1"), SCALE_NORMAL);
+        assert_eq!(scale_of("8/8/8/4k3/8/P7/8/2B1K3 w - - 0 1"), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn opposite_bishops_are_drawish() {
+        assert_eq!(
+            scale_of("4k3/8/2b5/8/4P3/8/8/2B1K3 w - - 0 1"),
+            SCALE_NORMAL / 4
+        );
+        assert_eq!(
+            scale_of("4k3/8/2b5/8/3PP3/8/8/2B1K3 w - - 0 1"),
+            SCALE_NORMAL / 2
+        );
+        // bishops of the same color
+        assert_eq!(
+            scale_of("4k3/8/8/2b5/4P3/8/8/2B1K3 w - - 0 1"),
+            SCALE_NORMAL
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::cheso::board::Board;
+use crate::cheso::movement::Movement;
+use crate::cheso::search::{is_mate_score, MATE};
+
+/// Position in Extended Position Description (EPD).
+///
+/// The four position fields of FEN, followed by operations: an opcode and its operands, ended by a semicolon
+/// (e.g. `bm Qg6; id "WAC.001";`). Operations are kept in their order, with string operands unquoted.
+/// The `hmvc` and `fmvn` operations set the clocks of the board.
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Expected outcome of a test position.
+///
+/// The best movements (`bm`), the movements to avoid (`am`), the number of movements of a direct mate (`dm`),
+/// and the points of the movements (from a `c0` comment such as `"Qg6=10, Qh5=5"`, as used by STS suites).
+#[derive(Clone, Debug, Default)]
+pub struct Solution {
+    pub best: Vec<Movement>,
+    pub avoid: Vec<Movement>,
+    pub mate: Option<i32>,
+    pub points: Vec<(Movement, u32)>,
+}
+
+impl std::fmt::Display for Epd {
+    /// Format the position in EPD, quoting string operands when needed.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let fen: String = self.board.to_fen();
+        let fields: Vec<&str> = fen.split_whitespace().take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                // identifiers and comments (`c0` to `c9`) are strings
+                let quoted: bool = opcode == "id"
+                    || (opcode.len() == 2
+                        && opcode.starts_with('c')
+                        && opcode.ends_with(|c: char| c.is_ascii_digit()))
+                    || operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == ';');
+                match quoted {
+                    true => write!(f, " \"{}\"", operand)?,
+                    false => write!(f, " {}", operand)?,
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+impl Epd {
+    /// Parse a position in EPD.
+    pub fn parse(line: &str) -> Result<Epd, String> {
+        let mut rest: &str = line.trim();
+        let mut fields: Vec<&str> = Vec::new();
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(format!("missing position fields in EPD '{}'", line));
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+        let mut board: Board = Board::from_fen(&fields.join(" "))?;
+        let mut operations: Vec<(String, Vec<String>)> = Vec::new();
+        let mut chars = rest.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let opcode: String =
+                std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != ';')).collect();
+            let mut operands: Vec<String> = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    None | Some(';') => break,
+                    Some('"') => {
+                        let mut operand: String = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => operand.push(c),
+                                None => {
+                                    return Err(format!("unterminated string in EPD '{}'", line))
+                                }
+                            }
+                        }
+                        operands.push(operand);
+                    }
+                    Some(c) => {
+                        let mut operand: String = c.to_string();
+                        operand.extend(std::iter::from_fn(|| {
+                            chars.next_if(|c| !c.is_whitespace() && *c != ';')
+                        }));
+                        operands.push(operand);
+                    }
+                }
+            }
+            if opcode.is_empty() {
+                continue;
+            }
+            let invalid = || format!("invalid {} operation in EPD '{}'", opcode, line);
+            let number = |operands: &[String]| -> Result<u16, String> {
+                operands
+                    .first()
+                    .and_then(|operand| operand.parse().ok())
+                    .ok_or_else(invalid)
+            };
+            match opcode.as_str() {
+                "hmvc" => {
+                    board.reversible_moves =
+                        u8::try_from(number(&operands)?).map_err(|_| invalid())?
+                }
+                "fmvn" => board.fullmove_number = number(&operands)?,
+                _ => (),
+            }
+            operations.push((opcode, operands));
+        }
+        Ok(Epd { board, operations })
+    }
+
+    /// Get the operands of the first operation with an opcode, if present.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(other, _)| other == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Get the first operand of an operation, if present.
+    fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operation(opcode)?.first().map(String::as_str)
+    }
+
+    /// Get the identifier of the position (`id`).
+    pub fn id(&self) -> Option<&str> {
+        self.operand("id")
+    }
+
+    /// Get the primary comment of the position (`c0`).
+    pub fn comment(&self) -> Option<&str> {
+        self.operand("c0")
+    }
+
+    /// Get the analysis depth (`acd`).
+    pub fn analysis_depth(&self) -> Option<i32> {
+        self.operand("acd")?.parse().ok()
+    }
+
+    /// Get the evaluation in centipawns, from the point of view of the side to move (`ce`).
+    pub fn centipawn_evaluation(&self) -> Option<i32> {
+        self.operand("ce")?.parse().ok()
+    }
+
+    /// Get the number of movements of a direct mate (`dm`).
+    pub fn direct_mate(&self) -> Option<i32> {
+        self.operand("dm")?.parse().ok()
+    }
+
+    /// Parse a movement of the position, in standard algebraic notation or else in long algebraic notation.
+    fn parse_move(&self, text: &str) -> Result<Movement, String> {
+        let mut board: Board = self.board.clone();
+        board
+            .parse_san(text)
+            .or_else(|| board.parse_move(text))
+            .ok_or(format!("illegal movement '{}' in EPD '{}'", text, self))
+    }
+
+    /// Parse the movements of an operation (`bm` or `am`).
+    fn moves(&self, opcode: &str) -> Result<Vec<Movement>, String> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|text| self.parse_move(text))
+            .collect()
+    }
+
+    /// Get the expected outcome of the position, parsing its movements.
+    ///
+    /// A `c0` comment which is not a list of movements with points is ignored.
+    pub fn solution(&self) -> Result<Solution, String> {
+        let points: Vec<(Movement, u32)> = self
+            .comment()
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| {
+                let (text, points) = item.trim().split_once('=')?;
+                Some((self.parse_move(text).ok()?, points.trim().parse().ok()?))
+            })
+            .collect::<Option<Vec<(Movement, u32)>>>()
+            .unwrap_or_default();
+        Ok(Solution {
+            best: self.moves("bm")?,
+            avoid: self.moves("am")?,
+            mate: self.direct_mate(),
+            points,
+        })
+    }
+}
+
+impl Solution {
+    /// Check if there is nothing to solve.
+    pub fn is_empty(&self) -> bool {
+        self.best.is_empty()
+            && self.avoid.is_empty()
+            && self.mate.is_none()
+            && self.points.is_empty()
+    }
+
+    /// Get the maximum number of points of the position (1 without points).
+    pub fn max_points(&self) -> u32 {
+        self.points
+            .iter()
+            .map(|(_, points)| *points)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Get the number of points of a movement (1 if it solves a position without points, 0 otherwise).
+    pub fn points(&self, movement: &Movement, score: i32) -> u32 {
+        match self.points.is_empty() {
+            true => self.is_solved_by(movement, score) as u32,
+            false => self
+                .points
+                .iter()
+                .find(|(other, _)| other == movement)
+                .map_or(0, |(_, points)| *points),
+        }
+    }
+
+    /// Check if a movement (with its score, from the point of view of the side to move) solves the position.
+    ///
+    /// The movement must be one of the best ones and none of those to avoid, and its score must be a mate
+    /// in the expected number of movements (or less). Without any of these, it must get the maximum points.
+    pub fn is_solved_by(&self, movement: &Movement, score: i32) -> bool {
+        if self.best.is_empty()
+            && self.avoid.is_empty()
+            && self.mate.is_none()
+            && !self.points.is_empty()
+        {
+            return self.points(movement, score) == self.max_points();
+        }
+        (self.best.is_empty() || self.best.contains(movement))
+            && !self.avoid.contains(movement)
+            && self.mate.is_none_or(|moves| {
+                is_mate_score(score) && score > 0 && (MATE - score + 1) / 2 <= moves
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a movement of a position in SAN.
+    fn san(epd: &Epd, text: &str) -> Movement {
+        epd.board.clone().parse_san(text).unwrap()
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let line: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - \
+                          bm Qg6; id \"WAC.001\"; c0 \"Qg6=10, Qh5=5\"; hmvc 3; fmvn 20;";
+        let epd: Epd = Epd::parse(line).unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.operation("bm"), Some(&[String::from("Qg6")][..]));
+        assert_eq!(epd.board.reversible_moves, 3);
+        assert_eq!(epd.board.fullmove_number, 20);
+        assert_eq!(epd.to_string(), line);
+        assert_eq!(Epd::parse(&epd.to_string()).unwrap().to_string(), line);
+    }
+
+    #[test]
+    fn reject_invalid_operations() {
+        let fen: &str = "4k3/8/8/8/8/8/8/4K3 w - -";
+        assert!(Epd::parse(&format!("{} hmvc 255;", fen)).is_ok());
+        for operation in ["hmvc 256;", "hmvc -1;", "fmvn x;", "id \"a;"] {
+            assert!(
+                Epd::parse(&format!("{} {}", fen, operation)).is_err(),
+                "{}",
+                operation
+            );
+        }
+        let epd: Epd = Epd::parse(&format!("{} bm Qg6;", fen)).unwrap();
+        assert!(epd.solution().is_err());
+    }
+
+    #[test]
+    fn solved_by_best_and_avoided_movements() {
+        let epd: Epd = Epd::parse("4k3/8/8/8/8/8/3P4/R3K3 w Q - bm Ra8+ O-O-O; am d4;").unwrap();
+        let solution: Solution = epd.solution().unwrap();
+        assert!(solution.is_solved_by(&san(&epd, "Ra8+"), 0));
+        assert!(solution.is_solved_by(&san(&epd, "O-O-O"), 0));
+        assert!(!solution.is_solved_by(&san(&epd, "d4"), 0));
+        assert!(!solution.is_solved_by(&san(&epd, "Kf2"), 0));
+        let epd: Epd = Epd::parse("4k3/8/8/8/8/8/3P4/R3K3 w Q - am d4;").unwrap();
+        let solution: Solution = epd.solution().unwrap();
+        assert!(solution.is_solved_by(&san(&epd, "d3"), 0));
+        assert!(!solution.is_solved_by(&san(&epd, "d4"), 0));
+    }
+
+    #[test]
+    fn solved_by_mates_and_points() {
+        let epd: Epd = Epd::parse("kbK5/pp6/1P6/8/8/8/8/R7 w - - dm 2;").unwrap();
+        let solution: Solution = epd.solution().unwrap();
+        let key: Movement = san(&epd, "Ra6");
+        assert!(solution.is_solved_by(&key, MATE - 3));
+        assert!(solution.is_solved_by(&key, MATE - 1));
+        assert!(!solution.is_solved_by(&key, MATE - 5));
+        assert!(!solution.is_solved_by(&key, 500));
+        let epd: Epd = Epd::parse("4k3/8/8/8/8/8/3P4/4K3 w - - c0 \"d4=10, d3=5\";").unwrap();
+        let solution: Solution = epd.solution().unwrap();
+        assert_eq!(solution.max_points(), 10);
+        assert_eq!(solution.points(&san(&epd, "d3"), 0), 5);
+        assert_eq!(solution.points(&san(&epd, "Kd1"), 0), 0);
+        assert!(solution.is_solved_by(&san(&epd, "d4"), 0));
+        assert!(!solution.is_solved_by(&san(&epd, "d3"), 0));
+    }
+}
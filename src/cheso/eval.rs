@@ -0,0 +1,112 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::endgame::{self, Material, SCALE_NORMAL};
+use crate::cheso::piece::Piece;
+use crate::cheso::square::Square;
+
+/// Piece-square tables.
+///
+/// Bonus (in centipawns) of each piece on each square, from the point of view of white.
+/// Tables are written as seen from white's side of the board, so the first row is the eighth rank.
+#[rustfmt::skip]
+static PIECE_SQUARE_TABLES: [[i32; 64]; 6] = [
+    // pawn
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+        50, 50, 50, 50, 50, 50, 50, 50,
+        10, 10, 20, 30, 30, 20, 10, 10,
+         5,  5, 10, 25, 25, 10,  5,  5,
+         0,  0,  0, 20, 20,  0,  0,  0,
+         5, -5,-10,  0,  0,-10, -5,  5,
+         5, 10, 10,-20,-20, 10, 10,  5,
+         0,  0,  0,  0,  0,  0,  0,  0,
+    ],
+    // knight
+    [
+        -50,-40,-30,-30,-30,-30,-40,-50,
+        -40,-20,  0,  0,  0,  0,-20,-40,
+        -30,  0, 10, 15, 15, 10,  0,-30,
+        -30,  5, 15, 20, 20, 15,  5,-30,
+        -30,  0, 15, 20, 20, 15,  0,-30,
+        -30,  5, 10, 15, 15, 10,  5,-30,
+        -40,-20,  0,  5,  5,  0,-20,-40,
+        -50,-40,-30,-30,-30,-30,-40,-50,
+    ],
+    // bishop
+    [
+        -20,-10,-10,-10,-10,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5, 10, 10,  5,  0,-10,
+        -10,  5,  5, 10, 10,  5,  5,-10,
+        -10,  0, 10, 10, 10, 10,  0,-10,
+        -10, 10, 10, 10, 10, 10, 10,-10,
+        -10,  5,  0,  0,  0,  0,  5,-10,
+        -20,-10,-10,-10,-10,-10,-10,-20,
+    ],
+    // rook
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+         5, 10, 10, 10, 10, 10, 10,  5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+         0,  0,  0,  5,  5,  0,  0,  0,
+    ],
+    // queen
+    [
+        -20,-10,-10, -5, -5,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5,  5,  5,  5,  0,-10,
+         -5,  0,  5,  5,  5,  5,  0, -5,
+          0,  0,  5,  5,  5,  5,  0, -5,
+        -10,  5,  5,  5,  5,  5,  0,-10,
+        -10,  0,  5,  0,  0,  0,  0,-10,
+        -20,-10,-10, -5, -5,-10,-10,-20,
+    ],
+    // king
+    [
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -20,-30,-30,-40,-40,-30,-30,-20,
+        -10,-20,-20,-20,-20,-20,-20,-10,
+         20, 20,  0,  0,  0,  0, 20, 20,
+         20, 30, 10,  0,  0, 10, 30, 20,
+    ],
+];
+
+/// Get the piece-square bonus of a piece of a color located in a square.
+pub fn piece_square(piece: Piece, color: Color, square: &Square) -> i32 {
+    let row: isize = match color {
+        Color::White => 8 - square.rank(),
+        Color::Black => square.rank() - 1,
+    };
+    PIECE_SQUARE_TABLES[piece.index()][(row * 8 + square.file() - 1) as usize]
+}
+
+/// Evaluate a position statically.
+///
+/// The score (in centipawns) is given from the point of view of the side to move,
+/// adding material and piece-square bonuses of its pieces and subtracting those of the opponent.
+/// Known endings are evaluated by their specialized functions, and drawish ones are scaled down.
+pub fn evaluate(board: &Board) -> i32 {
+    let material: Material = Material::of(board);
+    if let Some(score) = endgame::evaluate(board, &material) {
+        return score;
+    }
+    let mut score: i32 = 0;
+    for (square, piece) in &board.white_pieces {
+        score += piece.value() + piece_square(*piece, Color::White, square);
+    }
+    for (square, piece) in &board.black_pieces {
+        score -= piece.value() + piece_square(*piece, Color::Black, square);
+    }
+    score = score * endgame::scale(board, &material) / SCALE_NORMAL;
+    match board.side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
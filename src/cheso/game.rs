@@ -0,0 +1,546 @@
+use crate::cheso::board::{Board, STARTING_FEN};
+use crate::cheso::movement::Movement;
+use crate::cheso::pgn::{PgnEval, PgnGame, PgnMove, PgnVariation};
+use std::time::Duration;
+
+/// Identifier of a node of a game tree (its index in the arena of nodes).
+pub type NodeId = usize;
+
+/// Root node of every game tree: the starting position, before any movement.
+pub const ROOT: NodeId = 0;
+
+/// Node of a game tree: a movement with its annotations.
+///
+/// The first child of a node is the main continuation, and the other ones are its variations.
+/// Comments before the movement are only used at the start of variations,
+/// and the comments after the root node are the comments before the first movement of the game.
+#[derive(Clone, Debug, Default)]
+pub struct Node {
+    parent: Option<NodeId>,
+    movement: Option<Movement>,
+    children: Vec<NodeId>,
+    pub starting_comments: Vec<String>,
+    pub comments: Vec<String>,
+    pub nags: Vec<u8>,
+    pub clock: Option<Duration>,
+    pub eval: Option<PgnEval>,
+}
+
+impl Node {
+    /// Get the parent of the node (`None` for the root node).
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// Get the movement leading to the node (`None` for the root node).
+    pub fn movement(&self) -> Option<Movement> {
+        self.movement
+    }
+
+    /// Get the children of the node, the main continuation first.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// Game with a tree of movements.
+///
+/// Nodes are stored in an arena and referred to by their identifier, which stays valid until the node is deleted.
+/// The game keeps a current node, which can be moved along the tree, and its board is rebuilt on demand
+/// by replaying the movements from the starting position.
+#[derive(Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub result: String,
+    start: Board,
+    nodes: Vec<Option<Node>>,
+    current: NodeId,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    /// Create a game from the initial position.
+    pub fn new() -> Game {
+        Game::from_board(&Board::new())
+    }
+
+    /// Create a game from a position.
+    pub fn from_board(board: &Board) -> Game {
+        Game {
+            tags: Vec::new(),
+            result: String::from("*"),
+            start: board.clone(),
+            nodes: vec![Some(Node::default())],
+            current: ROOT,
+        }
+    }
+
+    /// Create a game from a PGN game, checking that its movements are legal.
+    pub fn from_pgn(pgn: &PgnGame) -> Result<Game, String> {
+        let mut game: Game = Game::from_board(&pgn.board()?);
+        game.tags = pgn.tags.clone();
+        game.result = pgn.result.clone();
+        game.nodes[ROOT] = Some(Node {
+            comments: pgn.comments.clone(),
+            ..Node::default()
+        });
+        let mut board: Board = game.start.clone();
+        game.add_line(&mut board, ROOT, &[], &pgn.moves)?;
+        Ok(game)
+    }
+
+    /// Add the movements of a PGN line (and their variations) after a node, leaving the board unchanged.
+    fn add_line(
+        &mut self,
+        board: &mut Board,
+        parent: NodeId,
+        comments: &[String],
+        moves: &[PgnMove],
+    ) -> Result<(), String> {
+        let mut parent: NodeId = parent;
+        let mut made: Vec<Movement> = Vec::new();
+        for (index, pgn) in moves.iter().enumerate() {
+            if !board.gen_legal_moves().contains(&pgn.movement) {
+                return Err(format!(
+                    "illegal movement {} in position {}",
+                    pgn.movement,
+                    board.to_fen()
+                ));
+            }
+            let node: NodeId = self.push(parent, pgn.movement);
+            if let Some(node) = self.node_mut(node) {
+                if index == 0 {
+                    node.starting_comments = comments.to_vec();
+                }
+                node.comments = pgn.comments.clone();
+                node.nags = pgn.nags.clone();
+                node.clock = pgn.clock;
+                node.eval = pgn.eval;
+            }
+            for variation in &pgn.variations {
+                self.add_line(board, parent, &variation.comments, &variation.moves)?;
+            }
+            board.make_move(&pgn.movement);
+            made.push(pgn.movement);
+            parent = node;
+        }
+        for movement in made.iter().rev() {
+            board.unmake_move(movement);
+        }
+        Ok(())
+    }
+
+    /// Make a PGN game of the game tree.
+    ///
+    /// A game not starting from the initial position gets `SetUp` and `FEN` tags.
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn: PgnGame = PgnGame {
+            tags: self.tags.clone(),
+            comments: self.nodes[ROOT]
+                .as_ref()
+                .map(|root| root.comments.clone())
+                .unwrap_or_default(),
+            moves: Vec::new(),
+            result: self.result.clone(),
+        };
+        let fen: String = self.start.to_fen();
+        if fen != STARTING_FEN {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &fen);
+        }
+        if let Some(first) = self.node(ROOT).and_then(|root| root.children.first()) {
+            pgn.moves = self.line(*first);
+        }
+        pgn
+    }
+
+    /// Make the PGN movements of the main continuation of a node, with the variations of each movement.
+    fn line(&self, first: NodeId) -> Vec<PgnMove> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut next: Option<NodeId> = Some(first);
+        while let Some(id) = next {
+            let node: &Node = match self.node(id) {
+                Some(node) => node,
+                None => break,
+            };
+            let mut pgn: PgnMove = match node.movement {
+                Some(movement) => PgnMove::new(movement),
+                None => break,
+            };
+            pgn.comments = node.comments.clone();
+            pgn.nags = node.nags.clone();
+            pgn.clock = node.clock;
+            pgn.eval = node.eval;
+            // the siblings of a main continuation are its variations
+            if let Some(siblings) = node
+                .parent
+                .and_then(|parent| self.node(parent))
+                .map(|parent| &parent.children)
+            {
+                if siblings.first() == Some(&id) {
+                    for sibling in &siblings[1..] {
+                        pgn.variations.push(PgnVariation {
+                            comments: self
+                                .node(*sibling)
+                                .map(|sibling| sibling.starting_comments.clone())
+                                .unwrap_or_default(),
+                            moves: self.line(*sibling),
+                        });
+                    }
+                }
+            }
+            moves.push(pgn);
+            next = node.children.first().copied();
+        }
+        moves
+    }
+
+    /// Add a node to the arena, as the last child of its parent.
+    fn push(&mut self, parent: NodeId, movement: Movement) -> NodeId {
+        let id: NodeId = self.nodes.len();
+        self.nodes.push(Some(Node {
+            parent: Some(parent),
+            movement: Some(movement),
+            ..Node::default()
+        }));
+        if let Some(parent) = self.node_mut(parent) {
+            parent.children.push(id);
+        }
+        id
+    }
+
+    /// Get a node, if it exists.
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id)?.as_ref()
+    }
+
+    /// Get a node to edit its annotations, if it exists.
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id)?.as_mut()
+    }
+
+    /// Get the current node.
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// Get the starting position of the game.
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    /// Get the movements from the starting position to a node.
+    pub fn moves_to(&self, id: NodeId) -> Vec<Movement> {
+        let mut moves: Vec<Movement> = Vec::new();
+        let mut next: Option<NodeId> = Some(id);
+        while let Some(node) = next.and_then(|id| self.node(id)) {
+            moves.extend(node.movement);
+            next = node.parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// Get the board at a node, replaying the movements from the starting position.
+    pub fn board_at(&self, id: NodeId) -> Option<Board> {
+        self.node(id)?;
+        let mut board: Board = self.start.clone();
+        for movement in self.moves_to(id) {
+            board.make_move(&movement);
+        }
+        Some(board)
+    }
+
+    /// Get the board at the current node.
+    pub fn board(&self) -> Board {
+        self.board_at(self.current)
+            .unwrap_or_else(|| self.start.clone())
+    }
+
+    /// Jump to a node, returning false if it does not exist.
+    pub fn go_to(&mut self, id: NodeId) -> bool {
+        match self.node(id) {
+            Some(_) => {
+                self.current = id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go to the main continuation of the current node, returning false at the end of the line.
+    pub fn go_forward(&mut self) -> bool {
+        match self
+            .node(self.current)
+            .and_then(|node| node.children.first())
+        {
+            Some(child) => {
+                self.current = *child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go to the parent of the current node, returning false at the root node.
+    pub fn go_back(&mut self) -> bool {
+        match self.node(self.current).and_then(|node| node.parent) {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go to the starting position.
+    pub fn go_to_start(&mut self) {
+        self.current = ROOT;
+    }
+
+    /// Go to the end of the main continuation of the current node.
+    pub fn go_to_end(&mut self) {
+        while self.go_forward() {}
+    }
+
+    /// Play a movement from the current node and go to its node.
+    ///
+    /// If the movement has already been played from the current node its node is reused,
+    /// otherwise it is added as the main continuation, or as a new variation if there is one already.
+    pub fn add_move(&mut self, movement: &Movement) -> Result<NodeId, String> {
+        let children: Vec<NodeId> = self
+            .node(self.current)
+            .map(|node| node.children.clone())
+            .unwrap_or_default();
+        let existing: Option<NodeId> = children
+            .into_iter()
+            .find(|child| self.node(*child).and_then(|node| node.movement) == Some(*movement));
+        let id: NodeId = match existing {
+            Some(id) => id,
+            None => {
+                let mut board: Board = self.board();
+                if !board.gen_legal_moves().contains(movement) {
+                    return Err(format!(
+                        "illegal movement {} in position {}",
+                        movement,
+                        board.to_fen()
+                    ));
+                }
+                self.push(self.current, *movement)
+            }
+        };
+        self.current = id;
+        Ok(id)
+    }
+
+    /// Move a variation one place up among its siblings, returning false if it is already the main continuation.
+    pub fn promote(&mut self, id: NodeId) -> bool {
+        let parent: NodeId = match self.node(id).and_then(|node| node.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        match self.node_mut(parent) {
+            Some(parent) => match parent.children.iter().position(|child| *child == id) {
+                Some(index) if index > 0 => {
+                    parent.children.swap(index - 1, index);
+                    true
+                }
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Make a node part of the main line, promoting it and each of its ancestors to the main continuation.
+    pub fn promote_to_mainline(&mut self, id: NodeId) {
+        let mut next: Option<NodeId> = Some(id);
+        while let Some(id) = next {
+            while self.promote(id) {}
+            next = self.node(id).and_then(|node| node.parent);
+        }
+    }
+
+    /// Delete a node and all its descendants, returning false for the root node or a missing node.
+    ///
+    /// If the current node is deleted, the parent of the deleted node becomes the current node.
+    pub fn delete(&mut self, id: NodeId) -> bool {
+        let parent: NodeId = match self.node(id).and_then(|node| node.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        if let Some(parent) = self.node_mut(parent) {
+            parent.children.retain(|child| *child != id);
+        }
+        if self.path(self.current).contains(&id) {
+            self.current = parent;
+        }
+        let mut pending: Vec<NodeId> = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.nodes.get_mut(id).and_then(Option::take) {
+                pending.extend(node.children);
+            }
+        }
+        true
+    }
+
+    /// Get the nodes from a node up to the root node (the node itself first).
+    fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path: Vec<NodeId> = Vec::new();
+        let mut next: Option<NodeId> = Some(id);
+        while let Some(id) = next {
+            path.push(id);
+            next = self.node(id).and_then(|node| node.parent);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheso::pgn::{PgnReader, PgnWriter};
+
+    /// Game with nested variations and comments.
+    const PGN: &str = "[Event \"Test\"]\n[Result \"*\"]\n\n{Start} 1. e4 {King pawn} e5 \
+        (1... c5 {Sicilian} 2. Nf3 ({Or} 2. Nc3 {Closed} Nc6) d6) 2. Nf3 $1 Nc6 *\n";
+
+    /// Read the test game.
+    fn read() -> (PgnGame, Game) {
+        let pgn: PgnGame = PgnReader::new(PGN.as_bytes()).next().unwrap().unwrap();
+        let game: Game = Game::from_pgn(&pgn).unwrap();
+        (pgn, game)
+    }
+
+    /// Play a movement in SAN from the current node.
+    fn play(game: &mut Game, text: &str) -> NodeId {
+        let movement: Movement = game.board().parse_san(text).unwrap();
+        game.add_move(&movement).unwrap()
+    }
+
+    /// Get the movements of the main line, in SAN.
+    fn main_line(game: &Game) -> Vec<String> {
+        let mut board: Board = game.start().clone();
+        let mut moves: Vec<String> = Vec::new();
+        let mut id: NodeId = ROOT;
+        while let Some(child) = game
+            .node(id)
+            .and_then(|node| node.children().first().copied())
+        {
+            let movement: Movement = game.node(child).unwrap().movement().unwrap();
+            moves.push(board.to_san(&movement));
+            board.make_move(&movement);
+            id = child;
+        }
+        moves
+    }
+
+    #[test]
+    fn promote_variations() {
+        let mut game: Game = Game::new();
+        let e4: NodeId = play(&mut game, "e4");
+        play(&mut game, "e5");
+        game.go_to(e4);
+        let c5: NodeId = play(&mut game, "c5");
+        let nf3: NodeId = play(&mut game, "Nf3");
+        game.go_to(ROOT);
+        play(&mut game, "d4");
+        assert_eq!(main_line(&game), vec!["e4", "e5"]);
+        assert!(game.promote(c5));
+        assert!(!game.promote(c5));
+        assert_eq!(main_line(&game), vec!["e4", "c5", "Nf3"]);
+        // playing an existing movement reuses its node
+        game.go_to(e4);
+        assert_eq!(play(&mut game, "c5"), c5);
+        let d4: NodeId = game.node(ROOT).unwrap().children()[1];
+        game.go_to(d4);
+        let d5: NodeId = play(&mut game, "d5");
+        game.promote_to_mainline(d5);
+        assert_eq!(main_line(&game), vec!["d4", "d5"]);
+        game.promote_to_mainline(nf3);
+        assert_eq!(main_line(&game), vec!["e4", "c5", "Nf3"]);
+    }
+
+    #[test]
+    fn delete_nodes() {
+        let mut game: Game = Game::new();
+        let e4: NodeId = play(&mut game, "e4");
+        let e5: NodeId = play(&mut game, "e5");
+        let nf3: NodeId = play(&mut game, "Nf3");
+        game.go_to(e4);
+        let c5: NodeId = play(&mut game, "c5");
+        game.go_to(nf3);
+        assert!(!game.delete(ROOT));
+        assert!(game.delete(e5));
+        // the current node was deleted with its ancestor, so the parent of the deleted node is current
+        assert_eq!(game.current(), e4);
+        assert!(game.node(e5).is_none());
+        assert!(game.node(nf3).is_none());
+        assert!(!game.delete(e5));
+        assert_eq!(game.node(e4).unwrap().children(), &[c5]);
+        assert_eq!(main_line(&game), vec!["e4", "c5"]);
+        assert_eq!(game.board().to_fen(), game.board_at(e4).unwrap().to_fen());
+    }
+
+    #[test]
+    fn navigate_lines_and_variations() {
+        let (_, mut game) = read();
+        assert!(!game.go_back());
+        assert!(game.go_forward());
+        let e4: NodeId = game.current();
+        game.go_to_end();
+        assert_eq!(main_line(&game), vec!["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.moves_to(game.current()).len(), 4);
+        assert!(!game.go_forward());
+        assert!(game.go_back());
+        assert_eq!(game.moves_to(game.current()).len(), 3);
+        game.go_to_start();
+        assert_eq!(game.current(), ROOT);
+        // into the variation 1... c5, then to the end of its main continuation
+        let c5: NodeId = game.node(e4).unwrap().children()[1];
+        assert!(game.go_to(c5));
+        game.go_to_end();
+        assert_eq!(game.moves_to(game.current()).len(), 4);
+        assert_eq!(
+            game.board().to_fen(),
+            "rnbqkbnr/pp2pppp/3p4/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3"
+        );
+        assert!(game.go_back() && game.go_back());
+        assert_eq!(game.current(), c5);
+    }
+
+    #[test]
+    fn board_in_a_nested_variation() {
+        let (_, game) = read();
+        let e4: NodeId = game.node(ROOT).unwrap().children()[0];
+        let c5: NodeId = game.node(e4).unwrap().children()[1];
+        let nc3: NodeId = game.node(c5).unwrap().children()[1];
+        let nc6: NodeId = game.node(nc3).unwrap().children()[0];
+        assert_eq!(game.node(nc3).unwrap().starting_comments, vec!["Or"]);
+        assert_eq!(game.node(nc3).unwrap().comments, vec!["Closed"]);
+        assert_eq!(
+            game.board_at(nc6).unwrap().to_fen(),
+            "r1bqkbnr/pp1ppppp/2n5/2p5/4P3/2N5/PPPP1PPP/R1BQKBNR w KQkq - 2 3"
+        );
+        assert!(game.board_at(game.nodes.len()).is_none());
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let (pgn, game) = read();
+        let writer: PgnWriter = PgnWriter::new();
+        let text: String = writer.write(&game.to_pgn()).unwrap();
+        assert_eq!(text, writer.write(&pgn).unwrap());
+        // the variations are nested in order, with their comments
+        assert!(text
+            .contains("e5 (1... c5 {Sicilian} 2. Nf3 ({Or} 2. Nc3\n{Closed} 2... Nc6) 2... d6)"));
+        // and back again
+        let again: PgnGame = PgnReader::new(text.as_bytes()).next().unwrap().unwrap();
+        let again: Game = Game::from_pgn(&again).unwrap();
+        assert_eq!(writer.write(&again.to_pgn()).unwrap(), text);
+    }
+}
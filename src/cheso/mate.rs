@@ -0,0 +1,260 @@
+use crate::cheso::board::Board;
+use crate::cheso::movement::Movement;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Solution tree of a mate problem.
+///
+/// A movement of the attacker, and every legal reply of the defender with the continuation that mates after it.
+/// A movement without replies gives mate.
+#[derive(Clone, Debug)]
+pub struct MateTree {
+    pub movement: Movement,
+    pub defences: Vec<(Movement, MateTree)>,
+}
+
+impl MateTree {
+    /// Get the number of movements of the attacker needed to mate (in the longest variation).
+    pub fn length(&self) -> u32 {
+        1 + self
+            .defences
+            .iter()
+            .map(|(_, tree)| tree.length())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Write the tree with one defence per line, indented by its depth.
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        for (defence, tree) in &self.defences {
+            writeln!(f)?;
+            write!(
+                f,
+                "{:indent$}{} {}",
+                "",
+                defence,
+                tree.movement,
+                indent = indent
+            )?;
+            tree.write(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MateTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.movement)?;
+        self.write(f, 2)
+    }
+}
+
+/// Mate problem solver.
+///
+/// Proves or refutes mate in a number of movements with an exhaustive AND/OR search without pruning:
+/// some movement of the attacker must mate against every reply of the defender.
+/// Positions already proven or refuted are remembered, with the number of movements searched.
+///
+/// When only checks are allowed for the attacker, the search is much faster, but quiet keys are not found
+/// (so a refutation is only a proof for problems whose solutions consist of checks).
+pub struct MateSolver {
+    pub checks_only: bool,
+    nodes: u64,
+    proven: HashMap<u64, u32>,
+    refuted: HashMap<u64, u32>,
+}
+
+impl Default for MateSolver {
+    fn default() -> MateSolver {
+        MateSolver::new()
+    }
+}
+
+impl MateSolver {
+    /// Make a new solver, allowing every movement of the attacker.
+    pub fn new() -> MateSolver {
+        MateSolver {
+            checks_only: false,
+            nodes: 0,
+            proven: HashMap::new(),
+            refuted: HashMap::new(),
+        }
+    }
+
+    /// Get the number of positions visited so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Solve a mate in (at most) the given number of movements of the side to move.
+    ///
+    /// Returns the solution tree, with the shortest mate in every variation, or nothing if there is no mate.
+    pub fn solve(&mut self, board: &mut Board, moves: u32) -> Option<MateTree> {
+        (1..=moves).find_map(|length| self.tree(board, length))
+    }
+
+    /// Get all the key movements that mate in (at most) the given number of movements.
+    ///
+    /// A sound problem has a single key, so more than one reveals a cook.
+    pub fn keys(&mut self, board: &mut Board, moves: u32) -> Vec<Movement> {
+        let mut keys: Vec<Movement> = Vec::new();
+        if moves == 0 {
+            return keys;
+        }
+        for movement in self.attacker_moves(board) {
+            board.make_move(&movement);
+            if self.defender_loses(board, moves - 1) {
+                keys.push(movement);
+            }
+            board.unmake_move(&movement);
+        }
+        keys
+    }
+
+    /// Get the candidate movements of the attacker: checks first, then captures, then the rest.
+    fn attacker_moves(&mut self, board: &mut Board) -> Vec<Movement> {
+        let mut checks: Vec<Movement> = Vec::new();
+        let mut captures: Vec<Movement> = Vec::new();
+        let mut quiets: Vec<Movement> = Vec::new();
+        for movement in board.gen_legal_moves() {
+            board.make_move(&movement);
+            let check: bool = board.in_check();
+            board.unmake_move(&movement);
+            if check {
+                checks.push(movement);
+            } else if self.checks_only {
+                continue;
+            } else if movement.is_capture() {
+                captures.push(movement);
+            } else {
+                quiets.push(movement);
+            }
+        }
+        checks.append(&mut captures);
+        checks.append(&mut quiets);
+        checks
+    }
+
+    /// Check if the attacker (the side to move) mates in (at most) the given number of movements.
+    fn attacker_wins(&mut self, board: &mut Board, moves: u32) -> bool {
+        self.nodes += 1;
+        let hash: u64 = board.hash();
+        if self
+            .proven
+            .get(&hash)
+            .is_some_and(|proven| *proven <= moves)
+        {
+            return true;
+        }
+        if moves == 0
+            || self
+                .refuted
+                .get(&hash)
+                .is_some_and(|refuted| *refuted >= moves)
+        {
+            return false;
+        }
+        for movement in self.attacker_moves(board) {
+            board.make_move(&movement);
+            let mate: bool = self.defender_loses(board, moves - 1);
+            board.unmake_move(&movement);
+            if mate {
+                self.proven.insert(hash, moves);
+                return true;
+            }
+        }
+        self.refuted.insert(hash, moves);
+        false
+    }
+
+    /// Check if the defender (the side to move) is mated now or in (at most) the given number of movements.
+    ///
+    /// Stalemate is a defence.
+    fn defender_loses(&mut self, board: &mut Board, moves: u32) -> bool {
+        self.nodes += 1;
+        let replies: Vec<Movement> = board.gen_legal_moves();
+        if replies.is_empty() {
+            return board.in_check();
+        }
+        if moves == 0 {
+            return false;
+        }
+        for reply in replies {
+            board.make_move(&reply);
+            let mate: bool = self.attacker_wins(board, moves);
+            board.unmake_move(&reply);
+            if !mate {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Build the solution tree of a mate in exactly the given number of movements (if any).
+    fn tree(&mut self, board: &mut Board, moves: u32) -> Option<MateTree> {
+        if !self.attacker_wins(board, moves) {
+            return None;
+        }
+        for movement in self.attacker_moves(board) {
+            board.make_move(&movement);
+            if !self.defender_loses(board, moves - 1) {
+                board.unmake_move(&movement);
+                continue;
+            }
+            let mut defences: Vec<(Movement, MateTree)> = Vec::new();
+            for reply in board.gen_legal_moves() {
+                board.make_move(&reply);
+                if let Some(tree) = self.solve(board, moves - 1) {
+                    defences.push((reply, tree));
+                }
+                board.unmake_move(&reply);
+            }
+            board.unmake_move(&movement);
+            return Some(MateTree { movement, defences });
+        }
+        None
+    }
+}
+
+/// Solve a mate in (at most) the given number of movements of the side to move, returning the solution tree.
+pub fn solve_mate(board: &mut Board, moves: u32) -> Option<MateTree> {
+    MateSolver::new().solve(board, moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scholars_mate_in_one() {
+        let mut board: Board =
+            Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")
+                .unwrap();
+        let tree: MateTree = solve_mate(&mut board, 1).unwrap();
+        assert_eq!(tree.movement.to_string(), "h5f7");
+        assert!(tree.defences.is_empty());
+    }
+
+    #[test]
+    fn morphy_mate_in_two() {
+        // the key is quiet: 1. Ra6! bxa6 2. b7#
+        let mut board: Board = Board::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        assert!(solve_mate(&mut board, 1).is_none());
+        let tree: MateTree = solve_mate(&mut board, 2).unwrap();
+        assert_eq!(tree.movement.to_string(), "a1a6");
+        assert_eq!(tree.length(), 2);
+        assert!(!tree.defences.is_empty());
+        let keys: Vec<String> = MateSolver::new()
+            .keys(&mut board, 2)
+            .iter()
+            .map(Movement::to_string)
+            .collect();
+        assert_eq!(keys, vec![String::from("a1a6")]);
+        let mut checks: MateSolver = MateSolver {
+            checks_only: true,
+            ..MateSolver::new()
+        };
+        assert!(checks.solve(&mut board, 2).is_none());
+        assert_eq!(board.to_fen(), "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1");
+    }
+}
@@ -0,0 +1,470 @@
+use crate::cheso::board::Board;
+use crate::cheso::eval::evaluate;
+use crate::cheso::movement::Movement;
+use crate::cheso::ordering::see;
+use crate::cheso::search::{Limits, SearchInfo, SearchResult};
+use crate::cheso::time::TimeManager;
+use crate::cheso::tt::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default exploration constant of PUCT.
+const DEFAULT_EXPLORATION: f32 = 1.5;
+
+/// Default memory of the tree in megabytes.
+const DEFAULT_MEGABYTES: usize = 16;
+
+/// Maximum number of legal movements of a position, so the number of nodes added by an expansion.
+const MAX_MOVES: usize = 256;
+
+/// Minimum number of visits of a node of the principal variation to count towards the depth of the search.
+const DEPTH_VISITS: u32 = 16;
+
+/// Time between progress reports.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Scale (in centipawns) of the conversion between scores and values.
+const VALUE_SCALE: f32 = 400.0;
+
+/// Policy guiding a Monte Carlo tree search.
+///
+/// It gives the prior probability of each legal movement of a position, and the value of a position
+/// (between -1 and 1, from the point of view of the side to move), like the two heads of a neural network.
+pub trait Policy {
+    /// Get the prior probabilities of the legal movements of a position (in the same order).
+    fn priors(&self, board: &mut Board, moves: &[Movement]) -> Vec<f32>;
+
+    /// Get the value of a position, from the static evaluation by default.
+    fn value(&self, board: &mut Board) -> f32 {
+        (evaluate(board) as f32 / VALUE_SCALE).tanh()
+    }
+}
+
+/// Heuristic policy.
+///
+/// Priors are a softmax of simple movement features: captures (by the value of the captured piece and
+/// the static exchange evaluation), promotions and checks.
+pub struct HeuristicPolicy;
+
+impl Policy for HeuristicPolicy {
+    fn priors(&self, board: &mut Board, moves: &[Movement]) -> Vec<f32> {
+        let logits: Vec<f32> = moves
+            .iter()
+            .map(|movement| {
+                let mut logit: f32 = 0.0;
+                if let Some(capture) = movement.capture {
+                    logit += 0.5 + capture.value() as f32 / 300.0;
+                    if see(board, movement) < 0 {
+                        logit -= 1.5;
+                    }
+                }
+                if movement.is_promotion() {
+                    logit += 1.0;
+                }
+                board.make_move(movement);
+                if board.in_check() {
+                    logit += 1.0;
+                }
+                board.unmake_move(movement);
+                logit
+            })
+            .collect();
+        let total: f32 = logits.iter().map(|logit| logit.exp()).sum();
+        logits.iter().map(|logit| logit.exp() / total).collect()
+    }
+}
+
+/// Node of a search tree.
+///
+/// Its statistics are from the point of view of the side that made the movement leading to it.
+struct Node {
+    movement: Option<Movement>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    prior: f32,
+    visits: u32,
+    value: f32,
+    terminal: Option<f32>,
+}
+
+impl Node {
+    /// Get the mean value of the node.
+    fn mean(&self) -> f32 {
+        self.value / std::cmp::max(1, self.visits) as f32
+    }
+}
+
+/// Monte Carlo tree search.
+///
+/// An alternative to alpha-beta in the style of AlphaZero: each playout descends the tree choosing the child
+/// that maximizes its mean value plus an exploration bonus proportional to its prior (PUCT),
+/// expands the leaf with the priors of the policy, and backs up the value of the leaf given by the policy.
+/// The movement played is the most visited one.
+pub struct MonteCarloSearch {
+    pub exploration: f32,
+    policy: Box<dyn Policy + Send>,
+    tree: Vec<Node>,
+    max_nodes: usize,
+}
+
+impl Default for MonteCarloSearch {
+    fn default() -> MonteCarloSearch {
+        MonteCarloSearch::new(Box::new(HeuristicPolicy))
+    }
+}
+
+impl MonteCarloSearch {
+    /// Make a new Monte Carlo tree search guided by a policy.
+    pub fn new(policy: Box<dyn Policy + Send>) -> MonteCarloSearch {
+        MonteCarloSearch {
+            exploration: DEFAULT_EXPLORATION,
+            policy,
+            tree: Vec::new(),
+            max_nodes: MonteCarloSearch::capacity(DEFAULT_MEGABYTES),
+        }
+    }
+
+    /// Get the number of nodes of a tree fitting in the given size in megabytes.
+    ///
+    /// Each node is stored once in the tree and once in the children of its parent.
+    fn capacity(megabytes: usize) -> usize {
+        let node: usize = std::mem::size_of::<Node>() + std::mem::size_of::<usize>();
+        std::cmp::max(MAX_MOVES + 1, megabytes * 1024 * 1024 / node)
+    }
+
+    /// Limit the memory of the tree to the given size in megabytes (the search stops when it is full).
+    pub fn set_memory(&mut self, megabytes: usize) {
+        self.max_nodes = MonteCarloSearch::capacity(megabytes);
+        self.tree = Vec::new();
+    }
+
+    /// Search the current position until reaching the limits (the nodes are the number of playouts).
+    ///
+    /// The depth is the length of the principal variation, counting only nodes visited a minimum number of times:
+    /// the search stops when it reaches the depth, or ends in a mate or a draw.
+    /// A position without legal movements is not searched.
+    /// While the ponder flag is raised the clock is ignored, and it starts when the flag is lowered.
+    /// Progress is reported periodically and at the end, with the score converted to centipawns.
+    pub fn search(
+        &mut self,
+        board: &mut Board,
+        limits: &Limits,
+        stop: &AtomicBool,
+        ponder: &AtomicBool,
+        overhead: Duration,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let start: Instant = Instant::now();
+        let mut pondering: bool = ponder.load(Ordering::Relaxed);
+        let mut time: Option<TimeManager> = match pondering {
+            true => None,
+            false => TimeManager::new(limits, board.side_to_move, overhead),
+        };
+        self.tree.clear();
+        self.tree.reserve_exact(self.max_nodes);
+        self.tree.push(Node {
+            movement: None,
+            parent: None,
+            children: Vec::new(),
+            prior: 1.0,
+            visits: 0,
+            value: 0.0,
+            terminal: None,
+        });
+        let mut playouts: u64 = 0;
+        let mut last_report: Instant = start;
+        loop {
+            self.playout(board);
+            playouts += 1;
+            if playouts.is_multiple_of(64) && last_report.elapsed() >= REPORT_INTERVAL {
+                report(&self.info(playouts, start.elapsed()));
+                last_report = Instant::now();
+            }
+            // the expected movement was played, so the clock starts now
+            if pondering && playouts.is_multiple_of(64) && !ponder.load(Ordering::Relaxed) {
+                pondering = false;
+                time = TimeManager::new(limits, board.side_to_move, overhead);
+            }
+            let finished: bool = self.tree[0].terminal.is_some()
+                || limits.nodes.is_some_and(|nodes| playouts >= nodes)
+                || limits.depth.is_some_and(|depth| self.reached_depth(depth))
+                || self.tree.len() + MAX_MOVES > self.max_nodes
+                || self.tree[0].children.len() == 1 && time.is_some()
+                || playouts.is_multiple_of(64)
+                    && (stop.load(Ordering::Relaxed)
+                        || time.as_ref().is_some_and(TimeManager::soft_limit_reached));
+            if finished {
+                break;
+            }
+        }
+        let info: SearchInfo = self.info(playouts, start.elapsed());
+        report(&info);
+        SearchResult {
+            movement: info.pv.first().copied(),
+            score: info.score,
+            depth: info.depth,
+            nodes: playouts,
+            pv: info.pv,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Run a playout from the root: select a leaf, expand it and back up its value.
+    fn playout(&mut self, board: &mut Board) {
+        let mut index: usize = 0;
+        let mut path: Vec<Movement> = Vec::new();
+        while !self.tree[index].children.is_empty() {
+            index = self.select(index);
+            if let Some(movement) = self.tree[index].movement {
+                board.make_move(&movement);
+                path.push(movement);
+            }
+        }
+        // value of the leaf for the side to move
+        let value: f32 = match self.tree[index].terminal {
+            Some(value) => value,
+            None => self.expand(board, index),
+        };
+        for movement in path.iter().rev() {
+            board.unmake_move(movement);
+        }
+        let mut current: Option<usize> = Some(index);
+        let mut value: f32 = -value;
+        while let Some(index) = current {
+            self.tree[index].visits += 1;
+            self.tree[index].value += value;
+            value = -value;
+            current = self.tree[index].parent;
+        }
+    }
+
+    /// Select the child of a node maximizing its mean value plus the exploration bonus.
+    ///
+    /// Unvisited children take the mean value of their parent, slightly reduced.
+    fn select(&self, index: usize) -> usize {
+        let node: &Node = &self.tree[index];
+        let sqrt_visits: f32 = (node.visits as f32).sqrt();
+        let first_play: f32 = -node.mean() - 0.2;
+        let mut best: usize = node.children[0];
+        let mut best_score: f32 = f32::NEG_INFINITY;
+        for child in &node.children {
+            let child_node: &Node = &self.tree[*child];
+            let mean: f32 = match child_node.visits {
+                0 => first_play,
+                _ => child_node.mean(),
+            };
+            let score: f32 = mean
+                + self.exploration * child_node.prior * sqrt_visits
+                    / (1 + child_node.visits) as f32;
+            if score > best_score {
+                best = *child;
+                best_score = score;
+            }
+        }
+        best
+    }
+
+    /// Expand a leaf with the priors of the policy, returning its value for the side to move.
+    ///
+    /// Mates and draws are marked as terminal, with their exact value.
+    /// A drawn root is still searched when it has legal movements, since a movement must be chosen.
+    fn expand(&mut self, board: &mut Board, index: usize) -> f32 {
+        let moves: Vec<Movement> = board.gen_legal_moves();
+        let terminal: Option<f32> = match moves.is_empty() {
+            true if board.in_check() => Some(-1.0),
+            true => Some(0.0),
+            false if index > 0 && board.is_draw() => Some(0.0),
+            false => None,
+        };
+        if let Some(value) = terminal {
+            self.tree[index].terminal = terminal;
+            return value;
+        }
+        let priors: Vec<f32> = self.policy.priors(board, &moves);
+        self.tree[index].children.reserve_exact(moves.len());
+        for (movement, prior) in moves.iter().zip(priors) {
+            self.tree.push(Node {
+                movement: Some(*movement),
+                parent: Some(index),
+                children: Vec::new(),
+                prior,
+                visits: 0,
+                value: 0.0,
+                terminal: None,
+            });
+            let child: usize = self.tree.len() - 1;
+            self.tree[index].children.push(child);
+        }
+        self.policy.value(board)
+    }
+
+    /// Get the nodes of the principal variation, following the most visited children from the root.
+    fn principal_variation(&self) -> Vec<usize> {
+        let mut line: Vec<usize> = Vec::new();
+        let mut index: usize = 0;
+        while let Some(child) = self.tree[index]
+            .children
+            .iter()
+            .filter(|child| self.tree[**child].visits > 0)
+            .max_by_key(|child| self.tree[**child].visits)
+        {
+            index = *child;
+            line.push(index);
+        }
+        line
+    }
+
+    /// Get the depth of a principal variation, counting only its nodes visited a minimum number of times.
+    fn searched_depth(&self, line: &[usize]) -> i32 {
+        line.iter()
+            .take_while(|index| self.tree[**index].visits >= DEPTH_VISITS)
+            .count() as i32
+    }
+
+    /// Check if the principal variation reaches a depth, or ends in a mate or a draw before it.
+    fn reached_depth(&self, depth: i32) -> bool {
+        let line: Vec<usize> = self.principal_variation();
+        let searched: i32 = self.searched_depth(&line);
+        searched >= depth
+            || searched == line.len() as i32
+                && line
+                    .last()
+                    .is_some_and(|index| self.tree[*index].terminal.is_some())
+    }
+
+    /// Make the information about the progress of the search, following the most visited children.
+    fn info(&self, playouts: u64, time: Duration) -> SearchInfo {
+        let line: Vec<usize> = self.principal_variation();
+        let pv: Vec<Movement> = line
+            .iter()
+            .filter_map(|index| self.tree[*index].movement)
+            .collect();
+        // value for the side to move (exact at a terminal root)
+        let mean: f32 = match line.first() {
+            Some(index) => self.tree[*index].mean(),
+            None => self.tree[0].terminal.unwrap_or(0.0),
+        };
+        let score: f32 = VALUE_SCALE * mean.clamp(-0.999, 0.999).atanh();
+        SearchInfo {
+            depth: self.searched_depth(&line),
+            multipv: 1,
+            score: score as i32,
+            bound: Bound::Exact,
+            nodes: playouts,
+            time,
+            pv,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, limits: &Limits) -> SearchResult {
+        let mut board: Board = Board::from_fen(fen).unwrap();
+        let stop: AtomicBool = AtomicBool::new(false);
+        let ponder: AtomicBool = AtomicBool::new(false);
+        MonteCarloSearch::default().search(
+            &mut board,
+            limits,
+            &stop,
+            &ponder,
+            Duration::ZERO,
+            &mut |_| (),
+        )
+    }
+
+    #[test]
+    fn terminal_root_is_not_searched() {
+        let result: SearchResult = search("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", &Limits::default());
+        assert_eq!(result.movement, None);
+        assert_eq!(result.nodes, 1);
+    }
+
+    #[test]
+    fn search_stops_when_the_tree_is_full() {
+        let mut board: Board = Board::new();
+        let stop: AtomicBool = AtomicBool::new(false);
+        let ponder: AtomicBool = AtomicBool::new(false);
+        let mut mcts: MonteCarloSearch = MonteCarloSearch::default();
+        mcts.set_memory(1);
+        let result: SearchResult = mcts.search(
+            &mut board,
+            &Limits::default(),
+            &stop,
+            &ponder,
+            Duration::ZERO,
+            &mut |_| (),
+        );
+        assert!(result.movement.is_some());
+        assert!(mcts.tree.len() <= mcts.max_nodes);
+        assert!(mcts.tree.capacity() <= mcts.max_nodes);
+        assert!(
+            MonteCarloSearch::capacity(64)
+                * (std::mem::size_of::<Node>() + std::mem::size_of::<usize>())
+                <= 64 * 1024 * 1024
+        );
+    }
+
+    #[test]
+    fn drawn_root_is_searched() {
+        let limits: Limits = Limits {
+            nodes: Some(100),
+            ..Limits::default()
+        };
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3QK3 w - - 100 80",
+        ] {
+            let result: SearchResult = search(fen, &limits);
+            let mut board: Board = Board::from_fen(fen).unwrap();
+            assert!(board.gen_legal_moves().contains(&result.movement.unwrap()));
+            assert_eq!(result.nodes, 100);
+        }
+    }
+
+    #[test]
+    fn search_stops_at_depth() {
+        let limits: Limits = Limits {
+            depth: Some(3),
+            ..Limits::default()
+        };
+        let result: SearchResult = search(crate::cheso::board::STARTING_FEN, &limits);
+        assert_eq!(result.depth, 3);
+        assert!(result.movement.is_some());
+        let result: SearchResult = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &limits);
+        assert_eq!(
+            result.movement.map(|movement| movement.to_string()),
+            Some(String::from("a1a8"))
+        );
+    }
+
+    #[test]
+    fn clock_starts_at_ponderhit() {
+        let limits: Limits = Limits {
+            movetime: Some(Duration::from_millis(1)),
+            ..Limits::default()
+        };
+        let mut board: Board = Board::new();
+        let stop: AtomicBool = AtomicBool::new(false);
+        let ponder: AtomicBool = AtomicBool::new(true);
+        let start: Instant = Instant::now();
+        let result: SearchResult = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(100));
+                ponder.store(false, Ordering::Relaxed);
+            });
+            MonteCarloSearch::default().search(
+                &mut board,
+                &limits,
+                &stop,
+                &ponder,
+                Duration::ZERO,
+                &mut |_| (),
+            )
+        });
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(result.movement.is_some());
+    }
+}
@@ -1,5 +1,27 @@
 pub mod board;
+pub mod book;
 pub mod color;
+pub mod db;
+pub mod eco;
+pub mod endgame;
+pub mod epd;
+pub mod eval;
+pub mod game;
+pub mod mate;
+pub mod mcts;
 pub mod movement;
+pub mod ordering;
+pub mod pattern;
+pub mod pgn;
 pub mod piece;
+pub mod pns;
+pub mod search;
+pub mod skill;
 pub mod square;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
+pub mod tablebase;
+pub mod time;
+pub mod tt;
+pub mod uci;
+pub mod zobrist;
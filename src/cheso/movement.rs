@@ -4,13 +4,21 @@ use crate::cheso::square::Square;
 /// Knight translations.
 ///
 /// Constant to generate all possible moves of a knight.
-pub static KNIGHT_TRANSLATIONS: &'static [(isize, isize)] =
-    &[(1, 2), (2, 1), (-1, 2), (2, -1), (1, -2), (-2, 1)];
+pub static KNIGHT_TRANSLATIONS: &[(isize, isize)] = &[
+    (1, 2),
+    (2, 1),
+    (-1, 2),
+    (2, -1),
+    (1, -2),
+    (-2, 1),
+    (-1, -2),
+    (-2, -1),
+];
 
 /// King translations.
 ///
 /// Constant to generate all possible moves of a king.
-pub static KING_TRANSLATIONS: &'static [(isize, isize)] = &[
+pub static KING_TRANSLATIONS: &[(isize, isize)] = &[
     (0, 1),
     (1, 0),
     (0, -1),
@@ -24,15 +32,14 @@ pub static KING_TRANSLATIONS: &'static [(isize, isize)] = &[
 /// Promotion pieces.
 ///
 /// Constant to generate all possible promotions.
-pub static PROMOTION_PIECES: &'static [Piece] =
-    &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+pub static PROMOTION_PIECES: &[Piece] = &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
 
 /// Movement representation.
 ///
 /// A movement is represented by the original square of the piece, the square where it goes.
 /// Pawn movements include a promotion field to store the piece which replaces the pawn.
 /// Castling can be detected by looking at the number of files moved by the king.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Movement {
     pub from: Square,
     pub to: Square,
@@ -40,9 +47,30 @@ pub struct Movement {
     pub promotion: Option<Piece>,
 }
 
+impl std::fmt::Display for Movement {
+    /// Format the movement in long algebraic notation, as used by the UCI protocol (e.g. `e2e4` or `e7e8q`).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        match self.promotion {
+            Some(piece) => write!(f, "{}", piece.to_char()),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Movement {
     /// Check if the movement is a capture.
     pub fn is_capture(&self) -> bool {
         self.capture.is_some()
     }
+
+    /// Check if the movement is a promotion.
+    pub fn is_promotion(&self) -> bool {
+        self.promotion.is_some()
+    }
+
+    /// Check if the movement is quiet (neither a capture nor a promotion).
+    pub fn is_quiet(&self) -> bool {
+        !self.is_capture() && !self.is_promotion()
+    }
 }
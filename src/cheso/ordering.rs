@@ -0,0 +1,423 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::movement::Movement;
+use crate::cheso::piece::Piece;
+use crate::cheso::search::MAX_PLY;
+use crate::cheso::square::Square;
+
+/// Maximum absolute value of a history score.
+const MAX_HISTORY: i32 = 16384;
+
+/// Score bonus of queen promotions, so they are tried before any capture.
+const PROMOTION_BONUS: i32 = 1000;
+
+/// Move ordering heuristics.
+///
+/// Information gathered during the search about which quiet movements caused beta cutoffs:
+/// killer movements (per ply), history scores (per side, origin and destination)
+/// and countermoves (per origin and destination of the previous movement).
+pub struct Heuristics {
+    killers: Vec<[Option<Movement>; 2]>,
+    history: Vec<i32>,
+    countermoves: Vec<Option<Movement>>,
+}
+
+impl Default for Heuristics {
+    fn default() -> Heuristics {
+        Heuristics::new()
+    }
+}
+
+impl Heuristics {
+    /// Make empty heuristics.
+    pub fn new() -> Heuristics {
+        Heuristics {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![0; 2 * 64 * 64],
+            countermoves: vec![None; 64 * 64],
+        }
+    }
+
+    /// Remove all gathered information.
+    pub fn clear(&mut self) {
+        *self = Heuristics::new();
+    }
+
+    /// Prepare the heuristics for a new search.
+    ///
+    /// Killer movements are discarded, since plies change between searches, and history scores are halved.
+    pub fn age(&mut self) {
+        self.killers
+            .iter_mut()
+            .for_each(|killers| *killers = [None; 2]);
+        self.history.iter_mut().for_each(|score| *score /= 2);
+    }
+
+    /// Get the index of a movement in the tables indexed by origin and destination.
+    fn index(movement: &Movement) -> usize {
+        movement.from.index() * 64 + movement.to.index()
+    }
+
+    /// Get the killer movements of a ply.
+    pub fn killers(&self, ply: usize) -> [Option<Movement>; 2] {
+        self.killers[ply]
+    }
+
+    /// Get the history score of a movement for a side.
+    pub fn history(&self, color: Color, movement: &Movement) -> i32 {
+        self.history[color.index() * 64 * 64 + Heuristics::index(movement)]
+    }
+
+    /// Get the countermove of a previous movement.
+    pub fn countermove(&self, previous: Option<Movement>) -> Option<Movement> {
+        previous.and_then(|previous| self.countermoves[Heuristics::index(&previous)])
+    }
+
+    /// Add a bonus (or a malus) to the history score of a movement.
+    ///
+    /// Scores are updated with gravity, so they stay between -MAX_HISTORY and MAX_HISTORY.
+    fn update_history(&mut self, color: Color, movement: &Movement, bonus: i32) {
+        let score: &mut i32 =
+            &mut self.history[color.index() * 64 * 64 + Heuristics::index(movement)];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+
+    /// Update the heuristics after a quiet movement caused a beta cutoff.
+    ///
+    /// The movement becomes a killer of the ply and the countermove of the previous movement,
+    /// its history score increases and the history scores of the quiet movements tried before it decrease.
+    pub fn update(
+        &mut self,
+        color: Color,
+        ply: usize,
+        depth: i32,
+        previous: Option<Movement>,
+        movement: &Movement,
+        tried: &[Movement],
+    ) {
+        let killers: &mut [Option<Movement>; 2] = &mut self.killers[ply];
+        if killers[0] != Some(*movement) {
+            killers[1] = killers[0];
+            killers[0] = Some(*movement);
+        }
+        if let Some(previous) = previous {
+            self.countermoves[Heuristics::index(&previous)] = Some(*movement);
+        }
+        let bonus: i32 = std::cmp::min(depth * depth, 400);
+        self.update_history(color, movement, bonus);
+        for quiet in tried {
+            if quiet != movement {
+                self.update_history(color, quiet, -bonus);
+            }
+        }
+    }
+}
+
+/// Get the value of a piece for static exchange evaluation.
+///
+/// The king gets a huge value, so exchanges which leave it in check are never good.
+fn exchange_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => 20000,
+        _ => piece.value(),
+    }
+}
+
+/// Static exchange evaluation.
+///
+/// Compute the material balance of the sequence of captures in the destination square of a movement,
+/// where each side always recaptures with its least valuable attacker (and can stop capturing at any point).
+pub fn see(board: &Board, movement: &Movement) -> i32 {
+    let color: Color = board.side_to_move;
+    let mut piece: Piece = match board.get_square(&movement.from) {
+        Some((piece, _)) => movement.promotion.unwrap_or(piece),
+        None => return 0,
+    };
+    let mut gain: Vec<i32> = vec![
+        movement.capture.map_or(0, exchange_value)
+            + movement
+                .promotion
+                .map_or(0, |promotion| promotion.value() - Piece::Pawn.value()),
+    ];
+    let mut ignored: Vec<Square> = vec![movement.from];
+    let mut side: Color = color.opposite();
+    while let Some((square, attacker)) =
+        board.get_least_valuable_attacker(&movement.to, side, &ignored)
+    {
+        let previous: i32 = gain[gain.len() - 1];
+        gain.push(exchange_value(piece) - previous);
+        if std::cmp::max(-previous, gain[gain.len() - 1]) < 0 {
+            break;
+        }
+        ignored.push(square);
+        piece = attacker;
+        side = side.opposite();
+    }
+    while gain.len() > 1 {
+        let last: i32 = gain.pop().unwrap_or(0);
+        let previous: &mut i32 = gain.last_mut().expect("gain is not empty");
+        *previous = -std::cmp::max(-*previous, last);
+    }
+    gain[0]
+}
+
+/// Get the most valuable victim / least valuable attacker score of a capture.
+pub fn mvv_lva(board: &Board, movement: &Movement) -> i32 {
+    let attacker: usize = board
+        .get_square(&movement.from)
+        .map_or(0, |(piece, _)| piece.index());
+    let victim: usize = movement.capture.map_or(0, |piece| piece.index() + 1);
+    (victim * 8) as i32 - attacker as i32
+}
+
+/// Stages of the move picker.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Stage {
+    HashMove,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Staged move picker.
+///
+/// Pseudo-legal movements are yielded in an order likely to produce early cutoffs:
+/// first the movement stored in the transposition table, then winning or equal captures and queen promotions
+/// (by most valuable victim / least valuable attacker), killer movements, the countermove of the previous movement,
+/// the rest of quiet movements (by history score) and finally losing captures and underpromotions.
+/// Movements are generated lazily: the movement of the transposition table is tried before generating anything,
+/// and quiet movements are only generated once captures, killers and the countermove have been tried.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Movement>,
+    killers: [Option<Movement>; 2],
+    countermove: Option<Movement>,
+    captures_only: bool,
+    good_captures: Vec<(Movement, i32)>,
+    quiets: Vec<(Movement, i32)>,
+    bad_captures: Vec<(Movement, i32)>,
+    tried: Vec<Movement>,
+}
+
+impl MovePicker {
+    /// Make a move picker for all pseudo-legal movements.
+    pub fn new(
+        hash_move: Option<Movement>,
+        killers: [Option<Movement>; 2],
+        countermove: Option<Movement>,
+    ) -> MovePicker {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            countermove,
+            captures_only: false,
+            good_captures: Vec::new(),
+            quiets: Vec::new(),
+            bad_captures: Vec::new(),
+            tried: Vec::new(),
+        }
+    }
+
+    /// Make a move picker for winning or equal captures and queen promotions (for quiescence search).
+    pub fn new_captures() -> MovePicker {
+        MovePicker {
+            captures_only: true,
+            ..MovePicker::new(None, [None; 2], None)
+        }
+    }
+
+    /// Generate and score the pseudo-legal captures and promotions of the position.
+    fn generate_captures(&mut self, board: &Board) {
+        for movement in board.gen_captures() {
+            if self.tried.contains(&movement) {
+                continue;
+            }
+            match movement.promotion {
+                Some(Piece::Queen) => {
+                    let score: i32 = PROMOTION_BONUS + mvv_lva(board, &movement);
+                    self.good_captures.push((movement, score));
+                }
+                Some(_) => {
+                    if !self.captures_only {
+                        self.bad_captures.push((movement, -PROMOTION_BONUS));
+                    }
+                }
+                None => {
+                    let score: i32 = mvv_lva(board, &movement);
+                    if see(board, &movement) >= 0 {
+                        self.good_captures.push((movement, score));
+                    } else if !self.captures_only {
+                        self.bad_captures.push((movement, score));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Generate and score the pseudo-legal quiet movements of the position.
+    fn generate_quiets(&mut self, board: &Board, heuristics: &Heuristics) {
+        for movement in board.gen_quiets() {
+            if !self.tried.contains(&movement) {
+                let score: i32 = heuristics.history(board.side_to_move, &movement);
+                self.quiets.push((movement, score));
+            }
+        }
+    }
+
+    /// Remove the movement with the highest score from a list.
+    fn pick_best(moves: &mut Vec<(Movement, i32)>) -> Option<Movement> {
+        let best: usize = moves
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, (_, score))| (*score, std::cmp::Reverse(*index)))
+            .map(|(index, _)| index)?;
+        Some(moves.remove(best).0)
+    }
+
+    /// Check a movement given before generation (the movement of the transposition table, a killer or the countermove).
+    ///
+    /// It may not be pseudo-legal (because of hash collisions, or because it was found in another position),
+    /// and it is skipped if it has already been tried.
+    fn try_movement(&mut self, board: &Board, movement: Option<Movement>) -> Option<Movement> {
+        let movement: Movement = movement?;
+        if self.tried.contains(&movement) || !board.is_pseudo_legal(&movement) {
+            return None;
+        }
+        self.tried.push(movement);
+        Some(movement)
+    }
+
+    /// Check a killer movement or countermove, which must be quiet.
+    fn try_quiet(&mut self, board: &Board, movement: Option<Movement>) -> Option<Movement> {
+        self.try_movement(board, movement.filter(Movement::is_quiet))
+    }
+
+    /// Get the next movement to try, if any.
+    pub fn next(&mut self, board: &Board, heuristics: &Heuristics) -> Option<Movement> {
+        loop {
+            let movement: Option<Movement> = match self.stage {
+                Stage::HashMove => {
+                    let hash_move: Option<Movement> = self.hash_move.take();
+                    self.try_movement(board, hash_move)
+                }
+                Stage::GoodCaptures => MovePicker::pick_best(&mut self.good_captures),
+                Stage::Killers => {
+                    let killer: Option<Movement> = self.killers[0].take();
+                    self.try_quiet(board, killer).or_else(|| {
+                        let killer: Option<Movement> = self.killers[1].take();
+                        self.try_quiet(board, killer)
+                    })
+                }
+                Stage::Countermove => {
+                    let countermove: Option<Movement> = self.countermove.take();
+                    self.try_quiet(board, countermove)
+                }
+                Stage::Quiets => MovePicker::pick_best(&mut self.quiets),
+                Stage::BadCaptures => MovePicker::pick_best(&mut self.bad_captures),
+                Stage::Done => return None,
+            };
+            if movement.is_some() {
+                return movement;
+            }
+            self.stage = match self.stage {
+                Stage::HashMove => {
+                    self.generate_captures(board);
+                    Stage::GoodCaptures
+                }
+                Stage::GoodCaptures if self.captures_only => Stage::Done,
+                Stage::GoodCaptures => Stage::Killers,
+                Stage::Killers => Stage::Countermove,
+                Stage::Countermove => {
+                    self.generate_quiets(board, heuristics);
+                    Stage::Quiets
+                }
+                Stage::Quiets => Stage::BadCaptures,
+                Stage::BadCaptures | Stage::Done => Stage::Done,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Play movements in long algebraic notation from the initial position.
+    fn play(moves: &[&str]) -> Board {
+        let mut board: Board = Board::new();
+        for text in moves {
+            let movement: Movement = find(&mut board, text);
+            board.make_move(&movement);
+        }
+        board
+    }
+
+    /// Find a legal movement in long algebraic notation (without promotion).
+    fn find(board: &mut Board, text: &str) -> Movement {
+        let bytes: &[u8] = text.as_bytes();
+        let square = |at: usize| -> Square {
+            Square::new(
+                (bytes[at + 1] - b'0') as isize,
+                (bytes[at] - b'a') as isize + 1,
+            )
+        };
+        board
+            .gen_legal_moves()
+            .into_iter()
+            .find(|movement| movement.from == square(0) && movement.to == square(2))
+            .unwrap()
+    }
+
+    #[test]
+    fn static_exchange_evaluation() {
+        // the queen is taken by the bishop for free
+        let mut board: Board = play(&["e2e4", "d7d5", "d1g4"]);
+        let bxg4: Movement = find(&mut board, "c8g4");
+        let dxe4: Movement = find(&mut board, "d5e4");
+        assert_eq!(see(&board, &bxg4), Piece::Queen.value());
+        assert_eq!(see(&board, &dxe4), 0);
+        assert!(mvv_lva(&board, &bxg4) > mvv_lva(&board, &dxe4));
+        // the pawn is defended by a pawn, and the other pawns by the king and a rook
+        let mut board: Board = play(&["e2e4", "e7e5", "d1h5", "d7d6"]);
+        for text in ["h5e5", "h5f7", "h5h7"] {
+            let movement: Movement = find(&mut board, text);
+            assert!(see(&board, &movement) < 0, "{}", text);
+        }
+    }
+
+    #[test]
+    fn picker_stages() {
+        let mut board: Board = play(&["e2e4", "d7d5", "d1g4", "g8f6"]);
+        let mut find = |text: &str| -> Movement { find(&mut board, text) };
+        let (hash_move, killer, countermove) = (find("g4h4"), find("b1c3"), find("d2d3"));
+        let (good, bad) = (find("e4d5"), [find("g4c8"), find("g4g7")]);
+        // not pseudo-legal in the position, so it is skipped
+        let wrong: Movement = Movement {
+            from: Square::new(1, 1),
+            to: Square::new(5, 1),
+            capture: None,
+            promotion: None,
+        };
+        let heuristics: Heuristics = Heuristics::new();
+        let mut picker: MovePicker = MovePicker::new(
+            Some(hash_move),
+            [Some(wrong), Some(killer)],
+            Some(countermove),
+        );
+        let mut moves: Vec<Movement> = Vec::new();
+        while let Some(movement) = picker.next(&board, &heuristics) {
+            moves.push(movement);
+        }
+        assert_eq!(moves.len(), board.gen_moves().len());
+        assert_eq!(&moves[..4], &[hash_move, good, killer, countermove]);
+        assert!(moves[4..moves.len() - 2].iter().all(Movement::is_quiet));
+        assert_eq!(&moves[moves.len() - 2..], &bad);
+        let mut picker: MovePicker = MovePicker::new_captures();
+        assert_eq!(picker.next(&board, &heuristics), Some(good));
+        assert_eq!(picker.next(&board, &heuristics), None);
+    }
+}
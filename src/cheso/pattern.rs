@@ -0,0 +1,380 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::endgame::Material;
+use crate::cheso::piece::Piece;
+use crate::cheso::square::Square;
+
+/// Pieces of material specifications, by piece index (the king is not counted).
+const MATERIAL_PIECES: [Piece; 5] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+];
+
+/// Pattern of positions, for searching games.
+///
+/// A pattern is a list of terms (optionally joined by `and`), all of which must match the position.
+/// A term can be negated with a leading `!`. The terms are:
+///
+/// - `material <side> vs <side>`: the exact material of the sides (in either order), such as `R vs B+2P`,
+///   where each side lists its pieces besides the king with optional counts (`K` alone for a bare king).
+/// - `imbalance <side> vs <side>`: the material left once the pieces both sides have are cancelled out,
+///   such as `imbalance R vs B+2P` for the exchange against a minor piece and two pawns.
+/// - `[count]<piece><file><rank>`: pieces on squares, with `?` as wildcard, such as `Ng5`, `p?7` or `2R?7`
+///   (at least two white rooks on the seventh rank). White pieces are uppercase and black ones lowercase,
+///   `?` is any piece and `.` an empty square.
+/// - `passed <pawn>`, `isolated <pawn>`, `doubled <pawn>`: a pawn structure feature of white (`P`) or black (`p`).
+/// - `pawns <template>`: pawns of the ranks 8 to 1 separated by `/`, with `P` and `p` for pawns,
+///   `-` for a square without pawns and `?` (or a number of them, as a digit) for any square,
+///   such as `pawns 8/8/8/3P4/8/8/8/8` for a white pawn on d5.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    terms: Vec<(bool, Term)>,
+}
+
+/// Term of a pattern.
+#[derive(Clone, Debug)]
+enum Term {
+    /// Exact material of both sides (counts by piece index).
+    Material([u8; 5], [u8; 5]),
+    /// Material difference between both sides.
+    Imbalance([u8; 5], [u8; 5]),
+    /// At least a number of squares with a piece (`None` for any piece, or for an empty square),
+    /// on a file and a rank (`None` for any).
+    Pieces {
+        count: usize,
+        piece: Option<Option<(Piece, Color)>>,
+        file: Option<isize>,
+        rank: Option<isize>,
+    },
+    Passed(Color),
+    Isolated(Color),
+    Doubled(Color),
+    /// Pawns of each square (`None` for any), from a1 to h8.
+    Pawns(Vec<Option<Option<Color>>>),
+}
+
+impl Pattern {
+    /// Parse a pattern.
+    pub fn parse(text: &str) -> Result<Pattern, String> {
+        let mut terms: Vec<(bool, Term)> = Vec::new();
+        let mut tokens = text.split_whitespace().filter(|token| *token != "and");
+        while let Some(token) = tokens.next() {
+            let (negated, token) = match token.strip_prefix('!') {
+                Some(token) => (true, token),
+                None => (false, token),
+            };
+            let mut argument = || -> Result<&str, String> {
+                tokens.next().ok_or(format!(
+                    "missing argument of '{}' in pattern '{}'",
+                    token, text
+                ))
+            };
+            let term: Term = match token {
+                "material" | "imbalance" => {
+                    let first: [u8; 5] = parse_material(argument()?)?;
+                    if argument()? != "vs" {
+                        return Err(format!("missing 'vs' in pattern '{}'", text));
+                    }
+                    let second: [u8; 5] = parse_material(argument()?)?;
+                    match token {
+                        "material" => Term::Material(first, second),
+                        _ => Term::Imbalance(first, second),
+                    }
+                }
+                "passed" | "isolated" | "doubled" => {
+                    let color: Color = match argument()? {
+                        "P" => Color::White,
+                        "p" => Color::Black,
+                        pawn => {
+                            return Err(format!("invalid pawn '{}' in pattern '{}'", pawn, text))
+                        }
+                    };
+                    match token {
+                        "passed" => Term::Passed(color),
+                        "isolated" => Term::Isolated(color),
+                        _ => Term::Doubled(color),
+                    }
+                }
+                "pawns" => Term::Pawns(parse_template(argument()?)?),
+                _ => parse_pieces(token)
+                    .ok_or(format!("invalid term '{}' in pattern '{}'", token, text))?,
+            };
+            terms.push((negated, term));
+        }
+        Ok(Pattern { terms })
+    }
+
+    /// Check if a position matches the pattern.
+    pub fn matches(&self, board: &Board) -> bool {
+        self.terms
+            .iter()
+            .all(|(negated, term)| term.matches(board) != *negated)
+    }
+}
+
+/// Parse the material of a side (e.g. `B+2P`, or `K` for a bare king), as counts by piece index.
+fn parse_material(text: &str) -> Result<[u8; 5], String> {
+    let mut counts: [u8; 5] = [0; 5];
+    for item in text.split('+') {
+        let digits: usize = item.chars().take_while(char::is_ascii_digit).count();
+        let count: u8 = match digits {
+            0 => 1,
+            _ => item[..digits]
+                .parse()
+                .map_err(|_| format!("invalid material '{}'", text))?,
+        };
+        match &item[digits..] {
+            "K" if digits == 0 => (),
+            letter
+                if letter.len() == 1
+                    && letter != "K"
+                    && letter.chars().all(|c| c.is_ascii_uppercase()) =>
+            {
+                let index: usize = match letter.chars().next().and_then(Piece::from_char) {
+                    Some(piece) => piece.index(),
+                    None => return Err(format!("invalid material '{}'", text)),
+                };
+                counts[index] = counts[index]
+                    .checked_add(count)
+                    .ok_or(format!("invalid material '{}'", text))?;
+            }
+            _ => return Err(format!("invalid material '{}'", text)),
+        }
+    }
+    Ok(counts)
+}
+
+/// Parse a term of pieces on squares (e.g. `Ng5`, `p?7`, `.e4` or `2R?7`).
+fn parse_pieces(token: &str) -> Option<Term> {
+    let digits: usize = token.chars().take_while(char::is_ascii_digit).count();
+    let count: usize = match digits {
+        0 => 1,
+        _ => token[..digits].parse().ok()?,
+    };
+    let (piece, file, rank) = match &token.as_bytes()[digits..] {
+        [piece, file, rank] => (*piece as char, *file as char, *rank as char),
+        _ => return None,
+    };
+    let piece: Option<Option<(Piece, Color)>> = match piece {
+        '?' => None,
+        '.' => Some(None),
+        letter => {
+            let color: Color = match letter.is_ascii_uppercase() {
+                true => Color::White,
+                false => Color::Black,
+            };
+            Some(Some((Piece::from_char(letter)?, color)))
+        }
+    };
+    let file: Option<isize> = match file {
+        '?' => None,
+        'a'..='h' => Some((file as u8 - b'a') as isize + 1),
+        _ => return None,
+    };
+    let rank: Option<isize> = match rank {
+        '?' => None,
+        '1'..='8' => Some((rank as u8 - b'1') as isize + 1),
+        _ => return None,
+    };
+    Some(Term::Pieces {
+        count,
+        piece,
+        file,
+        rank,
+    })
+}
+
+/// Parse a pawn structure template, from rank 8 to rank 1, into the pawns of each square from a1 to h8.
+fn parse_template(text: &str) -> Result<Vec<Option<Option<Color>>>, String> {
+    let rows: Vec<&str> = text.split('/').collect();
+    if rows.len() != 8 {
+        return Err(format!("wrong number of ranks in pawn template '{}'", text));
+    }
+    let mut squares: Vec<Option<Option<Color>>> = Vec::new();
+    for row in rows.iter().rev() {
+        let mut rank: Vec<Option<Option<Color>>> = Vec::new();
+        for c in row.chars() {
+            match c {
+                'P' => rank.push(Some(Some(Color::White))),
+                'p' => rank.push(Some(Some(Color::Black))),
+                '-' => rank.push(Some(None)),
+                '?' => rank.push(None),
+                '1'..='8' => rank.extend(std::iter::repeat_n(None, (c as u8 - b'0') as usize)),
+                _ => {
+                    return Err(format!(
+                        "invalid rank '{}' in pawn template '{}'",
+                        row, text
+                    ))
+                }
+            }
+        }
+        if rank.len() != 8 {
+            return Err(format!(
+                "invalid rank '{}' in pawn template '{}'",
+                row, text
+            ));
+        }
+        squares.extend(rank);
+    }
+    Ok(squares)
+}
+
+/// Get the pawns of a color, as their squares.
+fn pawns(board: &Board, color: Color) -> Vec<Square> {
+    board
+        .get_pieces(color)
+        .iter()
+        .filter(|(_, piece)| **piece == Piece::Pawn)
+        .map(|(square, _)| *square)
+        .collect()
+}
+
+impl Term {
+    /// Check if a position matches the term.
+    fn matches(&self, board: &Board) -> bool {
+        let material = |color: Color| -> [u8; 5] {
+            let material: Material = Material::of(board);
+            MATERIAL_PIECES.map(|piece| material.count(color, piece))
+        };
+        match self {
+            Term::Material(first, second) => {
+                let (white, black) = (material(Color::White), material(Color::Black));
+                (white == *first && black == *second) || (white == *second && black == *first)
+            }
+            Term::Imbalance(first, second) => {
+                let (white, black) = (material(Color::White), material(Color::Black));
+                let difference = |a: &[u8; 5], b: &[u8; 5]| -> [i32; 5] {
+                    std::array::from_fn(|index| a[index] as i32 - b[index] as i32)
+                };
+                let expected: [i32; 5] = difference(first, second);
+                let actual: [i32; 5] = difference(&white, &black);
+                actual == expected || actual == expected.map(|count| -count)
+            }
+            Term::Pieces {
+                count,
+                piece,
+                file,
+                rank,
+            } => {
+                let found: usize = (0..64)
+                    .map(Square::from_index)
+                    .filter(|square| {
+                        file.is_none_or(|file| square.file() == file)
+                            && rank.is_none_or(|rank| square.rank() == rank)
+                            && match piece {
+                                None => board.get_square(square).is_some(),
+                                Some(piece) => board.get_square(square) == *piece,
+                            }
+                    })
+                    .count();
+                found >= *count
+            }
+            Term::Passed(color) => {
+                let others: Vec<Square> = pawns(board, color.opposite());
+                pawns(board, *color).iter().any(|pawn| {
+                    others.iter().all(|other| {
+                        (other.file() - pawn.file()).abs() > 1
+                            || other.relative_rank(*color) <= pawn.relative_rank(*color)
+                    })
+                })
+            }
+            Term::Isolated(color) => {
+                let own: Vec<Square> = pawns(board, *color);
+                own.iter().any(|pawn| {
+                    own.iter()
+                        .all(|other| (other.file() - pawn.file()).abs() != 1)
+                })
+            }
+            Term::Doubled(color) => {
+                let own: Vec<Square> = pawns(board, *color);
+                own.iter().any(|pawn| {
+                    own.iter()
+                        .any(|other| other != pawn && other.file() == pawn.file())
+                })
+            }
+            Term::Pawns(template) => template.iter().enumerate().all(|(index, expected)| {
+                let square: Square = Square::from_index(index);
+                match (expected, board.get_square(&square)) {
+                    (None, _) => true,
+                    (Some(Some(color)), Some((Piece::Pawn, other))) => *color == other,
+                    (Some(Some(_)), _) => false,
+                    (Some(None), Some((Piece::Pawn, _))) => false,
+                    (Some(None), _) => true,
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check if a position matches a pattern.
+    fn matches(pattern: &str, fen: &str) -> bool {
+        Pattern::parse(pattern)
+            .unwrap()
+            .matches(&Board::from_fen(fen).unwrap())
+    }
+
+    /// Rook and pawn against bishop and two pawns, with a white passed pawn on d5 and black pawns doubled on f7 and f6.
+    const POSITION: &str = "4k3/5p2/5p2/3P4/8/8/8/R3K1b1 w - - 0 1";
+
+    #[test]
+    fn material() {
+        assert!(matches("material R+P vs B+2P", POSITION));
+        assert!(matches("material B+2P vs R+P", POSITION));
+        assert!(!matches("material R vs B+2P", POSITION));
+        assert!(matches("material K vs K", "4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(matches("imbalance R vs B+P", POSITION));
+        assert!(matches(
+            "imbalance R vs B",
+            "4k3/p7/8/8/8/8/P7/R3K1b1 w - - 0 1"
+        ));
+        assert!(!matches("imbalance R vs B", POSITION));
+    }
+
+    #[test]
+    fn pieces_on_squares() {
+        assert!(matches("Ra1", POSITION));
+        assert!(matches("bg1 and .e4", POSITION));
+        assert!(!matches("Re1", POSITION));
+        assert!(matches("2p?? p?7 ?f?", POSITION));
+        assert!(!matches("3p??", POSITION));
+        assert!(matches("P?5 !P?4", POSITION));
+        assert!(matches("2?a?", "r3k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn pawn_structures() {
+        assert!(matches("passed P isolated P doubled p", POSITION));
+        assert!(matches("passed p", POSITION));
+        assert!(!matches("passed P", "4k3/4p3/8/3P4/8/8/8/4K3 w - - 0 1"));
+        assert!(!matches("doubled P", POSITION));
+        assert!(!matches("isolated p", "4k3/5pp1/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(matches("pawns 8/5p2/5p2/3P4/8/8/8/8", POSITION));
+        assert!(matches("pawns ????????/8/8/3P4/8/8/8/--------", POSITION));
+        assert!(!matches("pawns 8/8/8/8/3P4/8/8/8", POSITION));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in [
+            "material R",
+            "material R vs",
+            "material R against B",
+            "material 200P+100P vs K",
+            "material 2K vs K",
+            "imbalance X vs B",
+            "passed N",
+            "Nz9",
+            "pawns 8/8",
+            "pawns 9/8/8/8/8/8/8/8",
+        ] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,833 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::movement::Movement;
+use std::io::BufRead;
+use std::time::Duration;
+
+/// Tags of the seven tag roster, in the order of the export format.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Suffix annotations, with their numeric annotation glyph.
+const SUFFIX_ANNOTATIONS: [(&str, u8); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+/// Game in Portable Game Notation.
+///
+/// The tag pairs of the header (in the order they were read), the comments before the first movement,
+/// the movements of the main line and the result (`1-0`, `0-1`, `1/2-1/2` or `*`).
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+/// Movement of a game, with its annotations.
+///
+/// Comments and numeric annotation glyphs follow the movement,
+/// and variations are alternatives to the movement, played from the position before it.
+/// The clock (`[%clk]`) and evaluation (`[%eval]`) commands of the comments are stored apart.
+#[derive(Clone, Debug)]
+pub struct PgnMove {
+    pub movement: Movement,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    pub variations: Vec<PgnVariation>,
+    pub clock: Option<Duration>,
+    pub eval: Option<PgnEval>,
+}
+
+/// Evaluation of a position after a movement, from the point of view of white.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PgnEval {
+    /// Score in centipawns.
+    Centipawns(i32),
+    /// Mate in a number of movements (negative if black mates).
+    Mate(i32),
+}
+
+/// Recursive annotation variation: the comments before its first movement, and its movements.
+#[derive(Clone, Debug, Default)]
+pub struct PgnVariation {
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+/// Error while reading a PGN text, with its position (line and column, from 1).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for PgnError {
+    /// Format the error with its position (e.g. `line 12, column 5: illegal movement 'Nf3'`).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::fmt::Display for PgnEval {
+    /// Format the evaluation as in an `[%eval]` command (e.g. `0.17`, `-1.05` or `#-3`).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PgnEval::Centipawns(score) => write!(
+                f,
+                "{}{}.{:02}",
+                if *score < 0 { "-" } else { "" },
+                score.abs() / 100,
+                score.abs() % 100
+            ),
+            PgnEval::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+impl PgnEval {
+    /// Parse an evaluation of an `[%eval]` command, ignoring the depth that may follow it (e.g. `0.17,20`).
+    pub fn parse(text: &str) -> Option<PgnEval> {
+        let text: &str = text.split(',').next()?.trim();
+        match text.strip_prefix('#') {
+            Some(moves) => moves.parse().ok().map(PgnEval::Mate),
+            None => text
+                .parse::<f64>()
+                .ok()
+                .map(|pawns| PgnEval::Centipawns((pawns * 100.0).round() as i32)),
+        }
+    }
+}
+
+/// Parse a clock time of a `[%clk]` command (e.g. `1:05:09` or `0:00:07.5`).
+fn parse_clock(text: &str) -> Option<Duration> {
+    let mut seconds: f64 = 0.0;
+    for field in text.trim().split(':') {
+        seconds = seconds * 60.0 + field.parse::<f64>().ok().filter(|value| *value >= 0.0)?;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Format a clock time for a `[%clk]` command, with tenths of seconds only if needed.
+fn format_clock(clock: &Duration) -> String {
+    let seconds: u64 = clock.as_secs();
+    let tenths: u32 = clock.subsec_millis() / 100;
+    let text: String = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match tenths {
+        0 => text,
+        _ => format!("{}.{}", text, tenths),
+    }
+}
+
+impl PgnMove {
+    /// Create a movement without annotations.
+    pub fn new(movement: Movement) -> PgnMove {
+        PgnMove {
+            movement,
+            nags: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+            clock: None,
+            eval: None,
+        }
+    }
+
+    /// Add a comment, taking out its clock and evaluation commands (e.g. `[%clk 0:03:12]`).
+    fn add_comment(&mut self, mut text: String) {
+        let mut from: usize = 0;
+        while let Some(start) = text[from..].find("[%").map(|start| from + start) {
+            let end: usize = match text[start..].find(']') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let command: String = text[start + 2..end].to_string();
+            let known: bool = match command.split_once(char::is_whitespace) {
+                Some(("clk", value)) => {
+                    self.clock = parse_clock(value);
+                    true
+                }
+                Some(("eval", value)) => {
+                    self.eval = PgnEval::parse(value);
+                    true
+                }
+                // other commands (e.g. `[%csl]`) are kept in the comment
+                _ => false,
+            };
+            if known {
+                text.replace_range(start..=end, "");
+                from = start;
+            } else {
+                from = end + 1;
+            }
+        }
+        let text: &str = text.trim();
+        if !text.is_empty() {
+            self.comments.push(text.to_string());
+        }
+    }
+}
+
+impl PgnGame {
+    /// Get the value of a tag, if present.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Set the value of a tag, adding it after the others if it is not present.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Remove a tag, if it is present.
+    pub fn remove_tag(&mut self, name: &str) {
+        self.tags.retain(|(tag, _)| tag != name);
+    }
+
+    /// Get the starting position of the game, from its `FEN` tag or the standard one.
+    pub fn board(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Ok(Board::new()),
+        }
+    }
+}
+
+/// Token of the PGN grammar.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(String),
+    San(String),
+}
+
+impl Token {
+    /// Describe the token, for error messages.
+    fn description(&self) -> String {
+        match self {
+            Token::Tag(name, _) => format!("tag pair '{}'", name),
+            Token::Comment(_) => String::from("comment"),
+            Token::Nag(nag) => format!("annotation glyph '${}'", nag),
+            Token::Open => String::from("'('"),
+            Token::Close => String::from("')'"),
+            Token::Result(result) => format!("result '{}'", result),
+            Token::San(san) => format!("movement '{}'", san),
+        }
+    }
+}
+
+/// Streaming reader of PGN games.
+///
+/// The input is read line by line, so files of any size can be read game by game.
+/// Each movement is validated against the position it is played in.
+/// After an error the rest of the game is skipped, and reading goes on with the next game.
+pub struct PgnReader<R: BufRead> {
+    input: R,
+    /// Characters of the current line, with its line feed.
+    line: Vec<char>,
+    /// Number of the current line (from 1).
+    line_number: usize,
+    /// Index of the next character in the current line.
+    column: usize,
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_game() {
+            Ok(game) => game.map(Ok),
+            Err(error) => {
+                self.skip_game();
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    /// Create a reader of the games of an input.
+    pub fn new(input: R) -> PgnReader<R> {
+        PgnReader {
+            input,
+            line: Vec::new(),
+            line_number: 0,
+            column: 0,
+        }
+    }
+
+    /// Make an error at a position.
+    fn error(line: usize, column: usize, message: String) -> PgnError {
+        PgnError {
+            line,
+            column: column + 1,
+            message,
+        }
+    }
+
+    /// Read the next line, skipping escaped lines (starting with `%`), returning false at the end of the input.
+    fn read_line(&mut self) -> Result<bool, PgnError> {
+        loop {
+            let mut text: String = String::new();
+            let read: usize = self.input.read_line(&mut text).map_err(|error| {
+                PgnReader::<R>::error(self.line_number + 1, 0, error.to_string())
+            })?;
+            self.column = 0;
+            if read == 0 {
+                self.line.clear();
+                return Ok(false);
+            }
+            self.line_number += 1;
+            if !text.starts_with('%') {
+                self.line = text
+                    .trim_end()
+                    .chars()
+                    .chain(std::iter::once('\n'))
+                    .collect();
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Get the next character without consuming it, or `None` at the end of the input.
+    fn peek(&mut self) -> Result<Option<char>, PgnError> {
+        while self.column >= self.line.len() {
+            if !self.read_line()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.line[self.column]))
+    }
+
+    /// Consume the next character.
+    fn bump(&mut self) -> Result<Option<char>, PgnError> {
+        let c: Option<char> = self.peek()?;
+        self.column += 1;
+        Ok(c)
+    }
+
+    /// Skip whitespace, and get the position (line and column) of the next character.
+    fn skip_whitespace(&mut self) -> Result<(usize, usize), PgnError> {
+        while let Some(c) = self.peek()? {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.column += 1;
+        }
+        Ok((self.line_number, self.column))
+    }
+
+    /// Read the next token, with its position.
+    ///
+    /// Move numbers (`12.` or `12...`) are skipped, and suffix annotations (`!?`) are read as glyphs.
+    fn read_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        loop {
+            let (line, column) = self.skip_whitespace()?;
+            let c: char = match self.bump()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let token: Token = match c {
+                '[' => self.read_tag(line, column)?,
+                '{' => {
+                    let mut text: String = String::new();
+                    loop {
+                        match self.bump()? {
+                            Some('}') => break,
+                            Some(c) => text.push(c),
+                            None => {
+                                return Err(PgnReader::<R>::error(
+                                    line,
+                                    column,
+                                    String::from("unterminated comment"),
+                                ))
+                            }
+                        }
+                    }
+                    Token::Comment(text.trim().to_string())
+                }
+                ';' => {
+                    let text: String = self.line[self.column..].iter().collect();
+                    self.column = self.line.len();
+                    Token::Comment(text.trim().to_string())
+                }
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '$' => {
+                    let digits: String = self.read_while(|c| c.is_ascii_digit())?;
+                    match digits.parse() {
+                        Ok(nag) => Token::Nag(nag),
+                        Err(_) => {
+                            return Err(PgnReader::<R>::error(
+                                line,
+                                column,
+                                format!("invalid annotation glyph '${}'", digits),
+                            ))
+                        }
+                    }
+                }
+                '!' | '?' => {
+                    let suffix: String =
+                        c.to_string() + &self.read_while(|c| c == '!' || c == '?')?;
+                    match SUFFIX_ANNOTATIONS.iter().find(|(text, _)| *text == suffix) {
+                        Some((_, nag)) => Token::Nag(*nag),
+                        None => {
+                            return Err(PgnReader::<R>::error(
+                                line,
+                                column,
+                                format!("invalid annotation '{}'", suffix),
+                            ))
+                        }
+                    }
+                }
+                c if c.is_ascii_alphanumeric() || c == '*' => {
+                    let symbol: String = c.to_string()
+                        + &self
+                            .read_while(|c| c.is_ascii_alphanumeric() || "+#=:-/_*.".contains(c))?;
+                    match symbol.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" | "*" => Token::Result(symbol),
+                        _ => {
+                            // skip move numbers, possibly glued to the movement
+                            let san: &str = match symbol.split_once('.') {
+                                Some((number, san))
+                                    if number.chars().all(|c| c.is_ascii_digit()) =>
+                                {
+                                    san.trim_start_matches('.')
+                                }
+                                _ => symbol.as_str(),
+                            };
+                            if san.is_empty() {
+                                continue;
+                            }
+                            Token::San(san.to_string())
+                        }
+                    }
+                }
+                _ => {
+                    return Err(PgnReader::<R>::error(
+                        line,
+                        column,
+                        format!("unexpected character '{}'", c),
+                    ))
+                }
+            };
+            return Ok(Some((token, line, column)));
+        }
+    }
+
+    /// Read the characters of the current line satisfying a predicate.
+    fn read_while(&mut self, predicate: impl Fn(char) -> bool) -> Result<String, PgnError> {
+        let mut text: String = String::new();
+        while let Some(c) = self.peek()? {
+            if !predicate(c) {
+                break;
+            }
+            text.push(c);
+            self.column += 1;
+        }
+        Ok(text)
+    }
+
+    /// Read a tag pair (e.g. `[White "Carlsen, Magnus"]`), after its opening bracket.
+    fn read_tag(&mut self, line: usize, column: usize) -> Result<Token, PgnError> {
+        let invalid = || PgnReader::<R>::error(line, column, String::from("invalid tag pair"));
+        self.skip_whitespace()?;
+        let name: String = self.read_while(|c| c.is_ascii_alphanumeric() || c == '_')?;
+        self.skip_whitespace()?;
+        if name.is_empty() || self.bump()? != Some('"') {
+            return Err(invalid());
+        }
+        let mut value: String = String::new();
+        loop {
+            match self.bump()? {
+                Some('"') => break,
+                Some('\\') => match self.bump()? {
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err(invalid()),
+                },
+                Some('\n') | None => return Err(invalid()),
+                Some(c) => value.push(c),
+            }
+        }
+        self.skip_whitespace()?;
+        match self.bump()? {
+            Some(']') => Ok(Token::Tag(name, value)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Read the next game, or `None` at the end of the input.
+    pub fn read_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let mut game: PgnGame = PgnGame::default();
+        let (line, column) = self.skip_whitespace()?;
+        while self.peek()? == Some('[') {
+            if let Some((Token::Tag(name, value), _, _)) = self.read_token()? {
+                game.tags.push((name, value));
+            }
+            self.skip_whitespace()?;
+        }
+        if game.tags.is_empty() && self.peek()?.is_none() {
+            return Ok(None);
+        }
+        let mut board: Board = game
+            .board()
+            .map_err(|message| PgnReader::<R>::error(line, column, message))?;
+        let (comments, moves, result) = self.read_variation(&mut board, false)?;
+        if game.tags.is_empty() && moves.is_empty() && result.is_none() && self.peek()?.is_none() {
+            return Ok(None);
+        }
+        game.comments = comments;
+        game.moves = moves;
+        game.result = result
+            .or_else(|| game.tag("Result").map(String::from))
+            .unwrap_or_else(|| String::from("*"));
+        Ok(Some(game))
+    }
+
+    /// Read the movements of a line (the main line, or a variation after its opening parenthesis),
+    /// with the result at the end of the main line.
+    ///
+    /// The main line ends with a result, at the next tag pair, or at the end of the input.
+    /// The movements of a variation are unmade before returning, leaving the board unchanged.
+    #[allow(clippy::type_complexity)]
+    fn read_variation(
+        &mut self,
+        board: &mut Board,
+        nested: bool,
+    ) -> Result<(Vec<String>, Vec<PgnMove>, Option<String>), PgnError> {
+        let mut comments: Vec<String> = Vec::new();
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut result: Option<String> = None;
+        loop {
+            let (line, column) = self.skip_whitespace()?;
+            if !nested && self.peek()? == Some('[') {
+                break;
+            }
+            let (token, line, column) = match self.read_token()? {
+                Some(token) => token,
+                None if nested => {
+                    return Err(PgnReader::<R>::error(
+                        line,
+                        column,
+                        String::from("unterminated variation"),
+                    ))
+                }
+                None => break,
+            };
+            match (token, moves.last_mut()) {
+                (Token::Comment(text), Some(last)) => last.add_comment(text),
+                (Token::Comment(text), None) => comments.push(text),
+                (Token::Nag(nag), Some(last)) => last.nags.push(nag),
+                (Token::Open, Some(last)) => {
+                    board.unmake_move(&last.movement);
+                    let (comments, moves, _) = self.read_variation(board, true)?;
+                    board.make_move(&last.movement);
+                    last.variations.push(PgnVariation { comments, moves });
+                }
+                (Token::Close, _) if nested => break,
+                (Token::Result(text), _) if !nested => {
+                    result = Some(text);
+                    break;
+                }
+                (Token::San(san), _) => {
+                    let movement: Movement = board.parse_san(&san).ok_or_else(|| {
+                        PgnReader::<R>::error(
+                            line,
+                            column,
+                            format!("illegal or ambiguous movement '{}'", san),
+                        )
+                    })?;
+                    board.make_move(&movement);
+                    moves.push(PgnMove::new(movement));
+                }
+                (token, _) => {
+                    return Err(PgnReader::<R>::error(
+                        line,
+                        column,
+                        format!("unexpected {}", token.description()),
+                    ))
+                }
+            }
+        }
+        if nested {
+            for movement in moves.iter().rev() {
+                board.unmake_move(&movement.movement);
+            }
+        }
+        Ok((comments, moves, result))
+    }
+
+    /// Skip the rest of the current game, up to the tag pairs of the next one.
+    fn skip_game(&mut self) {
+        let mut in_tags: bool = self.line.first() == Some(&'[');
+        while let Ok(true) = self.read_line() {
+            let is_tag: bool = self.line.first() == Some(&'[');
+            if is_tag && !in_tags {
+                return;
+            }
+            in_tags = is_tag;
+        }
+    }
+}
+
+/// Writer of games in the PGN export format.
+///
+/// Tags of the seven tag roster come first, followed by the `SetUp` and `FEN` tags of a game not starting
+/// from the standard position, and by the other tags in their order.
+/// Movetext lines are wrapped at a number of columns, and black movements get a move number (`12...`)
+/// at the start of a variation and after a comment or a variation.
+#[derive(Clone, Debug)]
+pub struct PgnWriter {
+    /// Write comments.
+    pub comments: bool,
+    /// Write variations.
+    pub variations: bool,
+    /// Write the clock times (`[%clk]`) of the movements.
+    pub clocks: bool,
+    /// Write the evaluations (`[%eval]`) of the movements.
+    pub evals: bool,
+    /// Maximum length of movetext lines.
+    pub columns: usize,
+}
+
+impl Default for PgnWriter {
+    fn default() -> Self {
+        PgnWriter::new()
+    }
+}
+
+impl PgnWriter {
+    /// Create a writer with all annotations, wrapping lines at 80 columns.
+    pub fn new() -> PgnWriter {
+        PgnWriter {
+            comments: true,
+            variations: true,
+            clocks: true,
+            evals: true,
+            columns: 80,
+        }
+    }
+
+    /// Write a game, followed by an empty line.
+    pub fn write(&self, game: &PgnGame) -> Result<String, String> {
+        let mut text: String = String::new();
+        let result: &str = match game.result.as_str() {
+            "" => game.tag("Result").unwrap_or("*"),
+            result => result,
+        };
+        for name in SEVEN_TAG_ROSTER {
+            let value: &str = match name {
+                "Result" => result,
+                "Date" => game.tag(name).unwrap_or("????.??.??"),
+                _ => game.tag(name).unwrap_or("?"),
+            };
+            text.push_str(&PgnWriter::tag(name, value));
+        }
+        if let Some(fen) = game.tag("FEN") {
+            text.push_str(&PgnWriter::tag("SetUp", "1"));
+            text.push_str(&PgnWriter::tag("FEN", fen));
+        }
+        for (name, value) in &game.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                text.push_str(&PgnWriter::tag(name, value));
+            }
+        }
+        text.push('\n');
+        let mut board: Board = game.board()?;
+        let mut tokens: Vec<String> = Vec::new();
+        if self.comments {
+            tokens.extend(
+                game.comments
+                    .iter()
+                    .map(|comment| PgnWriter::comment(comment)),
+            );
+        }
+        self.write_moves(&mut board, &game.moves, &mut tokens);
+        tokens.push(result.to_string());
+        let mut line: String = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > self.columns {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        text.push_str(&line);
+        text.push_str("\n\n");
+        Ok(text)
+    }
+
+    /// Write a tag pair, escaping its value.
+    fn tag(name: &str, value: &str) -> String {
+        format!(
+            "[{} \"{}\"]\n",
+            name,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    }
+
+    /// Write a comment, removing the braces that would end it.
+    fn comment(text: &str) -> String {
+        format!("{{{}}}", text.replace('}', ""))
+    }
+
+    /// Write the movements of a line as tokens, leaving the board unchanged.
+    ///
+    /// A black movement is numbered at the start of the line, or after a comment or a variation.
+    fn write_moves(&self, board: &mut Board, moves: &[PgnMove], tokens: &mut Vec<String>) {
+        let mut numbered: bool = true;
+        for node in moves {
+            match board.side_to_move {
+                Color::White => tokens.push(format!("{}.", board.fullmove_number)),
+                Color::Black if numbered => tokens.push(format!("{}...", board.fullmove_number)),
+                Color::Black => (),
+            }
+            tokens.push(board.to_san(&node.movement));
+            tokens.extend(node.nags.iter().map(|nag| format!("${}", nag)));
+            numbered = false;
+            let mut commands: Vec<String> = Vec::new();
+            if let (true, Some(eval)) = (self.evals, &node.eval) {
+                commands.push(format!("[%eval {}]", eval));
+            }
+            if let (true, Some(clock)) = (self.clocks, &node.clock) {
+                commands.push(format!("[%clk {}]", format_clock(clock)));
+            }
+            let mut comments: Vec<String> = match self.comments {
+                true => node.comments.clone(),
+                false => Vec::new(),
+            };
+            if !commands.is_empty() {
+                match comments.first_mut() {
+                    Some(first) => *first = format!("{} {}", commands.join(" "), first),
+                    None => comments.push(commands.join(" ")),
+                }
+            }
+            for comment in &comments {
+                tokens.push(PgnWriter::comment(comment));
+                numbered = true;
+            }
+            if self.variations {
+                for variation in &node.variations {
+                    let mut variation_tokens: Vec<String> = Vec::new();
+                    if self.comments {
+                        variation_tokens.extend(
+                            variation
+                                .comments
+                                .iter()
+                                .map(|comment| PgnWriter::comment(comment)),
+                        );
+                    }
+                    self.write_moves(board, &variation.moves, &mut variation_tokens);
+                    if let Some(first) = variation_tokens.first_mut() {
+                        first.insert(0, '(');
+                    }
+                    if let Some(last) = variation_tokens.last_mut() {
+                        last.push(')');
+                    }
+                    tokens.extend(variation_tokens);
+                    numbered = true;
+                }
+            }
+            board.make_move(&node.movement);
+        }
+        for node in moves.iter().rev() {
+            board.unmake_move(&node.movement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read the games of a PGN text.
+    fn read(text: &str) -> Vec<Result<PgnGame, PgnError>> {
+        PgnReader::new(text.as_bytes()).collect()
+    }
+
+    #[test]
+    fn read_and_write_round_trip() {
+        let text: &str = concat!(
+            "[Event \"Test\"]\n",
+            "[Site \"?\"]\n",
+            "[Date \"2024.01.02\"]\n",
+            "[Round \"1\"]\n",
+            "[White \"A \\\"B\\\"\"]\n",
+            "[Black \"C\"]\n",
+            "[Result \"1-0\"]\n",
+            "[ECO \"C20\"]\n",
+            "\n",
+            "{Before} 1. e4 $1 {[%eval 0.25] [%clk 0:05:00] Good} 1... e5 (1... c5 $2\n",
+            "{Sicilian} 2. Nf3 (2. c3) 2... d6) (1... e6) 2. Nf3 Nc6 3. Bb5 $14 {[%eval #3]}\n",
+            "1-0\n",
+            "\n",
+        );
+        let games: Vec<Result<PgnGame, PgnError>> = read(text);
+        assert_eq!(games.len(), 1);
+        let game: &PgnGame = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("White"), Some("A \"B\""));
+        assert_eq!(game.comments, vec![String::from("Before")]);
+        assert_eq!(game.moves.len(), 5);
+        assert_eq!(game.moves[0].nags, vec![1]);
+        assert_eq!(game.moves[0].comments, vec![String::from("Good")]);
+        assert_eq!(game.moves[0].eval, Some(PgnEval::Centipawns(25)));
+        assert_eq!(game.moves[0].clock, Some(Duration::from_secs(300)));
+        assert_eq!(game.moves[1].variations.len(), 2);
+        assert_eq!(game.moves[1].variations[0].moves[1].variations.len(), 1);
+        assert_eq!(game.moves[4].eval, Some(PgnEval::Mate(3)));
+        assert_eq!(PgnWriter::new().write(game).unwrap(), text);
+    }
+
+    #[test]
+    fn write_without_annotations() {
+        let games: Vec<Result<PgnGame, PgnError>> =
+            read("1. e4 {A} (1. d4) 1... e5 $1 {[%clk 0:01:00]} *");
+        let writer: PgnWriter = PgnWriter {
+            comments: false,
+            variations: false,
+            clocks: false,
+            ..PgnWriter::new()
+        };
+        let text: String = writer.write(games[0].as_ref().unwrap()).unwrap();
+        assert!(text.ends_with("\n1. e4 e5 $1 *\n\n"), "{}", text);
+    }
+
+    #[test]
+    fn errors_have_positions_and_reading_goes_on() {
+        let games: Vec<Result<PgnGame, PgnError>> =
+            read("[Event \"a\"]\n\n1. e4 e4 *\n\n[Event \"b\"]\n\n1. d4 *\n");
+        assert_eq!(games.len(), 2);
+        let error: &PgnError = games[0].as_ref().unwrap_err();
+        assert_eq!((error.line, error.column), (3, 7));
+        assert_eq!(games[1].as_ref().unwrap().tag("Event"), Some("b"));
+    }
+}
@@ -1,7 +1,7 @@
 /// Types of pieces.
 ///
 /// A piece can be one of six types: king, queen, rook, bishop, knight, or pawn.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum Piece {
     Pawn,
     Knight,
@@ -10,3 +10,49 @@ pub enum Piece {
     Queen,
     King,
 }
+
+impl Piece {
+    /// Get the material value of the piece, in centipawns.
+    ///
+    /// The king has no material value, since it can never be captured.
+    pub fn value(&self) -> i32 {
+        match self {
+            Piece::Pawn => 100,
+            Piece::Knight => 320,
+            Piece::Bishop => 330,
+            Piece::Rook => 500,
+            Piece::Queen => 900,
+            Piece::King => 0,
+        }
+    }
+
+    /// Get the index of the piece (from 0 for pawns to 5 for kings).
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Get the piece represented by a letter (`p`, `n`, `b`, `r`, `q` or `k`, in any case).
+    pub fn from_char(letter: char) -> Option<Piece> {
+        match letter.to_ascii_lowercase() {
+            'p' => Some(Piece::Pawn),
+            'n' => Some(Piece::Knight),
+            'b' => Some(Piece::Bishop),
+            'r' => Some(Piece::Rook),
+            'q' => Some(Piece::Queen),
+            'k' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
+    /// Get the (lowercase) letter of the piece.
+    pub fn to_char(&self) -> char {
+        match self {
+            Piece::Pawn => 'p',
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Rook => 'r',
+            Piece::Queen => 'q',
+            Piece::King => 'k',
+        }
+    }
+}
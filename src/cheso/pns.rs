@@ -0,0 +1,313 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::movement::Movement;
+
+/// Proof or disproof number of a node that can not be proven (or disproven).
+const INFINITY: u32 = u32::MAX / 2;
+
+/// Default maximum number of nodes of the tree.
+pub const DEFAULT_MAX_NODES: usize = 1_000_000;
+
+/// Theoretical outcome of a position, for the side to move.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+    Unknown,
+}
+
+/// Goal of a proof, for the side to move at the root.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Goal {
+    Win,
+    AtLeastDraw,
+}
+
+/// Result of a proof-number search.
+///
+/// It contains the outcome of the position, the movement proving it (unless it is a loss or unknown)
+/// and the number of nodes created (in the main trees, without the nested searches of PN²).
+#[derive(Clone, Debug)]
+pub struct ProofResult {
+    pub outcome: Outcome,
+    pub movement: Option<Movement>,
+    pub nodes: usize,
+}
+
+/// Node of a proof tree.
+///
+/// Nodes where the side of the root is to move are OR nodes (one proven child is enough),
+/// and nodes where the opponent is to move are AND nodes (every child must be proven).
+struct Node {
+    movement: Option<Movement>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    proof: u32,
+    disproof: u32,
+    or: bool,
+}
+
+/// Proof-number search.
+///
+/// Proves the outcome of a position without any heuristic evaluation: the tree is grown best-first from the
+/// most proving node, until the goal is proven or disproven, or the tree reaches its maximum number of nodes.
+/// Mates, stalemates and draws by the rules are the only terminal positions.
+///
+/// With PN², each new node is initialized by a nested proof-number search (limited to the size of the main tree),
+/// which finds much better proof and disproof numbers for the same memory.
+pub struct ProofNumberSearch {
+    pub max_nodes: usize,
+    pub pn2: bool,
+    tree: Vec<Node>,
+    created: usize,
+}
+
+impl Default for ProofNumberSearch {
+    fn default() -> ProofNumberSearch {
+        ProofNumberSearch::new()
+    }
+}
+
+impl ProofNumberSearch {
+    /// Make a new proof-number search (PN²) with the default maximum number of nodes.
+    pub fn new() -> ProofNumberSearch {
+        ProofNumberSearch {
+            max_nodes: DEFAULT_MAX_NODES,
+            pn2: true,
+            tree: Vec::new(),
+            created: 0,
+        }
+    }
+
+    /// Prove the outcome of a position for the side to move.
+    ///
+    /// A win is proven first; otherwise, at least a draw is proven (a draw) or disproven (a loss).
+    /// The outcome is unknown when a proof does not fit in the maximum number of nodes.
+    pub fn prove(&mut self, board: &mut Board) -> ProofResult {
+        self.created = 0;
+        let attacker: Color = board.side_to_move;
+        let (proof, disproof) = self.search(board, Goal::Win, attacker, self.max_nodes);
+        if proof == 0 {
+            return self.result(Outcome::Win);
+        }
+        let win_disproven: bool = disproof == 0;
+        let (proof, disproof) = self.search(board, Goal::AtLeastDraw, attacker, self.max_nodes);
+        let outcome: Outcome = match (proof, disproof) {
+            (0, _) if win_disproven => Outcome::Draw,
+            (_, 0) => Outcome::Loss,
+            _ => Outcome::Unknown,
+        };
+        self.result(outcome)
+    }
+
+    /// Make the result of a proof, with the proving movement of the last search.
+    fn result(&self, outcome: Outcome) -> ProofResult {
+        let movement: Option<Movement> = match outcome {
+            Outcome::Win | Outcome::Draw => self.tree.first().and_then(|root| {
+                root.children
+                    .iter()
+                    .find(|child| self.tree[**child].proof == 0)
+                    .and_then(|child| self.tree[*child].movement)
+            }),
+            _ => None,
+        };
+        ProofResult {
+            outcome,
+            movement,
+            nodes: self.created,
+        }
+    }
+
+    /// Grow a proof tree for a goal until it is proven or disproven or it reaches the maximum number of nodes.
+    ///
+    /// Returns the proof and disproof numbers of the root.
+    fn search(
+        &mut self,
+        board: &mut Board,
+        goal: Goal,
+        attacker: Color,
+        max_nodes: usize,
+    ) -> (u32, u32) {
+        let (proof, disproof) = ProofNumberSearch::evaluate(board, goal, attacker);
+        self.tree = vec![Node {
+            movement: None,
+            parent: None,
+            children: Vec::new(),
+            proof,
+            disproof,
+            or: board.side_to_move == attacker,
+        }];
+        self.created += 1;
+        while self.tree[0].proof != 0 && self.tree[0].disproof != 0 && self.tree.len() < max_nodes {
+            // descend to the most proving node
+            let mut index: usize = 0;
+            let mut path: Vec<Movement> = Vec::new();
+            while !self.tree[index].children.is_empty() {
+                let node: &Node = &self.tree[index];
+                let best: usize = *node
+                    .children
+                    .iter()
+                    .min_by_key(|child| match node.or {
+                        true => self.tree[**child].proof,
+                        false => self.tree[**child].disproof,
+                    })
+                    .unwrap();
+                index = best;
+                if let Some(movement) = self.tree[index].movement {
+                    board.make_move(&movement);
+                    path.push(movement);
+                }
+            }
+            self.expand(board, index, goal, attacker);
+            self.update(index);
+            for movement in path.iter().rev() {
+                board.unmake_move(movement);
+            }
+        }
+        (self.tree[0].proof, self.tree[0].disproof)
+    }
+
+    /// Get the initial proof and disproof numbers of a position.
+    fn evaluate(board: &mut Board, goal: Goal, attacker: Color) -> (u32, u32) {
+        let moves: usize = board.gen_legal_moves().len();
+        let attacker_to_move: bool = board.side_to_move == attacker;
+        let proven: bool = match moves {
+            0 if board.in_check() => !attacker_to_move,
+            0 => goal == Goal::AtLeastDraw,
+            _ if board.is_draw() => goal == Goal::AtLeastDraw,
+            // the more replies, the harder to prove (or disprove) the node
+            _ => {
+                let moves: u32 = moves as u32;
+                return match attacker_to_move {
+                    true => (1, moves),
+                    false => (moves, 1),
+                };
+            }
+        };
+        match proven {
+            true => (0, INFINITY),
+            false => (INFINITY, 0),
+        }
+    }
+
+    /// Expand a leaf, creating a child for each legal movement.
+    fn expand(&mut self, board: &mut Board, index: usize, goal: Goal, attacker: Color) {
+        let limit: usize = self.tree.len();
+        for movement in board.gen_legal_moves() {
+            board.make_move(&movement);
+            let (mut proof, mut disproof) = ProofNumberSearch::evaluate(board, goal, attacker);
+            if self.pn2 && proof != 0 && disproof != 0 {
+                let mut nested: ProofNumberSearch = ProofNumberSearch {
+                    max_nodes: limit,
+                    pn2: false,
+                    tree: Vec::new(),
+                    created: 0,
+                };
+                (proof, disproof) = nested.search(board, goal, attacker, limit);
+            }
+            let or: bool = board.side_to_move == attacker;
+            board.unmake_move(&movement);
+            self.tree.push(Node {
+                movement: Some(movement),
+                parent: Some(index),
+                children: Vec::new(),
+                proof,
+                disproof,
+                or,
+            });
+            let child: usize = self.tree.len() - 1;
+            self.tree[index].children.push(child);
+            self.created += 1;
+        }
+    }
+
+    /// Update the proof and disproof numbers of a node and its ancestors.
+    fn update(&mut self, index: usize) {
+        let mut current: Option<usize> = Some(index);
+        while let Some(index) = current {
+            let node: &Node = &self.tree[index];
+            if !node.children.is_empty() {
+                let proofs = node.children.iter().map(|child| self.tree[*child].proof);
+                let disproofs = node.children.iter().map(|child| self.tree[*child].disproof);
+                let (proof, disproof) = match node.or {
+                    true => (proofs.min().unwrap(), ProofNumberSearch::sum(disproofs)),
+                    false => (ProofNumberSearch::sum(proofs), disproofs.min().unwrap()),
+                };
+                self.tree[index].proof = proof;
+                self.tree[index].disproof = disproof;
+            }
+            current = self.tree[index].parent;
+        }
+    }
+
+    /// Add proof (or disproof) numbers, saturating at infinity.
+    fn sum(numbers: impl Iterator<Item = u32>) -> u32 {
+        numbers.fold(0, |sum, number| {
+            std::cmp::min(sum.saturating_add(number), INFINITY)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prove the outcome of a position, with or without nested searches.
+    fn prove(fen: &str, pn2: bool) -> ProofResult {
+        let mut board: Board = Board::from_fen(fen).unwrap();
+        let mut search: ProofNumberSearch = ProofNumberSearch {
+            pn2,
+            ..ProofNumberSearch::new()
+        };
+        search.prove(&mut board)
+    }
+
+    #[test]
+    fn proven_win() {
+        for pn2 in [false, true] {
+            let result: ProofResult = prove("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", pn2);
+            assert_eq!(result.outcome, Outcome::Win);
+            assert_eq!(
+                result.movement.map(|movement| movement.to_string()),
+                Some(String::from("a1a8"))
+            );
+        }
+    }
+
+    #[test]
+    fn proven_loss() {
+        // the only movement of the king is answered by a mate on the back rank
+        for pn2 in [false, true] {
+            let result: ProofResult = prove("k7/8/1K6/8/8/8/8/7R b - - 0 1", pn2);
+            assert_eq!(result.outcome, Outcome::Loss);
+            assert_eq!(result.movement, None);
+        }
+    }
+
+    #[test]
+    fn proven_draw() {
+        // the only movement captures the rook, leaving the kings alone
+        for pn2 in [false, true] {
+            let result: ProofResult = prove("8/8/8/8/8/3k4/1r6/K7 w - - 0 1", pn2);
+            assert_eq!(result.outcome, Outcome::Draw);
+            assert_eq!(
+                result.movement.map(|movement| movement.to_string()),
+                Some(String::from("a1b2"))
+            );
+        }
+    }
+
+    #[test]
+    fn node_limit() {
+        let mut board: Board = Board::new();
+        let mut search: ProofNumberSearch = ProofNumberSearch {
+            max_nodes: 100,
+            ..ProofNumberSearch::new()
+        };
+        let result: ProofResult = search.prove(&mut board);
+        assert_eq!(result.outcome, Outcome::Unknown);
+        assert_eq!(result.movement, None);
+        assert!(result.nodes > 0);
+    }
+}
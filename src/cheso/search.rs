@@ -0,0 +1,1183 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::eval::evaluate;
+use crate::cheso::mcts::MonteCarloSearch;
+use crate::cheso::movement::Movement;
+use crate::cheso::ordering::{Heuristics, MovePicker};
+use crate::cheso::piece::Piece;
+use crate::cheso::skill::{xorshift, Skill, CANDIDATES};
+#[cfg(feature = "syzygy")]
+use crate::cheso::syzygy::{Tablebases, Wdl, MAX_PIECES};
+use crate::cheso::time::TimeManager;
+use crate::cheso::tt::{Bound, Entry, TranspositionTable};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Default time reserved for communication with the GUI on each movement, in milliseconds.
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 30;
+
+/// Maximum number of plies from the root of the search.
+pub const MAX_PLY: usize = 128;
+
+/// Score greater than any possible score.
+pub const INFINITY: i32 = 32000;
+
+/// Score of being checkmated at the root (mated in n plies scores -MATE + n).
+pub const MATE: i32 = 31000;
+
+/// Score of a position won according to the endgame tablebases (at the root, decreasing with the ply).
+///
+/// Lower than any mate score, since the tablebases only tell the result of the position.
+#[cfg(feature = "syzygy")]
+pub const TABLEBASE_WIN: i32 = MATE - 2 * MAX_PLY as i32;
+
+/// Check if a score is a mate score.
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+/// Convert a score relative to the root into a score relative to the current node (for storing).
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        score if score >= MATE - MAX_PLY as i32 => score + ply as i32,
+        score if score <= -MATE + MAX_PLY as i32 => score - ply as i32,
+        score => score,
+    }
+}
+
+/// Convert a score relative to the current node into a score relative to the root (for probing).
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    match score {
+        score if score >= MATE - MAX_PLY as i32 => score - ply as i32,
+        score if score <= -MATE + MAX_PLY as i32 => score + ply as i32,
+        score => score,
+    }
+}
+
+/// Configuration of the search.
+///
+/// Each selectivity technique can be switched on or off independently, so their effect can be measured.
+/// The extension budget limits the number of plies a line can be extended, to avoid search explosions.
+/// The aspiration window is the distance from the previous score to the bounds of the first window of an iteration.
+#[derive(Copy, Clone, Debug)]
+pub struct SearchConfig {
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub reverse_futility: bool,
+    pub futility: bool,
+    pub razoring: bool,
+    pub check_extensions: bool,
+    pub singular_extensions: bool,
+    pub pawn_extensions: bool,
+    pub extension_budget: i32,
+    pub aspiration_window: i32,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            null_move: true,
+            late_move_reductions: true,
+            reverse_futility: true,
+            futility: true,
+            razoring: true,
+            check_extensions: true,
+            singular_extensions: true,
+            pawn_extensions: true,
+            extension_budget: 16,
+            aspiration_window: 25,
+        }
+    }
+}
+
+/// Limits of a search.
+///
+/// The search stops when any of the limits is reached (or when it is stopped from outside).
+/// Clock times and increments of both sides are turned into deadlines by a time manager.
+/// A search without limits goes on until it is stopped.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+}
+
+/// Information about the progress of a search.
+///
+/// It is reported after each iteration (once per principal variation, numbered from 1),
+/// and also when the score falls outside the aspiration window
+/// (with a lower bound when it fails high, or an upper bound when it fails low).
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: i32,
+    pub multipv: usize,
+    pub score: i32,
+    pub bound: Bound,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Movement>,
+}
+
+/// Principal variation of a root movement, with its score.
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<Movement>,
+}
+
+/// Result of a search.
+///
+/// It contains the best movement found (if any), its score from the point of view of the side to move,
+/// the depth reached, the number of visited nodes and the principal variation.
+/// When searching several principal variations, all of them are included (best first).
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub movement: Option<Movement>,
+    pub score: i32,
+    pub depth: i32,
+    pub nodes: u64,
+    pub pv: Vec<Movement>,
+    pub lines: Vec<PvLine>,
+}
+
+/// Alpha-beta searcher.
+///
+/// A negamax search with alpha-beta pruning, iterative deepening, a transposition table and quiescence search.
+/// Movements are ordered by a staged move picker, fed with the heuristics gathered by the searcher.
+/// Unpromising nodes are pruned or searched with reduced depth, as enabled in the search configuration.
+/// Only the first movement of each node is searched with a full window (principal variation search),
+/// and each iteration starts with a narrow window around the score of the previous one (aspiration windows).
+/// Forcing movements (checks, pawn pushes to the seventh rank and singular movements) are searched deeper.
+///
+/// With more than one thread, helper searchers explore the same root at varying depths (lazy SMP),
+/// sharing only the transposition table with the main searcher, which alone reports progress and picks the result.
+/// In analysis, several principal variations can be searched, each one excluding the root movements of the previous.
+/// Alternatively, the search can be delegated to a Monte Carlo tree search.
+/// Below full skill, the search is limited and the movement is randomly chosen among the best ones.
+pub struct Searcher {
+    pub config: SearchConfig,
+    pub tt: Arc<TranspositionTable>,
+    pub move_overhead: Duration,
+    pub threads: usize,
+    pub multipv: usize,
+    pub monte_carlo: bool,
+    pub skill: Skill,
+    #[cfg(feature = "syzygy")]
+    pub tablebases: Arc<Tablebases>,
+    #[cfg(feature = "syzygy")]
+    pub probe_limit: usize,
+    #[cfg(feature = "syzygy")]
+    probe_tablebases: bool,
+    mcts: MonteCarloSearch,
+    random: u64,
+    id: usize,
+    helpers: Vec<Searcher>,
+    helpers_stop: Arc<AtomicBool>,
+    helpers_nodes: Arc<AtomicU64>,
+    heuristics: Heuristics,
+    nodes: u64,
+    pv: Vec<Vec<Movement>>,
+    excluded: Vec<Option<Movement>>,
+    root_excluded: Vec<Movement>,
+    root_moves: Vec<Movement>,
+    extensions: Vec<i32>,
+    limits: Limits,
+    time: Option<TimeManager>,
+    side: Color,
+    start: Instant,
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    ponder: Arc<AtomicBool>,
+    pondering: bool,
+}
+
+impl Searcher {
+    /// Make a new (single-threaded) searcher with a transposition table of the given size in megabytes.
+    pub fn new(megabytes: usize) -> Searcher {
+        let mut searcher: Searcher =
+            Searcher::with_table(Arc::new(TranspositionTable::new(megabytes)));
+        searcher.mcts.set_memory(megabytes);
+        searcher
+    }
+
+    /// Resize the transposition table, and the tree of the Monte Carlo tree search, to the given size in megabytes.
+    pub fn set_hash(&mut self, megabytes: usize) {
+        self.tt = Arc::new(TranspositionTable::new(megabytes));
+        self.mcts.set_memory(megabytes);
+    }
+
+    /// Make a new (single-threaded) searcher using a transposition table.
+    fn with_table(tt: Arc<TranspositionTable>) -> Searcher {
+        Searcher {
+            config: SearchConfig::default(),
+            tt,
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD),
+            threads: 1,
+            multipv: 1,
+            monte_carlo: false,
+            skill: Skill::default(),
+            #[cfg(feature = "syzygy")]
+            tablebases: Arc::new(Tablebases::default()),
+            #[cfg(feature = "syzygy")]
+            probe_limit: MAX_PIECES,
+            #[cfg(feature = "syzygy")]
+            probe_tablebases: false,
+            mcts: MonteCarloSearch::default(),
+            random: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |time| time.as_nanos() as u64 | 1),
+            id: 0,
+            helpers: Vec::new(),
+            helpers_stop: Arc::new(AtomicBool::new(false)),
+            helpers_nodes: Arc::new(AtomicU64::new(0)),
+            heuristics: Heuristics::new(),
+            nodes: 0,
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            excluded: vec![None; MAX_PLY + 1],
+            root_excluded: Vec::new(),
+            root_moves: Vec::new(),
+            extensions: vec![0; MAX_PLY + 1],
+            limits: Limits::default(),
+            time: None,
+            side: Color::White,
+            start: Instant::now(),
+            stop: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            ponder: Arc::new(AtomicBool::new(false)),
+            pondering: false,
+        }
+    }
+
+    /// Get the flag used to stop the search from outside.
+    ///
+    /// The search stops as soon as the flag is raised, but the flag is never lowered by the search.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Get the flag telling that the search is pondering (searching during the time of the opponent).
+    ///
+    /// While the flag is raised, the clock is ignored. When it is lowered (the expected movement was played),
+    /// the search goes on as a normal timed search, with the clock starting at that moment.
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        self.ponder.clone()
+    }
+
+    /// Forget everything learned in previous searches.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.heuristics.clear();
+        self.helpers.clear();
+    }
+
+    /// Make a helper searcher, sharing the transposition table, the node counter and the stop flag of the helpers.
+    fn helper(&self, id: usize) -> Searcher {
+        Searcher {
+            id,
+            stop: self.helpers_stop.clone(),
+            helpers_nodes: self.helpers_nodes.clone(),
+            ..Searcher::with_table(self.tt.clone())
+        }
+    }
+
+    /// Search the current position by iterative deepening, until reaching the limits.
+    ///
+    /// Progress is reported after each iteration and on each aspiration window failure.
+    /// Incomplete iterations are discarded, except when no iteration has been completed yet.
+    /// Helper threads (if any) run until the main search finishes, and their nodes are added to the result.
+    pub fn search(
+        &mut self,
+        board: &mut Board,
+        limits: &Limits,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchResult {
+        if self.monte_carlo {
+            return self.mcts.search(
+                board,
+                limits,
+                &self.stop,
+                &self.ponder,
+                self.move_overhead,
+                report,
+            );
+        }
+        if !self.skill.is_limited() {
+            return self.search_threads(board, limits, report);
+        }
+        // search the best candidates within the limits of the skill, and choose one of them
+        let limits: Limits = Limits {
+            depth: Some(std::cmp::min(
+                limits.depth.unwrap_or(i32::MAX),
+                self.skill.depth(),
+            )),
+            nodes: Some(std::cmp::min(
+                limits.nodes.unwrap_or(u64::MAX),
+                self.skill.nodes(),
+            )),
+            ..limits.clone()
+        };
+        // the extra candidates are not reported, only the requested number of principal variations
+        let multipv: usize = self.multipv;
+        self.multipv = std::cmp::max(multipv, CANDIDATES);
+        let mut result: SearchResult = self.search_threads(board, &limits, &mut |info| {
+            if info.multipv <= multipv {
+                report(info);
+            }
+        });
+        self.multipv = multipv;
+        self.random = xorshift(self.random);
+        if let Some(line) = result
+            .lines
+            .get(self.skill.choose(&result.lines, self.random))
+        {
+            result.movement = line.pv.first().copied();
+            result.score = line.score;
+            result.pv = line.pv.clone();
+        }
+        result.lines.truncate(std::cmp::max(1, multipv));
+        result
+    }
+
+    /// Search the current position with all the threads.
+    fn search_threads(
+        &mut self,
+        board: &mut Board,
+        limits: &Limits,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let threads: usize = std::cmp::max(1, self.threads);
+        self.helpers.truncate(threads - 1);
+        while self.helpers.len() < threads - 1 {
+            let helper: Searcher = self.helper(self.helpers.len() + 1);
+            self.helpers.push(helper);
+        }
+        self.helpers_stop.store(false, Ordering::Relaxed);
+        self.helpers_nodes.store(0, Ordering::Relaxed);
+        #[cfg(feature = "syzygy")]
+        self.probe_root(board);
+        let helper_limits: Limits = Limits {
+            depth: limits.depth,
+            ..Limits::default()
+        };
+        let mut helpers: Vec<Searcher> = std::mem::take(&mut self.helpers);
+        let mut result: SearchResult = std::thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                helper.tt = self.tt.clone();
+                helper.config = self.config;
+                helper.root_moves = self.root_moves.clone();
+                #[cfg(feature = "syzygy")]
+                {
+                    helper.tablebases = self.tablebases.clone();
+                    helper.probe_limit = self.probe_limit;
+                    helper.probe_tablebases = self.probe_tablebases;
+                }
+                let mut board: Board = board.clone();
+                let limits: &Limits = &helper_limits;
+                scope.spawn(move || helper.iterate(&mut board, limits, &mut |_| ()));
+            }
+            let result: SearchResult = self.iterate(board, limits, report);
+            self.helpers_stop.store(true, Ordering::Relaxed);
+            result
+        });
+        self.helpers = helpers;
+        self.root_moves.clear();
+        result.nodes = self.nodes + self.helpers_nodes.load(Ordering::Relaxed);
+        result
+    }
+
+    /// Restrict the root movements to the best ones according to the endgame tablebases (when the root is in them).
+    ///
+    /// When the movements are ranked by distance to zeroing, the search only has to choose among equally good
+    /// movements, so the tablebases are not probed during the search. Otherwise they are, when winning.
+    #[cfg(feature = "syzygy")]
+    fn probe_root(&mut self, board: &mut Board) {
+        self.root_moves.clear();
+        self.probe_tablebases = !self.tablebases.is_empty();
+        if !self.tablebases.can_probe(board, self.probe_limit) {
+            return;
+        }
+        if let Some(probe) = self.tablebases.probe_root(board) {
+            self.probe_tablebases = !probe.dtz && probe.wdl > Wdl::Draw;
+            self.root_moves = probe.moves;
+        }
+    }
+
+    /// Search the current position by iterative deepening in the current thread.
+    ///
+    /// Helper searchers start each iteration one ply deeper than the main searcher (in odd helpers),
+    /// so threads diverge and fill the transposition table with different subtrees.
+    fn iterate(
+        &mut self,
+        board: &mut Board,
+        limits: &Limits,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchResult {
+        self.nodes = 0;
+        self.limits = limits.clone();
+        self.side = board.side_to_move;
+        self.pondering = self.id == 0 && self.ponder.load(Ordering::Relaxed);
+        self.time = match self.pondering {
+            true => None,
+            false => TimeManager::new(limits, self.side, self.move_overhead),
+        };
+        self.start = Instant::now();
+        self.stopped = false;
+        self.heuristics.age();
+        let mut result: SearchResult = SearchResult {
+            movement: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+            lines: Vec::new(),
+        };
+        let max_depth: i32 = limits
+            .depth
+            .unwrap_or(MAX_PLY as i32 - 1)
+            .clamp(1, MAX_PLY as i32 - 1);
+        let root_moves: usize = match self.root_moves.len() {
+            0 => board.gen_legal_moves().len(),
+            moves => moves,
+        };
+        let multipv: usize = self.multipv.clamp(1, std::cmp::max(1, root_moves));
+        for iteration in 1..=max_depth {
+            let depth: i32 = iteration + (self.id % 2) as i32;
+            if depth > max_depth {
+                break;
+            }
+            // search each principal variation, excluding the root movements of the previous ones
+            let mut lines: Vec<PvLine> = Vec::new();
+            self.root_excluded.clear();
+            for slot in 0..multipv {
+                let previous: Option<PvLine> = result.lines.get(slot).cloned();
+                let score: i32 = self.aspiration(board, depth, slot, previous.as_ref(), report);
+                if self.stopped {
+                    break;
+                }
+                let line: PvLine = PvLine {
+                    score,
+                    pv: self.pv[0].clone(),
+                };
+                match line.pv.first() {
+                    Some(movement) => self.root_excluded.push(*movement),
+                    None => break,
+                }
+                lines.push(line);
+            }
+            self.root_excluded.clear();
+            if self.stopped {
+                if result.movement.is_none() {
+                    result.movement = lines
+                        .first()
+                        .map_or(self.pv[0].first().copied(), |line| line.pv.first().copied());
+                }
+                break;
+            }
+            lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+            let best: PvLine = match lines.first() {
+                Some(line) => line.clone(),
+                // checkmate or stalemate: there is no movement to play
+                None => {
+                    result.score = if board.in_check() { -MATE } else { 0 };
+                    result.depth = depth;
+                    break;
+                }
+            };
+            let best_move_changed: bool =
+                result.movement.is_some() && result.movement != best.pv.first().copied();
+            let score_drop: i32 = if depth > 1 {
+                result.score - best.score
+            } else {
+                0
+            };
+            for (slot, line) in lines.iter().enumerate() {
+                report(&self.info(depth, slot + 1, line.score, Bound::Exact, &line.pv));
+            }
+            result = SearchResult {
+                movement: best.pv.first().copied(),
+                score: best.score,
+                depth,
+                nodes: self.nodes,
+                pv: best.pv,
+                lines,
+            };
+            if is_mate_score(result.score) && MATE - result.score.abs() <= depth {
+                break;
+            }
+            // time management (forced replies need no thinking)
+            self.check_ponderhit();
+            if let Some(time) = self.time.as_mut() {
+                time.update(best_move_changed, score_drop);
+                if root_moves == 1 || time.soft_limit_reached() {
+                    break;
+                }
+            }
+        }
+        if result.movement.is_none() {
+            result.movement = match self.root_moves.first() {
+                Some(movement) => Some(*movement),
+                None => board.gen_legal_moves().first().copied(),
+            };
+        }
+        if self.id > 0 {
+            self.helpers_nodes
+                .fetch_add(self.nodes % 1024, Ordering::Relaxed);
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    /// Search the root with an aspiration window around the score of the previous iteration (for the same slot).
+    ///
+    /// When the score falls outside the window, it is reported and the window is gradually widened.
+    fn aspiration(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        slot: usize,
+        previous: Option<&PvLine>,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> i32 {
+        let mut delta: i32 = std::cmp::max(1, self.config.aspiration_window);
+        let previous_pv: Vec<Movement> = previous.map_or(Vec::new(), |line| line.pv.clone());
+        let (mut alpha, mut beta) = match previous {
+            Some(line) if depth >= 4 && !is_mate_score(line.score) => {
+                (line.score - delta, line.score + delta)
+            }
+            _ => (-INFINITY, INFINITY),
+        };
+        loop {
+            let score: i32 = self.negamax(board, depth, 0, alpha, beta, None);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                report(&self.info(depth, slot + 1, score, Bound::Upper, &previous_pv));
+                beta = (alpha + beta) / 2;
+                alpha = std::cmp::max(score - delta, -INFINITY);
+            } else if score >= beta {
+                let pv: Vec<Movement> = self.pv[0].clone();
+                report(&self.info(depth, slot + 1, score, Bound::Lower, &pv));
+                beta = std::cmp::min(score + delta, INFINITY);
+            } else {
+                return score;
+            }
+            delta += delta / 2;
+            if delta > 1000 {
+                alpha = -INFINITY;
+                beta = INFINITY;
+            }
+        }
+    }
+
+    /// Make the information about the progress of the search.
+    fn info(
+        &self,
+        depth: i32,
+        multipv: usize,
+        score: i32,
+        bound: Bound,
+        pv: &[Movement],
+    ) -> SearchInfo {
+        SearchInfo {
+            depth,
+            multipv,
+            score,
+            bound,
+            nodes: self.nodes + self.helpers_nodes.load(Ordering::Relaxed),
+            time: self.start.elapsed(),
+            pv: pv.to_vec(),
+        }
+    }
+
+    /// Start the clock if the search was pondering and the expected movement has been played.
+    fn check_ponderhit(&mut self) {
+        if self.pondering && !self.ponder.load(Ordering::Relaxed) {
+            self.pondering = false;
+            self.time = TimeManager::new(&self.limits, self.side, self.move_overhead);
+        }
+    }
+
+    /// Count a visited node, checking the node limit, and from time to time the other reasons to stop.
+    ///
+    /// Once stopped, the nodes returning without being searched are not counted.
+    fn visit(&mut self) {
+        if self.stopped {
+            return;
+        }
+        self.nodes += 1;
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.stopped = true;
+        }
+        if self.nodes.is_multiple_of(1024) {
+            if self.id > 0 {
+                self.helpers_nodes.fetch_add(1024, Ordering::Relaxed);
+            }
+            self.check_ponderhit();
+            self.stopped = self.stopped
+                || self.stop.load(Ordering::Relaxed)
+                || self
+                    .time
+                    .as_ref()
+                    .is_some_and(TimeManager::hard_limit_reached);
+        }
+    }
+
+    /// Search a node with alpha-beta pruning, returning its score from the point of view of the side to move.
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        previous: Option<Movement>,
+    ) -> i32 {
+        self.pv[ply].clear();
+        if ply > 0 && board.is_draw() {
+            return 0;
+        }
+        if depth <= 0 || ply >= MAX_PLY {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        self.visit();
+        if self.stopped {
+            return 0;
+        }
+        // transposition table (ignored when searching without a movement)
+        let excluded: Option<Movement> = self.excluded[ply];
+        let entry: Option<Entry> = self.tt.probe(board.hash());
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth >= depth && excluded.is_none() {
+                let score: i32 = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+        // endgame tablebases (only right after a capture or a pawn movement, since they ignore the fifty-move rule)
+        #[cfg(feature = "syzygy")]
+        if self.probe_tablebases
+            && ply > 0
+            && excluded.is_none()
+            && board.reversible_moves == 0
+            && self.tablebases.can_probe(board, self.probe_limit)
+        {
+            if let Some(wdl) = self.tablebases.probe_wdl(board) {
+                let (score, bound) = match wdl {
+                    Wdl::Win => (TABLEBASE_WIN - ply as i32, Bound::Lower),
+                    Wdl::Loss => (-TABLEBASE_WIN + ply as i32, Bound::Upper),
+                    _ => (0, Bound::Exact),
+                };
+                if bound == Bound::Exact
+                    || bound == Bound::Lower && score >= beta
+                    || bound == Bound::Upper && score <= alpha
+                {
+                    self.tt.store(Entry {
+                        hash: board.hash(),
+                        depth: std::cmp::min(depth + 6, MAX_PLY as i32 - 1),
+                        score,
+                        bound,
+                        movement: None,
+                    });
+                    return score;
+                }
+            }
+        }
+        let color: Color = board.side_to_move;
+        let in_check: bool = board.in_check();
+        let pv_node: bool = beta - alpha > 1;
+        let static_eval: i32 = if in_check { -INFINITY } else { evaluate(board) };
+        if !pv_node && !in_check && !is_mate_score(beta) && excluded.is_none() {
+            // reverse futility pruning
+            if self.config.reverse_futility && depth <= 6 && static_eval - 80 * depth >= beta {
+                return static_eval;
+            }
+            // razoring
+            if self.config.razoring && depth <= 2 && static_eval + 300 * depth < alpha {
+                let score: i32 = self.quiescence(board, ply, alpha, alpha + 1);
+                if score <= alpha {
+                    return score;
+                }
+            }
+            // null move pruning (not twice in a row, nor with only king and pawns because of zugzwang)
+            if self.config.null_move
+                && previous.is_some()
+                && depth >= 3
+                && static_eval >= beta
+                && board.has_non_pawn_material(color)
+            {
+                let reduction: i32 = 3 + depth / 6;
+                self.extensions[ply + 1] = self.extensions[ply];
+                board.make_null_move();
+                let score: i32 = -self.negamax(
+                    board,
+                    depth - 1 - reduction,
+                    ply + 1,
+                    -beta,
+                    -beta + 1,
+                    None,
+                );
+                board.unmake_null_move();
+                if score >= beta {
+                    return if is_mate_score(score) { beta } else { score };
+                }
+            }
+        }
+        // futility pruning (quiet movements in frontier nodes can not raise alpha)
+        let futile: bool = self.config.futility
+            && !pv_node
+            && !in_check
+            && depth <= 2
+            && static_eval + 100 + 150 * depth <= alpha;
+        // singular extension (the movement of the transposition table is much better than any other)
+        let mut singular: Option<Movement> = None;
+        if let Some(entry) = entry {
+            if self.config.singular_extensions
+                && ply > 0
+                && depth >= 6
+                && excluded.is_none()
+                && entry.movement.is_some()
+                && entry.bound != Bound::Upper
+                && entry.depth >= depth - 3
+                && !is_mate_score(entry.score)
+            {
+                let singular_beta: i32 = score_from_tt(entry.score, ply) - 2 * depth;
+                self.excluded[ply] = entry.movement;
+                let score: i32 = self.negamax(
+                    board,
+                    (depth - 1) / 2,
+                    ply,
+                    singular_beta - 1,
+                    singular_beta,
+                    previous,
+                );
+                self.excluded[ply] = None;
+                if score < singular_beta {
+                    singular = entry.movement;
+                }
+            }
+        }
+        let original_alpha: i32 = alpha;
+        let mut best_score: i32 = -INFINITY;
+        let mut best_move: Option<Movement> = None;
+        let mut legal_moves: usize = 0;
+        let mut quiets: Vec<Movement> = Vec::new();
+        let killers: [Option<Movement>; 2] = self.heuristics.killers(ply);
+        let mut picker: MovePicker = MovePicker::new(
+            entry.and_then(|entry| entry.movement),
+            killers,
+            self.heuristics.countermove(previous),
+        );
+        while let Some(movement) = picker.next(board, &self.heuristics) {
+            if Some(movement) == excluded
+                || ply == 0
+                    && (self.root_excluded.contains(&movement)
+                        || !self.root_moves.is_empty() && !self.root_moves.contains(&movement))
+            {
+                continue;
+            }
+            let pawn_push: bool = board.get_square(&movement.from) == Some((Piece::Pawn, color))
+                && movement.to.relative_rank(color) == 7;
+            board.make_move(&movement);
+            if board.is_in_check(color) {
+                board.unmake_move(&movement);
+                continue;
+            }
+            legal_moves += 1;
+            let gives_check: bool = board.in_check();
+            let quiet: bool = movement.is_quiet() && !gives_check;
+            if futile && quiet && legal_moves > 1 {
+                board.unmake_move(&movement);
+                continue;
+            }
+            // extensions (limited by the budget of the line)
+            let extension: i32 = if self.extensions[ply] >= self.config.extension_budget {
+                0
+            } else if self.config.check_extensions && gives_check
+                || self.config.singular_extensions && Some(movement) == singular
+                || self.config.pawn_extensions && pawn_push
+            {
+                1
+            } else {
+                0
+            };
+            self.extensions[ply + 1] = self.extensions[ply] + extension;
+            let new_depth: i32 = depth - 1 + extension;
+            // late move reductions (driven by the index of the movement and its history score)
+            let mut reduction: i32 = 0;
+            if self.config.late_move_reductions
+                && depth >= 3
+                && legal_moves > 3
+                && quiet
+                && extension == 0
+                && !in_check
+                && !killers.contains(&Some(movement))
+            {
+                let history: i32 = self.heuristics.history(color, &movement);
+                reduction = ((depth as f64).ln() * (legal_moves as f64).ln() / 2.0) as i32
+                    - history / 8192
+                    - pv_node as i32;
+                reduction = reduction.clamp(0, depth - 2);
+            }
+            // principal variation search (null window for all movements but the first one)
+            let mut score: i32;
+            if legal_moves == 1 {
+                score = -self.negamax(board, new_depth, ply + 1, -beta, -alpha, Some(movement));
+            } else {
+                score = -self.negamax(
+                    board,
+                    new_depth - reduction,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    Some(movement),
+                );
+                if reduction > 0 && score > alpha {
+                    score = -self.negamax(
+                        board,
+                        new_depth,
+                        ply + 1,
+                        -alpha - 1,
+                        -alpha,
+                        Some(movement),
+                    );
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(board, new_depth, ply + 1, -beta, -alpha, Some(movement));
+                }
+            }
+            board.unmake_move(&movement);
+            if score > best_score {
+                best_score = score;
+                best_move = Some(movement);
+                if score > alpha {
+                    alpha = score;
+                    let mut pv: Vec<Movement> = vec![movement];
+                    pv.extend_from_slice(&self.pv[ply + 1]);
+                    self.pv[ply] = pv;
+                }
+            }
+            if alpha >= beta {
+                if movement.is_quiet() {
+                    self.heuristics
+                        .update(color, ply, depth, previous, &movement, &quiets);
+                }
+                break;
+            }
+            if movement.is_quiet() {
+                quiets.push(movement);
+            }
+        }
+        if self.stopped {
+            return 0;
+        }
+        // checkmate or stalemate (or the excluded movement is the only legal one)
+        if legal_moves == 0 {
+            return match (excluded, in_check) {
+                (Some(_), _) => alpha,
+                (None, true) => -MATE + ply as i32,
+                (None, false) => 0,
+            };
+        }
+        // searches without some movements are not stored (nor the root when searching several variations)
+        if excluded.is_some() || ply == 0 && !self.root_excluded.is_empty() {
+            return best_score;
+        }
+        let bound: Bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(Entry {
+            hash: board.hash(),
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            movement: best_move,
+        });
+        best_score
+    }
+
+    /// Search only captures (and queen promotions) until reaching a quiet position.
+    ///
+    /// The side to move can always stand pat, so the static evaluation is a lower bound of the score.
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.visit();
+        self.pv[ply].clear();
+        if self.stopped {
+            return 0;
+        }
+        let stand_pat: i32 = evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        alpha = std::cmp::max(alpha, stand_pat);
+        let color: Color = board.side_to_move;
+        let mut best_score: i32 = stand_pat;
+        let mut picker: MovePicker = MovePicker::new_captures();
+        while let Some(movement) = picker.next(board, &self.heuristics) {
+            board.make_move(&movement);
+            if board.is_in_check(color) {
+                board.unmake_move(&movement);
+                continue;
+            }
+            let score: i32 = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(&movement);
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                }
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Search a position to a depth with a configuration.
+    fn search(fen: &str, config: SearchConfig, depth: i32) -> SearchResult {
+        let mut board: Board = Board::from_fen(fen).unwrap();
+        let mut searcher: Searcher = Searcher::new(1);
+        searcher.config = config;
+        let limits: Limits = Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        };
+        searcher.search(&mut board, &limits, &mut |_| ())
+    }
+
+    #[test]
+    fn toggles_keep_the_best_move() {
+        let positions: [(&str, &str); 4] = [
+            ("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", "a1a8"),
+            ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", "d2d5"),
+            ("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "b5c7"),
+            ("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"),
+        ];
+        let all: SearchConfig = SearchConfig::default();
+        let configs: [SearchConfig; 10] = [
+            all,
+            SearchConfig {
+                null_move: false,
+                ..all
+            },
+            SearchConfig {
+                late_move_reductions: false,
+                ..all
+            },
+            SearchConfig {
+                reverse_futility: false,
+                ..all
+            },
+            SearchConfig {
+                futility: false,
+                ..all
+            },
+            SearchConfig {
+                razoring: false,
+                ..all
+            },
+            SearchConfig {
+                check_extensions: false,
+                ..all
+            },
+            SearchConfig {
+                singular_extensions: false,
+                ..all
+            },
+            SearchConfig {
+                pawn_extensions: false,
+                ..all
+            },
+            SearchConfig {
+                null_move: false,
+                late_move_reductions: false,
+                reverse_futility: false,
+                futility: false,
+                razoring: false,
+                check_extensions: false,
+                singular_extensions: false,
+                pawn_extensions: false,
+                extension_budget: 0,
+                ..all
+            },
+        ];
+        for config in configs {
+            for (fen, best) in positions {
+                let result: SearchResult = search(fen, config, 6);
+                assert_eq!(
+                    result.movement.map(|movement| movement.to_string()),
+                    Some(String::from(best)),
+                    "{} with {:?}",
+                    fen,
+                    config
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extension_budget_bounds_perpetual_checks() {
+        // white draws by checking forever, so every line of the principal variation is a check
+        let fen: &str = "7k/5p1p/8/6Q1/8/8/rr3PPP/6K1 w - - 0 1";
+        for budget in [0, 2, 16] {
+            let config: SearchConfig = SearchConfig {
+                extension_budget: budget,
+                ..SearchConfig::default()
+            };
+            let result: SearchResult = search(fen, config, 8);
+            assert_eq!(result.depth, 8);
+            assert_eq!(result.score, 0);
+            assert!(
+                result.pv.len() <= (8 + budget) as usize,
+                "budget {}: {} plies",
+                budget,
+                result.pv.len()
+            );
+        }
+    }
+
+    #[test]
+    fn helper_threads() {
+        let mut board: Board = Board::new();
+        let mut searcher: Searcher = Searcher::new(1);
+        searcher.threads = 4;
+        let limits: Limits = Limits {
+            depth: Some(6),
+            ..Limits::default()
+        };
+        let result: SearchResult = searcher.search(&mut board, &limits, &mut |_| ());
+        assert!(board.gen_legal_moves().contains(&result.movement.unwrap()));
+        assert_eq!(searcher.helpers.len(), 3);
+        assert!(searcher.helpers.iter().all(|helper| helper.nodes > 0));
+        let helpers_nodes: u64 = searcher.helpers.iter().map(|helper| helper.nodes).sum();
+        assert_eq!(result.nodes, searcher.nodes + helpers_nodes);
+    }
+
+    #[test]
+    fn node_limit_is_exact() {
+        for nodes in [100, 1000, 1500] {
+            let limits: Limits = Limits {
+                nodes: Some(nodes),
+                ..Limits::default()
+            };
+            let mut board: Board = Board::new();
+            let result: SearchResult = Searcher::new(1).search(&mut board, &limits, &mut |_| ());
+            assert!(board.gen_legal_moves().contains(&result.movement.unwrap()));
+            assert_eq!(result.nodes, nodes);
+        }
+    }
+
+    #[test]
+    fn multiple_principal_variations() {
+        let limits: Limits = Limits {
+            depth: Some(5),
+            ..Limits::default()
+        };
+        let mut searcher: Searcher = Searcher::new(1);
+        searcher.multipv = 4;
+        let result: SearchResult = searcher.search(&mut Board::new(), &limits, &mut |_| ());
+        assert_eq!(result.lines.len(), 4);
+        let first_moves: Vec<Movement> = result.lines.iter().map(|line| line.pv[0]).collect();
+        for (index, movement) in first_moves.iter().enumerate() {
+            assert!(!first_moves[..index].contains(movement));
+        }
+        assert!(result
+            .lines
+            .windows(2)
+            .all(|lines| lines[0].score >= lines[1].score));
+        assert_eq!(result.movement, Some(result.lines[0].pv[0]));
+        assert_eq!(result.score, result.lines[0].score);
+        // no more variations than legal movements
+        let mut board: Board = Board::from_fen("k7/8/8/8/8/8/1q6/K7 w - - 0 1").unwrap();
+        let result: SearchResult = searcher.search(&mut board, &limits, &mut |_| ());
+        assert_eq!(result.lines.len(), 1);
+    }
+
+    #[test]
+    fn no_movement_at_the_root() {
+        let mated: SearchResult =
+            search("8/8/8/8/8/5k2/8/5K1q w - - 0 1", SearchConfig::default(), 3);
+        assert_eq!(mated.movement, None);
+        assert_eq!(mated.score, -MATE);
+        assert!(mated.pv.is_empty());
+        let stalemated: SearchResult =
+            search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", SearchConfig::default(), 3);
+        assert_eq!(stalemated.movement, None);
+        assert_eq!(stalemated.score, 0);
+        assert!(stalemated.pv.is_empty());
+    }
+
+    #[test]
+    fn aspiration_window_failures_are_reported() {
+        let mut searcher: Searcher = Searcher::new(1);
+        searcher.config.aspiration_window = 1;
+        let limits: Limits = Limits {
+            depth: Some(7),
+            ..Limits::default()
+        };
+        let mut infos: Vec<SearchInfo> = Vec::new();
+        let result: SearchResult = searcher.search(&mut Board::new(), &limits, &mut |info| {
+            infos.push(info.clone())
+        });
+        for bound in [Bound::Upper, Bound::Lower] {
+            assert!(
+                infos
+                    .iter()
+                    .any(|info| info.bound == bound && info.depth >= 4),
+                "no {:?} bound reported",
+                bound
+            );
+        }
+        // each iteration ends with the exact score, after the failures of its window
+        for depth in 1..=7 {
+            let last: &SearchInfo = infos.iter().rev().find(|info| info.depth == depth).unwrap();
+            assert_eq!(last.bound, Bound::Exact);
+        }
+        assert!(infos
+            .iter()
+            .filter(|info| info.depth < 4)
+            .all(|info| info.bound == Bound::Exact));
+        assert_eq!(result.score, infos.last().unwrap().score);
+    }
+
+    #[test]
+    fn ponderhit_starts_the_clock() {
+        let mut searcher: Searcher = Searcher::new(1);
+        let ponder: Arc<AtomicBool> = searcher.ponder_flag();
+        ponder.store(true, Ordering::Relaxed);
+        let limits: Limits = Limits {
+            movetime: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let search = std::thread::spawn(move || {
+            searcher.search(&mut Board::new(), &limits, &mut |_| ());
+        });
+        // the clock is ignored while pondering
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(!search.is_finished());
+        ponder.store(false, Ordering::Relaxed);
+        let start: Instant = Instant::now();
+        search.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::cheso::search::PvLine;
+
+/// Maximum skill level (full strength).
+pub const MAX_LEVEL: u8 = 20;
+
+/// Minimum rating when limiting the strength.
+pub const MIN_ELO: u32 = 1350;
+
+/// Maximum rating when limiting the strength.
+pub const MAX_ELO: u32 = 2850;
+
+/// Number of principal variations searched to choose from when the skill is limited.
+pub const CANDIDATES: usize = 4;
+
+/// Maximum score loss (in centipawns) of a movement chosen below full strength.
+const MAX_LOSS: i32 = 100;
+
+/// Skill of the engine.
+///
+/// Either a level from 0 to 20 or, when limiting the strength, a rating (turned into a level).
+/// Below full strength, the search depth and nodes are limited, and the movement is chosen among the best ones
+/// with a randomness weighted by their scores: the weaker the level, the more likely a worse movement is chosen,
+/// but clearly losing movements are still very unlikely, so mistakes look plausible rather than random blunders.
+#[derive(Copy, Clone, Debug)]
+pub struct Skill {
+    pub level: u8,
+    pub limit_strength: bool,
+    pub elo: u32,
+}
+
+impl Default for Skill {
+    fn default() -> Skill {
+        Skill {
+            level: MAX_LEVEL,
+            limit_strength: false,
+            elo: MAX_ELO,
+        }
+    }
+}
+
+impl Skill {
+    /// Get the effective level, from the rating when limiting the strength.
+    pub fn effective_level(&self) -> u8 {
+        match self.limit_strength {
+            true => {
+                let elo: u32 = self.elo.clamp(MIN_ELO, MAX_ELO);
+                ((elo - MIN_ELO) * MAX_LEVEL as u32 / (MAX_ELO - MIN_ELO)) as u8
+            }
+            false => std::cmp::min(self.level, MAX_LEVEL),
+        }
+    }
+
+    /// Check if the skill is limited (below full strength).
+    pub fn is_limited(&self) -> bool {
+        self.effective_level() < MAX_LEVEL
+    }
+
+    /// Get the maximum search depth.
+    pub fn depth(&self) -> i32 {
+        1 + self.effective_level() as i32
+    }
+
+    /// Get the maximum number of nodes.
+    pub fn nodes(&self) -> u64 {
+        1000 << (self.effective_level() / 2)
+    }
+
+    /// Choose one of the principal variations (sorted from best to worst), with a random number.
+    ///
+    /// Each variation gets back part of its distance to the best score (the weaker, the larger the part),
+    /// plus a random bonus of up to the score difference between the first and the last ones (at most a pawn).
+    /// Variations more than a pawn worse than the best one are never chosen, and at full strength the best one is.
+    pub fn choose(&self, lines: &[PvLine], random: u64) -> usize {
+        if !self.is_limited() {
+            return 0;
+        }
+        let weakness: i32 = 120 - 2 * self.effective_level() as i32;
+        let (best, worst) = match (lines.first(), lines.last()) {
+            (Some(best), Some(worst)) => (best.score, worst.score),
+            _ => return 0,
+        };
+        let delta: i32 = std::cmp::min(best - worst, 100);
+        let mut random: u64 = random;
+        let mut chosen: usize = 0;
+        let mut chosen_score: i32 = i32::MIN;
+        for (index, line) in lines.iter().enumerate() {
+            if best - line.score > MAX_LOSS {
+                break;
+            }
+            let bonus: i32 =
+                (weakness * (best - line.score) + delta * (random % weakness as u64) as i32) / 128;
+            random = xorshift(random);
+            if line.score + bonus > chosen_score {
+                chosen = index;
+                chosen_score = line.score + bonus;
+            }
+        }
+        chosen
+    }
+}
+
+/// Get the next number of a xorshift pseudo-random sequence.
+pub fn xorshift(state: u64) -> u64 {
+    let mut state: u64 = state;
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(scores: &[i32]) -> Vec<PvLine> {
+        scores
+            .iter()
+            .map(|score| PvLine {
+                score: *score,
+                pv: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_strength_chooses_the_best_line() {
+        let lines: Vec<PvLine> = lines(&[30, 25, 20, 10]);
+        let mut random: u64 = 1;
+        for _ in 0..1000 {
+            assert_eq!(Skill::default().choose(&lines, random), 0);
+            random = xorshift(random);
+        }
+    }
+
+    #[test]
+    fn weakest_level_never_chooses_a_losing_line() {
+        let skill: Skill = Skill {
+            level: 0,
+            ..Skill::default()
+        };
+        let lines: Vec<PvLine> = lines(&[30, 10, -40, -400]);
+        let mut chosen: [usize; 4] = [0; 4];
+        let mut random: u64 = 1;
+        for _ in 0..1000 {
+            chosen[skill.choose(&lines, random)] += 1;
+            random = xorshift(random);
+        }
+        assert!(chosen[1] + chosen[2] > 0);
+        assert_eq!(chosen[3], 0);
+    }
+
+    #[test]
+    fn elo_is_mapped_to_levels() {
+        let level = |elo: u32| -> u8 {
+            Skill {
+                limit_strength: true,
+                elo,
+                ..Skill::default()
+            }
+            .effective_level()
+        };
+        assert_eq!(level(MIN_ELO), 0);
+        assert_eq!(level(MIN_ELO - 100), 0);
+        assert_eq!(level((MIN_ELO + MAX_ELO) / 2), MAX_LEVEL / 2);
+        assert_eq!(level(MAX_ELO), MAX_LEVEL);
+        assert_eq!(level(MAX_ELO + 100), MAX_LEVEL);
+        assert!(Skill {
+            limit_strength: true,
+            elo: MAX_ELO - 1,
+            ..Skill::default()
+        }
+        .is_limited());
+    }
+}
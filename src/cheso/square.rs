@@ -5,18 +5,46 @@ use crate::cheso::color::Color;
 /// This enumeration is used to store pieces in the board and to represent movements.
 /// An square must be between rank (row) 1 and 8, and between file (column) 1 and 8.
 /// It is impossible to generate an invalid (out of range) square.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Square {
     rank: isize,
     file: isize,
 }
 
+impl std::fmt::Display for Square {
+    /// Format the square in algebraic notation (e.g. `e4`).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file as u8 - 1) as char, self.rank)
+    }
+}
+
 impl Square {
     /// Create a new square.
     pub fn new(rank: isize, file: isize) -> Square {
         Square {
-            rank: std::cmp::max(1, std::cmp::min(8, rank)),
-            file: std::cmp::max(1, std::cmp::min(8, file)),
+            rank: rank.clamp(1, 8),
+            file: file.clamp(1, 8),
+        }
+    }
+
+    /// Create a square from its index (from 0 for a1 to 63 for h8).
+    pub fn from_index(index: usize) -> Square {
+        Square::new((index / 8) as isize + 1, (index % 8) as isize + 1)
+    }
+
+    /// Get the index of the square (from 0 for a1 to 63 for h8).
+    pub fn index(&self) -> usize {
+        ((self.rank - 1) * 8 + self.file - 1) as usize
+    }
+
+    /// Parse a square in algebraic notation (e.g. `e4`).
+    pub fn parse(text: &str) -> Option<Square> {
+        match text.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some(Square::new(
+                (rank - b'0') as isize,
+                (file - b'a') as isize + 1,
+            )),
+            _ => None,
         }
     }
 
@@ -44,6 +72,26 @@ impl Square {
         .filter(Self::on_board)
     }
 
+    /// Get the rank of the square from the point of view of a side (from 1 to 8).
+    pub fn relative_rank(&self, color: Color) -> isize {
+        match color {
+            Color::White => self.rank,
+            Color::Black => 9 - self.rank,
+        }
+    }
+
+    /// Get the distance to another square, in king moves.
+    pub fn distance(&self, other: &Square) -> isize {
+        (self.rank - other.rank)
+            .abs()
+            .max((self.file - other.file).abs())
+    }
+
+    /// Check if the square is a light square.
+    pub fn is_light(&self) -> bool {
+        (self.rank + self.file) % 2 == 1
+    }
+
     /// Check if is the last rank, based on the side to move.
     pub fn is_last_rank(&self, color: Color) -> bool {
         match color {
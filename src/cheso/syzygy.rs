@@ -0,0 +1,1781 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::movement::Movement;
+use crate::cheso::piece::Piece;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Maximum number of pieces of a Syzygy table.
+pub const MAX_PIECES: usize = 7;
+
+/// Magic number at the start of WDL files (`.rtbw`).
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+
+/// Magic number at the start of DTZ files (`.rtbz`).
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/// Flags of a table: side to move of a DTZ table, mapped values, values in plies, wide map and single value.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Rank of a root movement winning (or losing) within the fifty-move rule.
+const MAX_DTZ: i32 = 1 << 18;
+
+/// Number of pieces of each kind (by piece index), for white and black.
+type Material = [[u8; 6]; 2];
+
+/// Result of a position with perfect play, from the point of view of the side to move.
+///
+/// Cursed wins and blessed losses are wins and losses that are drawn by the fifty-move rule.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl std::ops::Neg for Wdl {
+    type Output = Wdl;
+
+    /// Get the result from the point of view of the opponent.
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-self.value())
+    }
+}
+
+impl Wdl {
+    /// Get the result from its value (from -2 for a loss to 2 for a win).
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// Get the value of the result (from -2 for a loss to 2 for a win).
+    pub fn value(&self) -> i32 {
+        *self as i32 - 2
+    }
+
+    /// Get the distance to zeroing of the movement making the position (a capture or a pawn movement).
+    fn dtz_before_zeroing(&self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+}
+
+/// State of a probe (besides failures).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum State {
+    Ok,
+    /// The DTZ table only stores the other side to move.
+    ChangeStm,
+    /// The best movement is a capture or a pawn movement, whose value the tables do not store.
+    ZeroingBestMove,
+}
+
+/// Tables of square indices, computed once.
+struct Maps {
+    /// Squares a2-h7 to 0-47, greater the nearer to the edge and the lower the rank.
+    pawns: [usize; 64],
+    /// Squares below the a1-h8 diagonal to 0-27.
+    b1h1h7: [usize; 64],
+    /// Squares of the a1-d1-d4 triangle to 0-9 (the diagonal last).
+    a1d1d4: [usize; 64],
+    /// The 462 legal placements of two kings, the first one in the a1-d1-d4 triangle.
+    kk: [[u64; 64]; 10],
+    /// Binomial coefficients, `binomial[k][n]` ways of choosing k elements among n.
+    binomial: [[u64; 64]; 6],
+    /// Index of the leading pawn, by number of leading pawns and square.
+    lead_pawn_idx: [[u64; 64]; 6],
+    /// Number of placements of the leading pawns, by number of leading pawns and file.
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+static MAPS: OnceLock<Maps> = OnceLock::new();
+
+/// Get the tables of square indices.
+fn maps() -> &'static Maps {
+    MAPS.get_or_init(Maps::new)
+}
+
+/// Get the rank of a square index (from 0).
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+/// Get the file of a square index (from 0).
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+/// Get the signed distance of a square index to the a1-h8 diagonal (negative below it).
+fn off_a1h8(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+impl Maps {
+    fn new() -> Maps {
+        let mut maps: Maps = Maps {
+            pawns: [0; 64],
+            b1h1h7: [0; 64],
+            a1d1d4: [0; 64],
+            kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+        let mut code: usize = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                maps.b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+        let mut diagonal: Vec<usize> = Vec::new();
+        code = 0;
+        for square in 0..=27 {
+            if off_a1h8(square) < 0 && file_of(square) <= 3 {
+                maps.a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && file_of(square) <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            maps.a1d1d4[square] = code;
+            code += 1;
+        }
+        // both kings on the diagonal are encoded last
+        let mut both_on_diagonal: Vec<(usize, usize)> = Vec::new();
+        let mut code: u64 = 0;
+        for idx in 0..10 {
+            for first in 0..=27 {
+                if maps.a1d1d4[first] != idx || idx == 0 && first != 1 {
+                    continue;
+                }
+                for second in 0..64 {
+                    let distance: usize = std::cmp::max(
+                        rank_of(first).abs_diff(rank_of(second)),
+                        file_of(first).abs_diff(file_of(second)),
+                    );
+                    if distance <= 1 || off_a1h8(first) == 0 && off_a1h8(second) > 0 {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        maps.kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            maps.kk[idx][second] = code;
+            code += 1;
+        }
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..std::cmp::min(6, n + 1) {
+                maps.binomial[k][n] = if k > 0 {
+                    maps.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+        // the leading pawn is the one with the greatest index, so other pawns have lower ones
+        let mut available: usize = 48;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx: u64 = 0;
+                for rank in 1..=6 {
+                    let square: usize = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        available -= 2;
+                        maps.pawns[square] = available + 1;
+                        maps.pawns[square ^ 7] = available;
+                    }
+                    maps.lead_pawn_idx[lead_pawns][square] = idx;
+                    idx += maps.binomial[lead_pawns - 1][maps.pawns[square]];
+                }
+                maps.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        maps
+    }
+}
+
+/// Read a little-endian number of some bytes (missing bytes read as zero).
+fn read_le(data: &[u8], offset: usize, bytes: usize) -> u64 {
+    (0..bytes).fold(0, |value, i| {
+        value | (data.get(offset + i).copied().unwrap_or(0) as u64) << (8 * i)
+    })
+}
+
+/// Read a big-endian number of some bytes (missing bytes read as zero).
+fn read_be(data: &[u8], offset: usize, bytes: usize) -> u64 {
+    (0..bytes).fold(0, |value, i| {
+        value << 8 | data.get(offset + i).copied().unwrap_or(0) as u64
+    })
+}
+
+/// Decoding data of a table (for a side to move and a file of the leading pawn).
+///
+/// Values are compressed by recursive pairing (each symbol stands for a pair of symbols) and canonical Huffman codes,
+/// in blocks of a fixed size. A sparse index points into the list of block lengths, to find the block of an index.
+/// Offsets are relative to the start of the file.
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: u64,
+    span: u64,
+    blocks: u64,
+    min_sym_len: u32,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: u64,
+    sparse_index: usize,
+    sparse_index_size: u64,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    map_idx: [u16; 4],
+}
+
+/// Contents of a WDL or DTZ file.
+struct Table {
+    data: Vec<u8>,
+    dtz: bool,
+    items: [[PairsData; 4]; 2],
+    map: usize,
+}
+
+/// Tables of a material signature (e.g. `KRvK`), loaded on first use.
+///
+/// The first key is the material with white as the side named first, the second one with the colors swapped.
+struct Entry {
+    key: Material,
+    key2: Material,
+    pieces: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    /// Number of pawns of the leading color and of the other one.
+    pawns: [usize; 2],
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl Entry {
+    /// Make the entry of a material signature, with the paths of its files.
+    fn new(code: &str, wdl_path: PathBuf, dtz_path: Option<PathBuf>) -> Option<Entry> {
+        let (white, black) = code.split_once('v')?;
+        let mut key: Material = [[0; 6]; 2];
+        for (color, side) in [white, black].iter().enumerate() {
+            if !side.starts_with('K') {
+                return None;
+            }
+            for letter in side.chars() {
+                key[color][Piece::from_char(letter)?.index()] += 1;
+            }
+        }
+        let pieces: usize = key.iter().flatten().map(|count| *count as usize).sum();
+        if pieces > MAX_PIECES {
+            return None;
+        }
+        let pawn: usize = Piece::Pawn.index();
+        let (white_pawns, black_pawns) = (key[0][pawn] as usize, key[1][pawn] as usize);
+        // the leading color has less pawns, for a better compression
+        let white_leads: bool = black_pawns == 0 || white_pawns > 0 && black_pawns >= white_pawns;
+        Some(Entry {
+            key,
+            key2: [key[1], key[0]],
+            pieces,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: key
+                .iter()
+                .any(|counts| counts[..Piece::King.index()].contains(&1)),
+            pawns: match white_leads {
+                true => [white_pawns, black_pawns],
+                false => [black_pawns, white_pawns],
+            },
+            wdl_path,
+            dtz_path,
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new(),
+        })
+    }
+
+    /// Get the WDL or DTZ table, loading it when needed.
+    fn table(&self, dtz: bool) -> Option<&Table> {
+        match dtz {
+            true => self
+                .dtz
+                .get_or_init(|| Table::load(self.dtz_path.as_deref()?, self, true))
+                .as_ref(),
+            false => self
+                .wdl
+                .get_or_init(|| Table::load(&self.wdl_path, self, false))
+                .as_ref(),
+        }
+    }
+}
+
+impl Table {
+    /// Load and check a table file, reading its decoding data.
+    fn load(path: &Path, entry: &Entry, dtz: bool) -> Option<Table> {
+        let data: Vec<u8> = std::fs::read(path).ok()?;
+        let magic: [u8; 4] = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if data.len() < 5 || data[..4] != magic {
+            return None;
+        }
+        let mut table: Table = Table {
+            data,
+            dtz,
+            items: Default::default(),
+            map: 0,
+        };
+        match table.init(entry)? <= table.data.len() {
+            true => Some(table),
+            false => None,
+        }
+    }
+
+    /// Get the decoding data of a side to move and a file of the leading pawn.
+    fn get(&self, entry: &Entry, stm: usize, file: usize) -> &PairsData {
+        let sides: usize = if self.dtz { 1 } else { 2 };
+        &self.items[stm % sides][if entry.has_pawns { file } else { 0 }]
+    }
+
+    /// Read the decoding data, returning the end offset of the data.
+    fn init(&mut self, entry: &Entry) -> Option<usize> {
+        let sides: usize = if !self.dtz && entry.key != entry.key2 {
+            2
+        } else {
+            1
+        };
+        let files: usize = if entry.has_pawns { 4 } else { 1 };
+        let both_pawns: bool = entry.has_pawns && entry.pawns[1] > 0;
+        let mut offset: usize = 5;
+        for file in 0..files {
+            let first: u8 = *self.data.get(offset)?;
+            let second: u8 = match both_pawns {
+                true => *self.data.get(offset + 1)?,
+                false => 0xFF,
+            };
+            let order: [[u8; 2]; 2] = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            offset += 1 + both_pawns as usize;
+            for k in 0..entry.pieces {
+                let pieces: u8 = *self.data.get(offset)?;
+                for side in 0..sides {
+                    self.items[side][file].pieces[k] =
+                        if side == 1 { pieces >> 4 } else { pieces & 0xF };
+                }
+                offset += 1;
+            }
+            for (side, order) in order.iter().enumerate().take(sides) {
+                set_groups(entry, &mut self.items[side][file], order, file);
+            }
+        }
+        offset += offset & 1;
+        for file in 0..files {
+            for side in 0..sides {
+                offset = self.set_sizes(side, file, offset)?;
+            }
+        }
+        if self.dtz {
+            offset = self.set_dtz_map(offset, files)?;
+        }
+        for file in 0..files {
+            for item in self.items[..sides].iter_mut() {
+                item[file].sparse_index = offset;
+                offset += item[file].sparse_index_size as usize * 6;
+            }
+        }
+        for file in 0..files {
+            for item in self.items[..sides].iter_mut() {
+                item[file].block_length = offset;
+                offset += item[file].block_length_size as usize * 2;
+            }
+        }
+        for file in 0..files {
+            for item in self.items[..sides].iter_mut() {
+                offset = (offset + 0x3F) & !0x3F;
+                item[file].data = offset;
+                offset += (item[file].blocks * item[file].block_size) as usize;
+            }
+        }
+        Some(offset)
+    }
+
+    /// Read the sizes and the Huffman codes of a side and file, returning the offset after them.
+    fn set_sizes(&mut self, side: usize, file: usize, offset: usize) -> Option<usize> {
+        let data: &[u8] = &self.data;
+        let d: &mut PairsData = &mut self.items[side][file];
+        d.flags = *data.get(offset)?;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            // the single value is stored as the minimum symbol length
+            d.min_sym_len = *data.get(offset + 1)? as u32;
+            return Some(offset + 2);
+        }
+        let end: usize = d
+            .group_len
+            .iter()
+            .position(|len| *len == 0)
+            .unwrap_or(MAX_PIECES);
+        let size: u64 = d.group_idx[end];
+        d.block_size = 1 << *data.get(offset + 1)?;
+        d.span = 1 << *data.get(offset + 2)?;
+        d.sparse_index_size = size.div_ceil(d.span);
+        let padding: u64 = *data.get(offset + 3)? as u64;
+        d.blocks = read_le(data, offset + 4, 4);
+        d.block_length_size = d.blocks + padding;
+        let max_sym_len: u32 = *data.get(offset + 8)? as u32;
+        d.min_sym_len = *data.get(offset + 9)? as u32;
+        if max_sym_len < d.min_sym_len || d.min_sym_len == 0 {
+            return None;
+        }
+        d.lowest_sym = offset + 10;
+        // longer codes have lower values, so base64[l] is the lowest code of length l padded to 64 bits
+        let lengths: usize = (max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            d.base64[i] = d.base64[i + 1]
+                .wrapping_add(read_le(data, d.lowest_sym + 2 * i, 2))
+                .wrapping_sub(read_le(data, d.lowest_sym + 2 * (i + 1), 2))
+                / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - d.min_sym_len).unwrap_or(0);
+        }
+        let offset: usize = d.lowest_sym + 2 * lengths;
+        let symbols: usize = read_le(data, offset, 2) as usize;
+        d.btree = offset + 2;
+        if d.btree + 3 * symbols > data.len() {
+            return None;
+        }
+        // each symbol of the tree stands for a pair of symbols, down to the leaves (the values)
+        d.symlen = vec![0; symbols];
+        let mut visited: Vec<bool> = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                d.symlen[symbol] = set_symlen(data, d.btree, &mut d.symlen, &mut visited, symbol);
+            }
+        }
+        Some(d.btree + 3 * symbols + (symbols & 1))
+    }
+
+    /// Read the maps of the DTZ values (by result), returning the offset after them.
+    fn set_dtz_map(&mut self, offset: usize, files: usize) -> Option<usize> {
+        let mut offset: usize = offset;
+        self.map = offset;
+        for file in 0..files {
+            let flags: u8 = self.items[0][file].flags;
+            if flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = ((offset - self.map) / 2 + 1) as u16;
+                    offset += 2 * read_le(&self.data, offset, 2) as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = (offset - self.map + 1) as u16;
+                    offset += *self.data.get(offset)? as usize + 1;
+                }
+            }
+        }
+        Some(offset + (offset & 1))
+    }
+
+    /// Get the left and right symbols of a symbol of the tree.
+    fn pair(&self, d: &PairsData, symbol: usize) -> (usize, usize) {
+        pair(&self.data, d.btree, symbol)
+    }
+
+    /// Get the length (minus one) of the values a symbol stands for.
+    fn symlen(d: &PairsData, symbol: usize) -> i64 {
+        d.symlen.get(symbol).copied().unwrap_or(0) as i64
+    }
+
+    /// Decompress the value of an index.
+    fn decompress(&self, d: &PairsData, idx: u64) -> i32 {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return d.min_sym_len as i32;
+        }
+        // find the block from the nearest entry of the sparse index
+        let k: usize = (idx / d.span) as usize;
+        let mut block: u64 = read_le(&self.data, d.sparse_index + 6 * k, 4);
+        let mut offset: i64 = read_le(&self.data, d.sparse_index + 6 * k + 4, 2) as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+        let block_length = |block: u64| -> i64 {
+            read_le(&self.data, d.block_length + 2 * block as usize, 2) as i64
+        };
+        while offset < 0 && block > 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) && block < d.blocks {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+        // decode the symbols of the block until reaching the one standing for the value
+        let mut pointer: usize = d.data + (block * d.block_size) as usize;
+        let mut buffer: u64 = read_be(&self.data, pointer, 8);
+        let mut buffer_size: u32 = 64;
+        pointer += 8;
+        let mut symbol: usize;
+        loop {
+            let mut len: usize = 0;
+            while len + 1 < d.base64.len() && buffer < d.base64[len] {
+                len += 1;
+            }
+            let code: u64 = buffer
+                .wrapping_sub(d.base64[len])
+                .checked_shr(64 - len as u32 - d.min_sym_len)
+                .unwrap_or(0);
+            symbol = (code as u16)
+                .wrapping_add(read_le(&self.data, d.lowest_sym + 2 * len, 2) as u16)
+                as usize;
+            if offset < Table::symlen(d, symbol) + 1 {
+                break;
+            }
+            offset -= Table::symlen(d, symbol) + 1;
+            let consumed: u32 = len as u32 + d.min_sym_len;
+            buffer = buffer.checked_shl(consumed).unwrap_or(0);
+            buffer_size = buffer_size.saturating_sub(consumed);
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= read_be(&self.data, pointer, 4) << (64 - buffer_size);
+                pointer += 4;
+            }
+        }
+        // expand the symbol into its pair until reaching a leaf
+        while Table::symlen(d, symbol) != 0 {
+            let (left, right) = self.pair(d, symbol);
+            if offset < Table::symlen(d, left) + 1 {
+                symbol = left;
+            } else {
+                offset -= Table::symlen(d, left) + 1;
+                symbol = right;
+            }
+        }
+        self.pair(d, symbol).0 as i32
+    }
+
+    /// Get the side to move, the file of the leading pawn and the index of a position of the material of the table.
+    fn index(&self, entry: &Entry, board: &Board) -> (usize, usize, u64) {
+        let material: Material = material(board);
+        let maps: &Maps = maps();
+        // tables have the stronger side as white, and symmetric ones only white to move:
+        // otherwise swap the colors and flip the board vertically
+        let black: bool = board.side_to_move == Color::Black;
+        let flip: bool = entry.key == entry.key2 && black || material != entry.key;
+        let flip_color: u8 = if flip { 8 } else { 0 };
+        let flip_squares: usize = if flip { 56 } else { 0 };
+        let stm: usize = (flip ^ black) as usize;
+        let mut squares: [usize; MAX_PIECES] = [0; MAX_PIECES];
+        let mut pieces: [u8; MAX_PIECES] = [0; MAX_PIECES];
+        let mut size: usize = 0;
+        let mut lead_pawns: usize = 0;
+        let mut file: usize = 0;
+        let mut lead_color: Option<Color> = None;
+        if entry.has_pawns {
+            // the leading pawn is the one nearest the edge (and with the lowest rank), which gives the file of the table
+            let code: u8 = self.get(entry, 0, 0).pieces[0] ^ flip_color;
+            let color: Color = if code & 8 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            for (square, piece) in board.get_pieces(color) {
+                if *piece == Piece::Pawn {
+                    squares[size] = square.index() ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+            let lead: usize = (0..lead_pawns).fold(0, |lead, i| {
+                match maps.pawns[squares[i]] > maps.pawns[squares[lead]] {
+                    true => i,
+                    false => lead,
+                }
+            });
+            squares.swap(0, lead);
+            file = std::cmp::min(file_of(squares[0]), 7 - file_of(squares[0]));
+            lead_color = Some(color);
+        }
+        let mut others: Vec<(usize, u8)> = Vec::new();
+        for color in [Color::White, Color::Black] {
+            for (square, piece) in board.get_pieces(color) {
+                if *piece != Piece::Pawn || Some(color) != lead_color {
+                    others.push((
+                        square.index() ^ flip_squares,
+                        piece_code(*piece, color) ^ flip_color,
+                    ));
+                }
+            }
+        }
+        others.sort_by_key(|(square, _)| *square ^ flip_squares);
+        for (square, code) in others {
+            squares[size] = square;
+            pieces[size] = code;
+            size += 1;
+        }
+        let d: &PairsData = self.get(entry, stm, file);
+        // order the pieces as in the table
+        for i in lead_pawns..size - 1 {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+        // mirror the board so the leading piece is on the files a to d
+        if file_of(squares[0]) > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 7;
+            }
+        }
+        let mut idx: u64;
+        if entry.has_pawns {
+            idx = maps.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|square| maps.pawns[*square]);
+            for (i, square) in squares[..lead_pawns].iter().enumerate().skip(1) {
+                idx += maps.binomial[i][maps.pawns[*square]];
+            }
+        } else {
+            // mirror the board so the leading piece is on the ranks 1 to 4, then below the a1-h8 diagonal
+            if rank_of(squares[0]) > 3 {
+                for square in squares[..size].iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for square in squares[i..size].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+            idx = match entry.has_unique_pieces {
+                true => encode_unique(&squares, maps),
+                false => maps.kk[maps.a1d1d4[squares[0]]][squares[1]],
+            };
+        }
+        // encode the other groups (pawns, then pieces) by their squares, skipping the squares of previous groups
+        idx *= d.group_idx[0];
+        let mut start: usize = d.group_len[0];
+        let mut remaining_pawns: bool = entry.has_pawns && entry.pawns[1] > 0;
+        let mut next: usize = 1;
+        while d.group_len[next] != 0 {
+            let end: usize = start + d.group_len[next];
+            squares[start..end].sort();
+            let mut n: u64 = 0;
+            for i in start..end {
+                let adjust: usize = squares[..start]
+                    .iter()
+                    .filter(|square| squares[i] > **square)
+                    .count();
+                let pawns: usize = if remaining_pawns { 8 } else { 0 };
+                n += maps.binomial[i - start + 1][squares[i] - adjust - pawns];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start = end;
+            next += 1;
+        }
+        (stm, file, idx)
+    }
+
+    /// Map a decompressed value to the score of the table.
+    ///
+    /// WDL values are results; DTZ values are distances in plies (possibly through a map of the values by result).
+    fn map_score(&self, entry: &Entry, file: usize, value: i32, wdl: Wdl) -> i32 {
+        if !self.dtz {
+            return value - 2;
+        }
+        let d: &PairsData = self.get(entry, 0, file);
+        let mut value: i32 = value;
+        if d.flags & FLAG_MAPPED != 0 {
+            let index: usize = d.map_idx[[1, 3, 0, 2, 0][(wdl.value() + 2) as usize]] as usize;
+            value = match d.flags & FLAG_WIDE != 0 {
+                true => read_le(&self.data, self.map + 2 * (index + value as usize), 2) as i32,
+                false => read_le(&self.data, self.map + index + value as usize, 1) as i32,
+            };
+        }
+        if wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0
+            || wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+        value + 1
+    }
+}
+
+/// Get the left and right symbols of a symbol of a tree (12 bits each).
+fn pair(data: &[u8], btree: usize, symbol: usize) -> (usize, usize) {
+    let bytes: u64 = read_le(data, btree + 3 * symbol, 3);
+    ((bytes & 0xFFF) as usize, (bytes >> 12) as usize)
+}
+
+/// Compute the length (minus one) of the values a symbol stands for, recursively.
+fn set_symlen(
+    data: &[u8],
+    btree: usize,
+    symlen: &mut [u8],
+    visited: &mut [bool],
+    symbol: usize,
+) -> u8 {
+    visited[symbol] = true;
+    let (left, right) = pair(data, btree, symbol);
+    if right == 0xFFF || left >= symlen.len() || right >= symlen.len() {
+        return 0;
+    }
+    if !visited[left] {
+        symlen[left] = set_symlen(data, btree, symlen, visited, left);
+    }
+    if !visited[right] {
+        symlen[right] = set_symlen(data, btree, symlen, visited, right);
+    }
+    symlen[left].wrapping_add(symlen[right]).wrapping_add(1)
+}
+
+/// Group the pieces encoded together, and compute the factor of each group in the index.
+///
+/// A group has pieces of the same kind and color, except the leading one: pawns of the leading color,
+/// three unique pieces (kings included) or the two kings. For example `KRvKN` gives `KRK + N`.
+/// The order of the groups in the index is a parameter of the table.
+fn set_groups(entry: &Entry, d: &mut PairsData, order: &[u8; 2], file: usize) {
+    let maps: &Maps = maps();
+    let mut n: usize = 0;
+    let mut first_len: i32 = match (entry.has_pawns, entry.has_unique_pieces) {
+        (true, _) => 0,
+        (false, true) => 3,
+        (false, false) => 2,
+    };
+    d.group_len[0] = 1;
+    for i in 1..entry.pieces {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+    let both_pawns: bool = entry.has_pawns && entry.pawns[1] > 0;
+    let mut next: usize = if both_pawns { 2 } else { 1 };
+    let mut free_squares: usize = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+    let mut k: usize = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= match (entry.has_pawns, entry.has_unique_pieces) {
+                (true, _) => maps.lead_pawns_size[d.group_len[0]][file],
+                (false, true) => 31332,
+                (false, false) => 462,
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= maps.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= maps.binomial[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+}
+
+/// Get the material of a position.
+fn material(board: &Board) -> Material {
+    let mut material: Material = [[0; 6]; 2];
+    for (color, pieces) in [&board.white_pieces, &board.black_pieces]
+        .iter()
+        .enumerate()
+    {
+        for piece in pieces.values() {
+            material[color][piece.index()] += 1;
+        }
+    }
+    material
+}
+
+/// Get the code of a piece in the tables (1 to 6 for white pieces, 9 to 14 for black ones).
+fn piece_code(piece: Piece, color: Color) -> u8 {
+    piece.index() as u8 + 1 + if color == Color::Black { 8 } else { 0 }
+}
+
+/// Root movements ranked with the tablebases.
+#[derive(Clone, Debug)]
+pub struct RootProbe {
+    /// The best ranked movements (the only ones worth searching).
+    pub moves: Vec<Movement>,
+    /// Result of the position.
+    pub wdl: Wdl,
+    /// Whether the movements were ranked by the distance to zeroing (otherwise only by their result).
+    pub dtz: bool,
+}
+
+/// Syzygy endgame tablebases.
+///
+/// The tables (WDL files `.rtbw` with the results, and DTZ files `.rtbz` with the distances to zeroing,
+/// the number of plies to the next capture or pawn movement) are found in a list of directories
+/// and loaded in memory on first use.
+/// Positions with castling rights are not in the tables, nor captures (which must be searched, since
+/// the tables may store any value for positions where a capture is the best movement).
+#[derive(Default)]
+pub struct Tablebases {
+    entries: HashMap<Material, Arc<Entry>>,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Find the tables in a list of directories (separated by `:`, or `;` on Windows).
+    pub fn open(paths: &str) -> Tablebases {
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        for directory in std::env::split_paths(paths) {
+            if let Ok(entries) = std::fs::read_dir(&directory) {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                        files.entry(name.to_string()).or_insert(path.clone());
+                    }
+                }
+            }
+        }
+        let mut tablebases: Tablebases = Tablebases::default();
+        for (name, path) in files.iter() {
+            let code: &str = match name.strip_suffix(".rtbw") {
+                Some(code) => code,
+                None => continue,
+            };
+            let dtz_path: Option<PathBuf> = files.get(&format!("{}.rtbz", code)).cloned();
+            if let Some(entry) = Entry::new(code, path.clone(), dtz_path) {
+                tablebases.max_pieces = std::cmp::max(tablebases.max_pieces, entry.pieces);
+                let entry: Arc<Entry> = Arc::new(entry);
+                tablebases.entries.insert(entry.key, entry.clone());
+                tablebases.entries.insert(entry.key2, entry);
+            }
+        }
+        tablebases
+    }
+
+    /// Get the number of material signatures found (counting once both colors of a signature).
+    pub fn len(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|(key, entry)| **key == entry.key)
+            .count()
+    }
+
+    /// Check if no table was found.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the largest number of pieces of the tables.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Check if a position can be probed: no castling rights and at most as many pieces as the limit and the tables.
+    pub fn can_probe(&self, board: &Board, limit: usize) -> bool {
+        board.white_pieces.len() + board.black_pieces.len() <= std::cmp::min(limit, self.max_pieces)
+            && !board.white_kingside_castling
+            && !board.white_queenside_castling
+            && !board.black_kingside_castling
+            && !board.black_queenside_castling
+    }
+
+    /// Get the result of a position, if its tables are found.
+    pub fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        let mut state: State = State::Ok;
+        self.search(board, false, &mut state)
+    }
+
+    /// Get the distance to zeroing of a position, if its tables are found.
+    ///
+    /// The distance is in plies, positive when winning and negative when losing (zero for draws).
+    /// Cursed wins and blessed losses are counted beyond 100 plies.
+    pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        let mut state: State = State::Ok;
+        let wdl: Wdl = self.search(board, true, &mut state)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if state == State::ZeroingBestMove {
+            return Some(wdl.dtz_before_zeroing());
+        }
+        let dtz: i32 = self.probe_table(board, true, wdl, &mut state)?;
+        if state != State::ChangeStm {
+            let cursed: bool = wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss;
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.value().signum());
+        }
+        // the table stores the other side to move: take the best distance of the replies
+        let mut best: i32 = 0xFFFF;
+        for movement in board.gen_legal_moves() {
+            let zeroing: bool = movement.capture.is_some()
+                || board.get_square(&movement.from).map(|(piece, _)| piece) == Some(Piece::Pawn);
+            board.make_move(&movement);
+            let dtz: Option<i32> = match zeroing {
+                true => self
+                    .search(board, false, &mut state)
+                    .map(|wdl| -wdl.dtz_before_zeroing()),
+                false => self.probe_dtz(board).map(|dtz| -dtz),
+            };
+            let mates: bool =
+                dtz == Some(1) && board.in_check() && board.gen_legal_moves().is_empty();
+            board.unmake_move(&movement);
+            let mut dtz: i32 = dtz?;
+            if mates {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == wdl.value().signum() {
+                best = dtz;
+            }
+        }
+        Some(if best == 0xFFFF { -1 } else { best })
+    }
+
+    /// Rank the legal movements of the root, keeping the best ones.
+    ///
+    /// Movements are ranked by distance to zeroing when the DTZ tables are found (winning movements within the
+    /// fifty-move rule equally, then the others by how far the rule is), otherwise only by their results.
+    pub fn probe_root(&self, board: &mut Board) -> Option<RootProbe> {
+        let moves: Vec<Movement> = board.gen_legal_moves();
+        let (ranks, dtz) = match self.rank_root_dtz(board, &moves) {
+            Some(ranks) => (ranks, true),
+            None => (self.rank_root_wdl(board, &moves)?, false),
+        };
+        let best: i32 = *ranks.iter().max()?;
+        let wdl: Wdl = match best {
+            rank if rank >= MAX_DTZ - 100 => Wdl::Win,
+            rank if rank > 0 => Wdl::CursedWin,
+            0 => Wdl::Draw,
+            rank if rank > -MAX_DTZ + 100 => Wdl::BlessedLoss,
+            _ => Wdl::Loss,
+        };
+        Some(RootProbe {
+            moves: moves
+                .iter()
+                .zip(ranks.iter())
+                .filter(|(_, rank)| **rank == best)
+                .map(|(movement, _)| *movement)
+                .collect(),
+            wdl,
+            dtz,
+        })
+    }
+
+    /// Rank the root movements by their distance to zeroing.
+    fn rank_root_dtz(&self, board: &mut Board, moves: &[Movement]) -> Option<Vec<i32>> {
+        let reversible: i32 = board.reversible_moves as i32;
+        let repetition: bool = board.is_repetition();
+        let mut ranks: Vec<i32> = Vec::new();
+        for movement in moves {
+            board.make_move(movement);
+            let dtz: Option<i32> = if board.reversible_moves == 0 {
+                self.probe_wdl(board).map(|wdl| (-wdl).dtz_before_zeroing())
+            } else if board.is_draw() {
+                Some(0)
+            } else {
+                self.probe_dtz(board).map(|dtz| -dtz - dtz.signum())
+            };
+            let mates: bool = board.in_check() && board.gen_legal_moves().is_empty();
+            board.unmake_move(movement);
+            let dtz: i32 = match dtz? {
+                2 if mates => 1,
+                dtz => dtz,
+            };
+            ranks.push(match dtz {
+                dtz if dtz > 0 && dtz + reversible <= 99 && !repetition => MAX_DTZ,
+                dtz if dtz > 0 => MAX_DTZ - (dtz + reversible),
+                dtz if dtz < 0 && -dtz * 2 + reversible < 100 => -MAX_DTZ,
+                dtz if dtz < 0 => -MAX_DTZ + (-dtz + reversible),
+                _ => 0,
+            });
+        }
+        Some(ranks)
+    }
+
+    /// Rank the root movements by their results.
+    fn rank_root_wdl(&self, board: &mut Board, moves: &[Movement]) -> Option<Vec<i32>> {
+        let mut ranks: Vec<i32> = Vec::new();
+        for movement in moves {
+            board.make_move(movement);
+            let wdl: Option<Wdl> = match board.is_draw() {
+                true => Some(Wdl::Draw),
+                false => self.probe_wdl(board).map(|wdl| -wdl),
+            };
+            board.unmake_move(movement);
+            ranks.push(match wdl? {
+                Wdl::Loss => -MAX_DTZ,
+                Wdl::BlessedLoss => -MAX_DTZ + 101,
+                Wdl::Draw => 0,
+                Wdl::CursedWin => MAX_DTZ - 101,
+                Wdl::Win => MAX_DTZ,
+            });
+        }
+        Some(ranks)
+    }
+
+    /// Get the result of a position, searching the captures (and pawn movements, when probing DTZ tables).
+    ///
+    /// The tables may store any value for positions where a capture wins, or where the side to move can draw
+    /// by a capture but loses otherwise, so the best of the captures and the value of the table is taken.
+    fn search(&self, board: &mut Board, zeroing: bool, state: &mut State) -> Option<Wdl> {
+        let moves: Vec<Movement> = board.gen_legal_moves();
+        let mut best: Wdl = Wdl::Loss;
+        let mut searched: usize = 0;
+        for movement in moves.iter() {
+            let pawn: bool =
+                board.get_square(&movement.from).map(|(piece, _)| piece) == Some(Piece::Pawn);
+            if movement.capture.is_none() && (!zeroing || !pawn) {
+                continue;
+            }
+            searched += 1;
+            board.make_move(movement);
+            let value: Option<Wdl> = self.search(board, false, state).map(|wdl| -wdl);
+            board.unmake_move(movement);
+            let value: Wdl = value?;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    *state = State::ZeroingBestMove;
+                    return Some(value);
+                }
+            }
+        }
+        // when all the movements were searched, the table is not needed (and may be wrong, with en passant)
+        let all_searched: bool = searched > 0 && searched == moves.len();
+        let value: Wdl = match all_searched {
+            true => best,
+            false => Wdl::from_value(self.probe_table(board, false, Wdl::Draw, state)?),
+        };
+        if best >= value {
+            *state = match best > Wdl::Draw || all_searched {
+                true => State::ZeroingBestMove,
+                false => State::Ok,
+            };
+            return Some(best);
+        }
+        *state = State::Ok;
+        Some(value)
+    }
+
+    /// Probe the WDL or DTZ table of a position (the result must be given for DTZ tables).
+    fn probe_table(&self, board: &Board, dtz: bool, wdl: Wdl, state: &mut State) -> Option<i32> {
+        let material: Material = material(board);
+        if board.white_pieces.len() + board.black_pieces.len() == 2 {
+            return Some(0);
+        }
+        let entry: &Entry = self.entries.get(&material)?;
+        let table: &Table = entry.table(dtz)?;
+        let (stm, file, idx) = table.index(entry, board);
+        // DTZ tables store one side to move only
+        if table.dtz {
+            let flags: u8 = table.get(entry, stm, file).flags;
+            if (flags & FLAG_STM) as usize != stm && (entry.key != entry.key2 || entry.has_pawns) {
+                *state = State::ChangeStm;
+                return Some(0);
+            }
+        }
+        let d: &PairsData = table.get(entry, stm, file);
+        Some(table.map_score(entry, file, table.decompress(d, idx), wdl))
+    }
+}
+
+/// Encode the leading group of three unique pieces (the first one in the a1-d1-d4 triangle).
+fn encode_unique(squares: &[usize], maps: &Maps) -> u64 {
+    let (first, second, third) = (squares[0], squares[1], squares[2]);
+    let adjust1: usize = (second > first) as usize;
+    let adjust2: usize = (third > first) as usize + (third > second) as usize;
+    let index: usize = if off_a1h8(first) != 0 {
+        (maps.a1d1d4[first] * 63 + second - adjust1) * 62 + third - adjust2
+    } else if off_a1h8(second) != 0 {
+        (6 * 63 + rank_of(first) * 28 + maps.b1h1h7[second]) * 62 + third - adjust2
+    } else if off_a1h8(third) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank_of(first) * 7 * 28
+            + (rank_of(second) - adjust1) * 28
+            + maps.b1h1h7[third]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank_of(first) * 7 * 6
+            + (rank_of(second) - adjust1) * 6
+            + (rank_of(third) - adjust2)
+    };
+    index as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheso::tablebase::{Dtm, Signature, TablebaseSet};
+    use std::collections::BTreeMap;
+
+    /// Directory of the test tables, written by `write_test_tables`.
+    const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+    /// Material signatures of the test tables (the promotions of the pawn need the tables of the pieces).
+    const CODES: [&str; 5] = ["KQvK", "KRvK", "KBvK", "KNvK", "KPvK"];
+
+    /// Logarithm of the size in bytes of the blocks of the written tables.
+    const BLOCK_SIZE: u8 = 6;
+
+    /// Logarithm of the number of values between entries of the sparse index of the written tables.
+    const SPAN: u8 = 10;
+
+    /// Maximum number of values of a block of the written tables (so offsets in the sparse index fit in 16 bits).
+    const BLOCK_VALUES: usize = 60000;
+
+    /// Logarithm of the longest run of equal values encoded by a symbol of the written tables.
+    const MAX_RUN: u32 = 8;
+
+    /// Probe the result and the distance to zeroing of a position.
+    fn probe(tablebases: &Tablebases, fen: &str) -> (Wdl, i32) {
+        let mut board: Board = Board::from_fen(fen).unwrap();
+        (
+            tablebases.probe_wdl(&mut board).unwrap(),
+            tablebases.probe_dtz(&mut board).unwrap(),
+        )
+    }
+
+    #[test]
+    fn tables_are_found() {
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        assert_eq!(tablebases.len(), CODES.len());
+        assert_eq!(tablebases.max_pieces(), 3);
+        assert!(Tablebases::open("/nonexistent").is_empty());
+        let mut board: Board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(!tablebases.can_probe(&board, MAX_PIECES));
+        board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(tablebases.can_probe(&board, MAX_PIECES));
+        assert!(!tablebases.can_probe(&board, 2));
+        board = Board::from_fen("4k3/8/8/8/8/8/8/RR2K3 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&mut board), None);
+    }
+
+    #[test]
+    fn tables_match_official_references() {
+        // the files start with the magic numbers of the official tables
+        for code in CODES {
+            let path: PathBuf = Path::new(TABLES).join(code);
+            let wdl: Vec<u8> = std::fs::read(path.with_extension("rtbw")).unwrap();
+            let dtz: Vec<u8> = std::fs::read(path.with_extension("rtbz")).unwrap();
+            assert_eq!(wdl[..4], [0x71, 0xE8, 0x23, 0x5D], "{}", code);
+            assert_eq!(dtz[..4], [0xD7, 0x66, 0x0C, 0xA5], "{}", code);
+        }
+        // the longest wins are the known longest mates, in 10 movements with a queen and 16 with a rook
+        // (by symmetry, the white king stays in the a1-d1-d4 triangle)
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        for (piece, plies) in [('Q', 19), ('R', 31)] {
+            let mut longest: i32 = 0;
+            for king in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27] {
+                for other in 0..64 {
+                    for lone in 0..64 {
+                        let squares: [usize; 3] = [king, other, lone];
+                        if other == king || lone == king || lone == other {
+                            continue;
+                        }
+                        let text: String = fen(&['K', piece, 'k'], &squares, Color::White);
+                        let mut board: Board = Board::from_fen(&text).unwrap();
+                        if let Some(dtz) = tablebases.probe_dtz(&mut board) {
+                            longest = longest.max(dtz);
+                        }
+                    }
+                }
+            }
+            assert_eq!(longest, plies, "K{}vK", piece);
+        }
+    }
+
+    #[test]
+    fn rook_endings() {
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        // mate in one, checkmated, and the rook captured
+        assert_eq!(
+            probe(&tablebases, "k7/8/1K6/8/8/8/8/7R w - - 0 1"),
+            (Wdl::Win, 1)
+        );
+        assert_eq!(
+            probe(&tablebases, "R6k/8/6K1/8/8/8/8/8 b - - 0 1"),
+            (Wdl::Loss, -1)
+        );
+        assert_eq!(
+            probe(&tablebases, "k7/1R6/8/8/8/8/8/7K b - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+        // the DTZ table only stores white to move: the only movement of black allows the mate
+        assert_eq!(
+            probe(&tablebases, "k7/2K5/8/8/8/8/8/1R6 b - - 0 1"),
+            (Wdl::Loss, -2)
+        );
+        // the same positions with the colors swapped
+        assert_eq!(
+            probe(&tablebases, "K7/8/1k6/8/8/8/8/7r b - - 0 1"),
+            (Wdl::Win, 1)
+        );
+        assert_eq!(
+            probe(&tablebases, "r6K/8/6k1/8/8/8/8/8 w - - 0 1"),
+            (Wdl::Loss, -1)
+        );
+        assert_eq!(
+            probe(&tablebases, "K7/2k5/8/8/8/8/8/1r6 w - - 0 1"),
+            (Wdl::Loss, -2)
+        );
+    }
+
+    #[test]
+    fn queen_and_minor_piece_endings() {
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        assert_eq!(
+            probe(&tablebases, "4k3/8/8/8/8/8/8/Q3K3 w - - 0 1"),
+            (Wdl::Win, 13)
+        );
+        assert_eq!(
+            probe(&tablebases, "4k3/8/8/8/8/8/8/Q3K3 b - - 0 1"),
+            (Wdl::Loss, -16)
+        );
+        assert_eq!(
+            probe(&tablebases, "4k3/3Q4/8/8/8/8/8/4K3 b - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+        assert_eq!(
+            probe(&tablebases, "4K3/3q4/8/8/8/8/8/4k3 w - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+        assert_eq!(
+            probe(&tablebases, "4k3/8/8/8/8/8/8/B3K3 w - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+        assert_eq!(
+            probe(&tablebases, "4k3/8/8/8/8/8/8/4K2n b - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+    }
+
+    #[test]
+    fn pawn_endings() {
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        // the king is outside the square of the pawn, which wins moving forward
+        assert_eq!(
+            probe(&tablebases, "k7/8/8/8/8/8/7P/7K w - - 0 1"),
+            (Wdl::Win, 1)
+        );
+        assert_eq!(
+            probe(&tablebases, "k7/8/8/8/8/8/7P/7K b - - 0 1"),
+            (Wdl::Loss, -2)
+        );
+        // the king blocks the rook pawn
+        assert_eq!(
+            probe(&tablebases, "7k/8/8/8/8/8/7P/7K w - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+        // the same positions with the colors swapped
+        assert_eq!(
+            probe(&tablebases, "7k/7p/8/8/8/8/8/K7 w - - 0 1"),
+            (Wdl::Loss, -2)
+        );
+        assert_eq!(
+            probe(&tablebases, "7k/7p/8/8/8/8/8/K7 b - - 0 1"),
+            (Wdl::Win, 1)
+        );
+        assert_eq!(
+            probe(&tablebases, "7k/8/8/8/8/8/7p/7K b - - 0 1"),
+            (Wdl::Draw, 0)
+        );
+    }
+
+    #[test]
+    fn root_keeps_only_the_best_movements() {
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        for (fen, wdl) in [
+            ("8/8/8/8/8/2k5/3R4/7K w - - 0 1", Wdl::Win),
+            ("8/4k3/8/3K4/4P3/8/8/8 w - - 0 1", Wdl::Win),
+            ("8/8/8/8/8/8/5k2/3Q2K1 w - - 0 1", Wdl::Win),
+            ("4k3/3Q4/8/8/8/8/8/4K3 b - - 0 1", Wdl::Draw),
+        ] {
+            let mut board: Board = Board::from_fen(fen).unwrap();
+            let root: RootProbe = tablebases.probe_root(&mut board).unwrap();
+            assert_eq!(root.wdl, wdl, "{}", fen);
+            assert!(root.dtz);
+            let moves: Vec<Movement> = board.gen_legal_moves();
+            let best: Vec<Movement> = moves
+                .iter()
+                .filter(|movement| {
+                    board.make_move(movement);
+                    let result: Option<Wdl> = tablebases.probe_wdl(&mut board);
+                    board.unmake_move(movement);
+                    result == Some(-wdl)
+                })
+                .copied()
+                .collect();
+            assert!(best.len() < moves.len(), "{}", fen);
+            assert_eq!(root.moves, best, "{}", fen);
+        }
+    }
+
+    /// Get the FEN of a placement of pieces (by their letters) with a side to move.
+    fn fen(pieces: &[char], squares: &[usize], side: Color) -> String {
+        let mut rows: Vec<String> = Vec::new();
+        for rank in (0..8).rev() {
+            let mut row: String = String::new();
+            let mut empty: u8 = 0;
+            for file in 0..8 {
+                match squares.iter().position(|square| *square == rank * 8 + file) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            row.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        row.push(pieces[piece]);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push((b'0' + empty) as char);
+            }
+            rows.push(row);
+        }
+        let side: char = match side {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+        format!("{} {} - - 0 1", rows.join("/"), side)
+    }
+
+    /// Place the remaining pieces on every free square, adding the legal positions (with both sides to move).
+    fn place(pieces: &[char], squares: &mut Vec<usize>, boards: &mut Vec<Board>) {
+        if squares.len() == pieces.len() {
+            for side in [Color::White, Color::Black] {
+                let board: Board = Board::from_fen(&fen(pieces, squares, side)).unwrap();
+                let (king, _) = board
+                    .get_pieces(side.opposite())
+                    .iter()
+                    .find(|(_, piece)| **piece == Piece::King)
+                    .unwrap();
+                if !board.is_attacked(king, side) {
+                    boards.push(board);
+                }
+            }
+            return;
+        }
+        let pawn: bool = pieces[squares.len()].eq_ignore_ascii_case(&'p');
+        for square in 0..64 {
+            if squares.contains(&square) || pawn && !(8..56).contains(&square) {
+                continue;
+            }
+            squares.push(square);
+            place(pieces, squares, boards);
+            squares.pop();
+        }
+    }
+
+    /// Get the legal positions of a material signature, with white as the first side.
+    fn positions(code: &str) -> Vec<Board> {
+        let (white, black) = code.split_once('v').unwrap();
+        let pieces: Vec<char> = white
+            .chars()
+            .chain(black.chars().map(|letter| letter.to_ascii_lowercase()))
+            .collect();
+        let mut boards: Vec<Board> = Vec::new();
+        place(&pieces, &mut Vec::new(), &mut boards);
+        boards
+    }
+
+    /// WDL and DTZ of every position of a table.
+    type Results = Vec<(Wdl, i32)>;
+
+    /// Movement of a position, for the distances to zeroing.
+    enum Child {
+        /// A mating movement.
+        Mate,
+        /// A capture or a pawn movement, with its result for the side making it.
+        Zeroing(Wdl),
+        /// Any other movement, leading to a position of the same material.
+        Position(usize),
+    }
+
+    /// Compute the results and the distances to zeroing of the positions of a material signature.
+    ///
+    /// Results are those of the distances to mate. Winning positions are at 1 ply of zeroing with a mating movement
+    /// or a winning capture or pawn movement, and otherwise at 1 ply more than their nearest losing child;
+    /// losing positions at 1 ply more than their farthest child (or 1 ply, when checkmated or only zeroing).
+    /// Positions are resolved by increasing distance, as in a retrograde analysis.
+    fn solve(boards: &mut [Board], tables: &TablebaseSet) -> Results {
+        let wdl_of = |dtm: Dtm| -> Wdl {
+            match dtm {
+                Dtm::Win(_) => Wdl::Win,
+                Dtm::Draw => Wdl::Draw,
+                Dtm::Loss(_) => Wdl::Loss,
+            }
+        };
+        let indices: HashMap<u64, usize> = boards
+            .iter()
+            .enumerate()
+            .map(|(index, board)| (board.hash(), index))
+            .collect();
+        let mut results: Vec<Wdl> = Vec::new();
+        let mut children: Vec<Vec<Child>> = Vec::new();
+        for board in boards.iter_mut() {
+            results.push(wdl_of(tables.probe(board).unwrap()));
+            let mut moves: Vec<Child> = Vec::new();
+            for movement in board.gen_legal_moves() {
+                let zeroing: bool = movement.capture.is_some()
+                    || board.get_square(&movement.from).map(|(piece, _)| piece)
+                        == Some(Piece::Pawn);
+                board.make_move(&movement);
+                moves.push(if board.in_check() && board.gen_legal_moves().is_empty() {
+                    Child::Mate
+                } else if zeroing {
+                    Child::Zeroing(-wdl_of(tables.probe(board).unwrap()))
+                } else {
+                    Child::Position(indices[&board.hash()])
+                });
+                board.unmake_move(&movement);
+            }
+            children.push(moves);
+        }
+        let mut dtz: Vec<Option<i32>> = results
+            .iter()
+            .map(|wdl| (*wdl == Wdl::Draw).then_some(0))
+            .collect();
+        for plies in 1..=100 {
+            for index in 0..boards.len() {
+                if dtz[index].is_some() || results[index] != Wdl::Loss {
+                    continue;
+                }
+                let farthest: Option<i32> =
+                    children[index]
+                        .iter()
+                        .try_fold(1, |farthest, child| match child {
+                            Child::Position(child) => dtz[*child].map(|dtz| farthest.max(1 + dtz)),
+                            _ => Some(farthest),
+                        });
+                dtz[index] = farthest.map(|farthest| -farthest);
+            }
+            for index in 0..boards.len() {
+                if dtz[index].is_some() || results[index] != Wdl::Win {
+                    continue;
+                }
+                let found: bool = children[index].iter().any(|child| match child {
+                    Child::Mate | Child::Zeroing(Wdl::Win) => plies == 1,
+                    Child::Zeroing(_) => false,
+                    Child::Position(child) => plies > 1 && dtz[*child] == Some(1 - plies),
+                });
+                if found {
+                    dtz[index] = Some(plies);
+                }
+            }
+        }
+        // wins beyond the fifty-move rule (cursed) are not expected
+        results
+            .into_iter()
+            .zip(dtz)
+            .map(|(wdl, dtz)| (wdl, dtz.unwrap()))
+            .collect()
+    }
+
+    /// Compressed values of a side to move and a file of the leading pawn: the sizes and Huffman codes,
+    /// the sparse index, the lengths of the blocks and the blocks.
+    #[derive(Default)]
+    struct Section {
+        sizes: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        blocks: Vec<u8>,
+    }
+
+    /// Compress the values of a side to move and a file (unknown values can take any value).
+    ///
+    /// Each symbol stands for a run of 2^j equal values (a pair of runs of 2^(j-1) values, down to a single value),
+    /// and all symbols have codes of the same length.
+    fn compress(values: &[Option<u16>], flags: u8) -> Section {
+        let first: u16 = values.iter().flatten().next().copied().unwrap_or(0);
+        let mut last: u16 = first;
+        let values: Vec<u16> = values
+            .iter()
+            .map(|value| {
+                last = value.unwrap_or(last);
+                last
+            })
+            .collect();
+        if values.iter().all(|value| *value == first) {
+            return Section {
+                sizes: vec![flags | FLAG_SINGLE_VALUE, first as u8],
+                ..Section::default()
+            };
+        }
+        let mut runs: Vec<(u16, u32)> = Vec::new();
+        let mut start: usize = 0;
+        while start < values.len() {
+            let length: usize = values[start..]
+                .iter()
+                .take_while(|value| **value == values[start])
+                .count();
+            let mut rest: usize = length;
+            while rest > 0 {
+                let run: u32 = std::cmp::min(MAX_RUN, rest.ilog2());
+                runs.push((values[start], run));
+                rest -= 1 << run;
+            }
+            start += length;
+        }
+        let mut longest: BTreeMap<u16, u32> = BTreeMap::new();
+        for (value, run) in &runs {
+            let longest: &mut u32 = longest.entry(*value).or_default();
+            *longest = std::cmp::max(*longest, *run);
+        }
+        let symbols: Vec<(u16, u32)> = longest
+            .iter()
+            .flat_map(|(value, longest)| (0..=*longest).map(|run| (*value, run)))
+            .collect();
+        let codes: HashMap<(u16, u32), usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(code, symbol)| (*symbol, code))
+            .collect();
+        let bits: usize = (symbols.len() - 1).ilog2() as usize + 1;
+        // pack the codes of whole runs in blocks, from the most significant bit
+        let block_bits: usize = 8 << BLOCK_SIZE;
+        let mut section: Section = Section::default();
+        let mut lengths: Vec<usize> = Vec::new();
+        let mut block: Vec<u8> = vec![0; 1 << BLOCK_SIZE];
+        let (mut used, mut count): (usize, usize) = (0, 0);
+        for (value, run) in runs {
+            if used + bits > block_bits || count + (1 << run) > BLOCK_VALUES {
+                section.blocks.append(&mut block);
+                block = vec![0; 1 << BLOCK_SIZE];
+                lengths.push(count);
+                (used, count) = (0, 0);
+            }
+            let code: usize = codes[&(value, run)];
+            for bit in 0..bits {
+                let position: usize = used + bit;
+                block[position / 8] |=
+                    (((code >> (bits - 1 - bit)) & 1) as u8) << (7 - position % 8);
+            }
+            used += bits;
+            count += 1 << run;
+        }
+        section.blocks.append(&mut block);
+        lengths.push(count);
+        section.sizes = vec![flags, BLOCK_SIZE, SPAN, 0];
+        section.sizes.extend((lengths.len() as u32).to_le_bytes());
+        // a single code length, whose lowest symbol is the first one
+        section.sizes.extend([bits as u8, bits as u8, 0, 0]);
+        section.sizes.extend((symbols.len() as u16).to_le_bytes());
+        for (value, run) in &symbols {
+            let pair: u32 = match run {
+                0 => *value as u32 | 0xFFF << 12,
+                run => codes[&(*value, run - 1)] as u32 * 0x1001,
+            };
+            section.sizes.extend(&pair.to_le_bytes()[..3]);
+        }
+        if symbols.len() % 2 == 1 {
+            section.sizes.push(0);
+        }
+        // the sparse index points to the block and offset of the value in the middle of each span
+        let mut starts: Vec<usize> = vec![0];
+        for length in &lengths {
+            starts.push(starts.last().unwrap() + length);
+        }
+        for k in 0..values.len().div_ceil(1 << SPAN) {
+            let idx: usize = (k << SPAN) + (1 << (SPAN - 1));
+            let block: usize = starts[..lengths.len()].partition_point(|start| *start <= idx) - 1;
+            section.sparse_index.extend((block as u32).to_le_bytes());
+            section
+                .sparse_index
+                .extend(u16::try_from(idx - starts[block]).unwrap().to_le_bytes());
+        }
+        for length in lengths {
+            section
+                .block_lengths
+                .extend((length as u16 - 1).to_le_bytes());
+        }
+        section
+    }
+
+    /// Write a WDL or DTZ table, with the value of each position (if it is stored), of white to move for DTZ tables.
+    fn write_table(
+        entry: &Entry,
+        dtz: bool,
+        boards: &[Board],
+        value: impl Fn(usize) -> Option<u16>,
+    ) -> Vec<u8> {
+        let files: usize = if entry.has_pawns { 4 } else { 1 };
+        let sides: usize = if !dtz && entry.key != entry.key2 {
+            2
+        } else {
+            1
+        };
+        assert_eq!(entry.pawns[1], 0, "pawns of both colors");
+        // the pieces in the order of the index, with the pawns first, each one for both sides
+        let mut pieces: Vec<u8> = Vec::new();
+        for (color, counts) in [Color::White, Color::Black].iter().zip(entry.key) {
+            for piece in [
+                Piece::Pawn,
+                Piece::Knight,
+                Piece::Bishop,
+                Piece::Rook,
+                Piece::Queen,
+                Piece::King,
+            ] {
+                for _ in 0..counts[piece.index()] {
+                    pieces.push(piece_code(piece, *color));
+                }
+            }
+        }
+        pieces.sort_by_key(|code| *code != piece_code(Piece::Pawn, Color::White));
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(if dtz { DTZ_MAGIC } else { WDL_MAGIC });
+        data.push((entry.key != entry.key2) as u8 | (entry.has_pawns as u8) << 1);
+        for _ in 0..files {
+            // the leading group is the first factor of the index
+            data.push(0);
+            data.extend(pieces.iter().map(|code| code | code << 4));
+        }
+        data.resize(data.len() + data.len() % 2, 0);
+        // read the groups of the index from a table of single values
+        let mut table: Table = Table {
+            data: data.clone(),
+            dtz,
+            items: Default::default(),
+            map: 0,
+        };
+        for _ in 0..files * sides {
+            table.data.extend([FLAG_SINGLE_VALUE, 0]);
+        }
+        table.init(entry).unwrap();
+        let mut values: Vec<Vec<Vec<Option<u16>>>> = (0..sides)
+            .map(|side| {
+                (0..files)
+                    .map(|file| {
+                        let d: &PairsData = &table.items[side][file];
+                        let groups: usize = d.group_len.iter().position(|len| *len == 0).unwrap();
+                        vec![None; d.group_idx[groups] as usize]
+                    })
+                    .collect()
+            })
+            .collect();
+        for (index, board) in boards.iter().enumerate() {
+            let (stm, file, idx) = table.index(entry, board);
+            match value(index) {
+                Some(value) if stm < sides => {
+                    let slot: &mut Option<u16> = &mut values[stm][file][idx as usize];
+                    assert!(slot.is_none() || *slot == Some(value), "{}", board.to_fen());
+                    *slot = Some(value);
+                }
+                _ => (),
+            }
+        }
+        let flags: u8 = match dtz {
+            true => FLAG_WIN_PLIES | FLAG_LOSS_PLIES,
+            false => 0,
+        };
+        let sections: Vec<Section> = (0..files)
+            .flat_map(|file| (0..sides).map(move |side| (file, side)))
+            .map(|(file, side)| compress(&values[side][file], flags))
+            .collect();
+        for section in &sections {
+            data.extend(&section.sizes);
+        }
+        if dtz {
+            data.resize(data.len() + data.len() % 2, 0);
+        }
+        for section in &sections {
+            data.extend(&section.sparse_index);
+        }
+        for section in &sections {
+            data.extend(&section.block_lengths);
+        }
+        for section in &sections {
+            data.resize(data.len().next_multiple_of(64), 0);
+            data.extend(&section.blocks);
+        }
+        data
+    }
+
+    /// Write the test tables, checking them on every position (`cargo test --release --features syzygy -- --ignored`).
+    ///
+    /// The results come from the distances to mate of tables generated by retrograde analysis,
+    /// from which the distances to zeroing are computed. DTZ tables store white to move.
+    #[test]
+    #[ignore]
+    fn write_test_tables() {
+        let mut tables: TablebaseSet = TablebaseSet::new();
+        for code in CODES {
+            tables.generate(&Signature::parse(code).unwrap()).unwrap();
+        }
+        let mut solved: Vec<(Vec<Board>, Results)> = Vec::new();
+        for code in CODES {
+            let mut boards: Vec<Board> = positions(code);
+            let results: Results = solve(&mut boards, &tables);
+            let entry: Entry = Entry::new(code, PathBuf::new(), None).unwrap();
+            let wdl: Vec<u8> = write_table(&entry, false, &boards, |index| {
+                Some((results[index].0.value() + 2) as u16)
+            });
+            let dtz: Vec<u8> = write_table(&entry, true, &boards, |index| match results[index] {
+                (Wdl::Draw, _) => None,
+                (_, dtz) => Some(dtz.unsigned_abs() as u16 - 1),
+            });
+            let path: PathBuf = Path::new(TABLES).join(code);
+            std::fs::write(path.with_extension("rtbw"), wdl).unwrap();
+            std::fs::write(path.with_extension("rtbz"), dtz).unwrap();
+            solved.push((boards, results));
+        }
+        let tablebases: Tablebases = Tablebases::open(TABLES);
+        for (mut boards, results) in solved {
+            for (board, (wdl, dtz)) in boards.iter_mut().zip(results) {
+                assert_eq!(tablebases.probe_wdl(board), Some(wdl), "{}", board.to_fen());
+                assert_eq!(tablebases.probe_dtz(board), Some(dtz), "{}", board.to_fen());
+            }
+        }
+    }
+}
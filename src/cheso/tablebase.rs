@@ -0,0 +1,890 @@
+use crate::cheso::board::Board;
+use crate::cheso::color::Color;
+use crate::cheso::movement::Movement;
+use crate::cheso::piece::Piece;
+use crate::cheso::search::MATE;
+use crate::cheso::square::Square;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Largest number of pieces (kings included) of a table.
+///
+/// Each additional piece multiplies the size of a table (and the time to generate it) by 64:
+/// four-men tables take seconds, five-men ones hours and gigabytes of memory.
+pub const MAX_MEN: usize = 5;
+
+/// Magic number at the start of a table file.
+const MAGIC: &[u8; 4] = b"CTB1";
+
+/// Extension of table files.
+pub const EXTENSION: &str = "ctb";
+
+/// Distance to mate of a position with perfect play, in plies, from the point of view of the side to move.
+///
+/// A checkmated position is a loss in 0 plies, and a position with a mating movement a win in 1 ply.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Dtm {
+    Win(u16),
+    Draw,
+    Loss(u16),
+}
+
+impl Dtm {
+    /// Get the distance from the point of view of the other side, one ply before.
+    pub fn parent(&self) -> Dtm {
+        match self {
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Draw => Dtm::Draw,
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+        }
+    }
+
+    /// Get the number of plies to mate (zero for draws).
+    pub fn plies(&self) -> u16 {
+        match self {
+            Dtm::Win(plies) | Dtm::Loss(plies) => *plies,
+            Dtm::Draw => 0,
+        }
+    }
+
+    /// Get the score of the position for the search, at a ply from the root.
+    pub fn score(&self, ply: usize) -> i32 {
+        match self {
+            Dtm::Win(plies) => MATE - ply as i32 - *plies as i32,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => -MATE + ply as i32 + *plies as i32,
+        }
+    }
+
+    /// Get a value ordering the distances from the worst (the fastest loss) to the best (the fastest win).
+    fn rank(&self) -> i32 {
+        match self {
+            Dtm::Win(plies) => (1 << 16) - *plies as i32,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => -(1 << 16) + *plies as i32,
+        }
+    }
+
+    /// Encode the distance in a byte: 0 for draws, the moves to mate for wins (1 to 127), and 128 plus the moves
+    /// to mate for losses.
+    fn encode(&self) -> u8 {
+        match self {
+            Dtm::Win(plies) => plies.div_ceil(2) as u8,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => 128 + (plies / 2) as u8,
+        }
+    }
+
+    /// Decode a distance from a byte.
+    fn decode(byte: u8) -> Dtm {
+        match byte {
+            0 => Dtm::Draw,
+            1..=127 => Dtm::Win(2 * byte as u16 - 1),
+            _ => Dtm::Loss(2 * (byte - 128) as u16),
+        }
+    }
+}
+
+/// Material signature of a table, like `KRvK`: the pieces of each side, kings first and then by decreasing value.
+///
+/// The canonical signature of a material has the stronger side first (more pieces, or more valuable ones).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Signature {
+    pieces: [Vec<Piece>; 2],
+}
+
+impl std::fmt::Display for Signature {
+    /// Format the signature (e.g. `KBNvK`).
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let side = |pieces: &Vec<Piece>| -> String {
+            pieces
+                .iter()
+                .map(|piece| piece.to_char().to_ascii_uppercase())
+                .collect()
+        };
+        write!(f, "{}v{}", side(&self.pieces[0]), side(&self.pieces[1]))
+    }
+}
+
+impl Signature {
+    /// Make a signature from the pieces of each side (sorted, kings first).
+    fn new(white: Vec<Piece>, black: Vec<Piece>) -> Signature {
+        let mut pieces: [Vec<Piece>; 2] = [white, black];
+        for side in pieces.iter_mut() {
+            side.sort_by_key(|piece| std::cmp::Reverse(piece.index()));
+        }
+        Signature { pieces }
+    }
+
+    /// Parse a signature (e.g. `KRvK`), which must have one king per side and at most `MAX_MEN` pieces.
+    pub fn parse(text: &str) -> Result<Signature, String> {
+        let (white, black) = text
+            .split_once('v')
+            .ok_or(format!("invalid material signature '{}'", text))?;
+        let mut sides: Vec<Vec<Piece>> = Vec::new();
+        for side in [white, black] {
+            let pieces: Vec<Piece> = side
+                .chars()
+                .map(Piece::from_char)
+                .collect::<Option<Vec<Piece>>>()
+                .ok_or(format!("invalid material signature '{}'", text))?;
+            if pieces.iter().filter(|piece| **piece == Piece::King).count() != 1 {
+                return Err(format!("each side must have one king in '{}'", text));
+            }
+            sides.push(pieces);
+        }
+        let black: Vec<Piece> = sides.pop().unwrap_or_default();
+        let white: Vec<Piece> = sides.pop().unwrap_or_default();
+        let signature: Signature = Signature::new(white, black);
+        match signature.men() {
+            men if men > MAX_MEN => Err(format!(
+                "too many pieces in '{}' (at most {})",
+                text, MAX_MEN
+            )),
+            _ => Ok(signature),
+        }
+    }
+
+    /// Get the signature of the material of a position.
+    pub fn of(board: &Board) -> Signature {
+        Signature::new(
+            board.white_pieces.values().copied().collect(),
+            board.black_pieces.values().copied().collect(),
+        )
+    }
+
+    /// Get the total number of pieces.
+    pub fn men(&self) -> usize {
+        self.pieces[0].len() + self.pieces[1].len()
+    }
+
+    /// Check if there are pawns.
+    fn has_pawns(&self) -> bool {
+        self.pieces
+            .iter()
+            .flatten()
+            .any(|piece| *piece == Piece::Pawn)
+    }
+
+    /// Get the signature with the colors swapped.
+    fn swapped(&self) -> Signature {
+        Signature {
+            pieces: [self.pieces[1].clone(), self.pieces[0].clone()],
+        }
+    }
+
+    /// Get the canonical signature, and whether the colors were swapped to get it.
+    pub fn canonical(&self) -> (Signature, bool) {
+        let strength = |pieces: &Vec<Piece>| -> (usize, i32, Vec<usize>) {
+            (
+                pieces.len(),
+                pieces.iter().map(Piece::value).sum(),
+                pieces.iter().map(Piece::index).collect(),
+            )
+        };
+        match strength(&self.pieces[1]) > strength(&self.pieces[0]) {
+            true => (self.swapped(), true),
+            false => (self.clone(), false),
+        }
+    }
+
+    /// Get the canonical signatures reachable by a capture or a promotion.
+    fn successors(&self) -> Vec<Signature> {
+        let mut successors: Vec<Signature> = Vec::new();
+        for side in 0..2 {
+            for (i, piece) in self.pieces[side].iter().enumerate() {
+                let mut changes: Vec<Option<Piece>> = Vec::new();
+                match piece {
+                    Piece::King => continue,
+                    Piece::Pawn => changes.extend(
+                        [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight].map(Some),
+                    ),
+                    _ => (),
+                }
+                changes.push(None);
+                for change in changes {
+                    let mut pieces: [Vec<Piece>; 2] = self.pieces.clone();
+                    match change {
+                        Some(promotion) => pieces[side][i] = promotion,
+                        None => {
+                            pieces[side].remove(i);
+                        }
+                    }
+                    let [white, black] = pieces;
+                    let (signature, _) = Signature::new(white, black).canonical();
+                    if signature.men() > 2 && !successors.contains(&signature) {
+                        successors.push(signature);
+                    }
+                }
+            }
+        }
+        successors
+    }
+}
+
+/// Apply a symmetry to a square index: bit 0 mirrors the files, bit 1 the ranks, and bit 2 swaps them.
+fn transform(square: usize, symmetry: usize) -> usize {
+    let mut square: usize = square;
+    if symmetry & 1 != 0 {
+        square ^= 7;
+    }
+    if symmetry & 2 != 0 {
+        square ^= 56;
+    }
+    if symmetry & 4 != 0 {
+        square = ((square >> 3) | (square << 3)) & 63;
+    }
+    square
+}
+
+/// Indexing of the positions of a signature.
+///
+/// Pieces are placed in slots (the white king first, then the other white pieces, the black king and the other
+/// black pieces). The white king is brought by symmetry to the a1-d1-d4 triangle (without pawns) or to the files a
+/// to d (with pawns), so the index is made of the side to move, the square of the white king in that region, and
+/// the squares of the other pieces. Among equivalent placements (identical pieces, or symmetric positions when the
+/// white king is on the diagonal), only the one with the lowest index is used.
+#[derive(Clone, Debug)]
+struct Layout {
+    slots: Vec<(Piece, Color)>,
+    has_pawns: bool,
+    king_squares: Vec<usize>,
+    king_index: [usize; 64],
+    size: usize,
+}
+
+impl Layout {
+    fn new(signature: &Signature) -> Layout {
+        let mut slots: Vec<(Piece, Color)> = Vec::new();
+        for (side, color) in [Color::White, Color::Black].iter().enumerate() {
+            slots.extend(signature.pieces[side].iter().map(|piece| (*piece, *color)));
+        }
+        let has_pawns: bool = signature.has_pawns();
+        let king_squares: Vec<usize> = (0..64)
+            .filter(|square| match has_pawns {
+                true => square % 8 <= 3,
+                false => square % 8 <= 3 && square / 8 <= square % 8,
+            })
+            .collect();
+        let mut king_index: [usize; 64] = [0; 64];
+        for (index, square) in king_squares.iter().enumerate() {
+            king_index[*square] = index;
+        }
+        let size: usize = 2 * king_squares.len() * 64usize.pow(slots.len() as u32 - 1);
+        Layout {
+            slots,
+            has_pawns,
+            king_squares,
+            king_index,
+            size,
+        }
+    }
+
+    /// Get the index of a position with the material of the layout (with the colors swapped or not).
+    fn board_index(&self, board: &Board, swapped: bool) -> usize {
+        // pieces in the order of the slots (kings first, then by decreasing value)
+        let mut squares: Vec<usize> = Vec::new();
+        for color in [Color::White, Color::Black] {
+            let color: Color = if swapped { color.opposite() } else { color };
+            let mut pieces: Vec<(Piece, usize)> = board
+                .get_pieces(color)
+                .iter()
+                .map(|(square, piece)| (*piece, square.index() ^ if swapped { 56 } else { 0 }))
+                .collect();
+            pieces.sort_by_key(|(piece, square)| (std::cmp::Reverse(piece.index()), *square));
+            squares.extend(pieces.iter().map(|(_, square)| *square));
+        }
+        let side_to_move: Color = match swapped {
+            true => board.side_to_move.opposite(),
+            false => board.side_to_move,
+        };
+        self.index(&squares, side_to_move)
+    }
+
+    /// Get the symmetries bringing the white king to its region.
+    fn symmetries(&self, king: usize) -> Vec<usize> {
+        let mut symmetry: usize = if king % 8 > 3 { 1 } else { 0 };
+        if self.has_pawns {
+            return vec![symmetry];
+        }
+        if king / 8 > 3 {
+            symmetry |= 2;
+        }
+        let square: usize = transform(king, symmetry);
+        if square / 8 > square % 8 {
+            symmetry |= 4;
+        }
+        match square / 8 == square % 8 {
+            true => vec![symmetry, symmetry ^ 4],
+            false => vec![symmetry],
+        }
+    }
+
+    /// Get the index of a placement (the squares of the slots) with a side to move.
+    fn index(&self, squares: &[usize], side_to_move: Color) -> usize {
+        self.symmetries(squares[0])
+            .iter()
+            .map(|symmetry| {
+                let mut squares: Vec<usize> = squares
+                    .iter()
+                    .map(|square| transform(*square, *symmetry))
+                    .collect();
+                // identical pieces in increasing square order
+                let mut start: usize = 0;
+                while start < squares.len() {
+                    let end: usize = (start..squares.len())
+                        .find(|i| self.slots[*i] != self.slots[start])
+                        .unwrap_or(squares.len());
+                    squares[start..end].sort();
+                    start = end;
+                }
+                let mut index: usize = if side_to_move == Color::White { 0 } else { 1 };
+                index = index * self.king_squares.len() + self.king_index[squares[0]];
+                squares[1..]
+                    .iter()
+                    .fold(index, |index, square| index * 64 + square)
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Get the placement and side to move of an index, if it is the index used for its position.
+    fn placement(&self, index: usize) -> Option<(Vec<usize>, Color)> {
+        let mut rest: usize = index;
+        let mut squares: Vec<usize> = vec![0; self.slots.len()];
+        for square in squares[1..].iter_mut().rev() {
+            *square = rest % 64;
+            rest /= 64;
+        }
+        squares[0] = self.king_squares[rest % self.king_squares.len()];
+        let side_to_move: Color = match rest / self.king_squares.len() {
+            0 => Color::White,
+            _ => Color::Black,
+        };
+        let mut occupied: u64 = 0;
+        for (square, (piece, _)) in squares.iter().zip(self.slots.iter()) {
+            if occupied & 1 << square != 0 || *piece == Piece::Pawn && matches!(square / 8, 0 | 7) {
+                return None;
+            }
+            occupied |= 1 << square;
+        }
+        match self.index(&squares, side_to_move) == index {
+            true => Some((squares, side_to_move)),
+            false => None,
+        }
+    }
+
+    /// Make the board of a placement.
+    fn board(&self, squares: &[usize], side_to_move: Color) -> Board {
+        let pieces: Vec<(Piece, Color, Square)> = self
+            .slots
+            .iter()
+            .zip(squares.iter())
+            .map(|((piece, color), square)| (*piece, *color, Square::from_index(*square)))
+            .collect();
+        Board::from_pieces(&pieces, side_to_move)
+    }
+
+    /// Get the placements of the positions from which a movement (neither a capture nor a promotion) leads to a
+    /// placement, with the other side to move.
+    fn predecessors(&self, squares: &[usize], side_to_move: Color) -> Vec<Vec<usize>> {
+        const KING: [(isize, isize); 8] = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+        const KNIGHT: [(isize, isize); 8] = [
+            (-2, -1),
+            (-2, 1),
+            (-1, -2),
+            (-1, 2),
+            (1, -2),
+            (1, 2),
+            (2, -1),
+            (2, 1),
+        ];
+        let occupied: u64 = squares
+            .iter()
+            .fold(0, |occupied, square| occupied | 1 << square);
+        let color: Color = side_to_move.opposite();
+        let mut predecessors: Vec<Vec<usize>> = Vec::new();
+        for (slot, (piece, _)) in self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, slot_color))| *slot_color == color)
+        {
+            let square: usize = squares[slot];
+            let (rank, file) = ((square / 8) as isize, (square % 8) as isize);
+            let mut origins: Vec<usize> = Vec::new();
+            let mut step = |rank: isize, file: isize| -> bool {
+                if !(0..8).contains(&rank) || !(0..8).contains(&file) {
+                    return false;
+                }
+                let origin: usize = (rank * 8 + file) as usize;
+                if occupied & 1 << origin != 0 {
+                    return false;
+                }
+                origins.push(origin);
+                true
+            };
+            match piece {
+                Piece::King => KING.iter().for_each(|(r, f)| {
+                    step(rank + r, file + f);
+                }),
+                Piece::Knight => KNIGHT.iter().for_each(|(r, f)| {
+                    step(rank + r, file + f);
+                }),
+                Piece::Pawn => {
+                    // pawns move backwards, one square or two from their fourth rank
+                    let (back, start) = match color {
+                        Color::White => (-1, 1),
+                        Color::Black => (1, 6),
+                    };
+                    let origin: isize = rank + back;
+                    if (1..=6).contains(&origin) && step(origin, file) && origin + back == start {
+                        step(origin + back, file);
+                    }
+                }
+                _ => {
+                    let straight: bool = matches!(piece, Piece::Rook | Piece::Queen);
+                    let diagonal: bool = matches!(piece, Piece::Bishop | Piece::Queen);
+                    for (r, f) in KING {
+                        if r != 0 && f != 0 && !diagonal || (r == 0 || f == 0) && !straight {
+                            continue;
+                        }
+                        let mut distance: isize = 1;
+                        while step(rank + r * distance, file + f * distance) {
+                            distance += 1;
+                        }
+                    }
+                }
+            }
+            for origin in origins {
+                let mut predecessor: Vec<usize> = squares.to_vec();
+                predecessor[slot] = origin;
+                predecessors.push(predecessor);
+            }
+        }
+        predecessors
+    }
+}
+
+/// Endgame table of a material signature, with the distance to mate of every position.
+///
+/// Positions with castling rights or en passant are not distinguished (they are treated as without them).
+#[derive(Clone, Debug)]
+pub struct Tablebase {
+    signature: Signature,
+    layout: Layout,
+    values: Vec<u8>,
+}
+
+impl Tablebase {
+    /// Get the signature of the table.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Get the number of indices of the table (including invalid or equivalent placements).
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the table has no indices.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get the index of a position, if it has the material of the table (with any colors).
+    fn index(&self, board: &Board) -> Option<usize> {
+        let swapped: bool = match Signature::of(board) {
+            signature if signature == self.signature => false,
+            signature if signature.swapped() == self.signature => true,
+            _ => return None,
+        };
+        Some(self.layout.board_index(board, swapped))
+    }
+
+    /// Get the distance to mate of a position, if it has the material of the table (with any colors).
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        Some(Dtm::decode(self.values[self.index(board)?]))
+    }
+
+    /// Read a table from a file.
+    pub fn read(path: &Path) -> Result<Tablebase, String> {
+        let data: Vec<u8> = std::fs::read(path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        let invalid = || format!("invalid tablebase file {}", path.display());
+        if data.len() < 5 || &data[..4] != MAGIC {
+            return Err(invalid());
+        }
+        let length: usize = data[4] as usize;
+        let text: &str = data
+            .get(5..5 + length)
+            .and_then(|text| std::str::from_utf8(text).ok())
+            .ok_or_else(invalid)?;
+        let signature: Signature = Signature::parse(text)?;
+        let layout: Layout = Layout::new(&signature);
+        if data.len() != 5 + length + layout.size {
+            return Err(invalid());
+        }
+        Ok(Tablebase {
+            signature,
+            layout,
+            values: data[5 + length..].to_vec(),
+        })
+    }
+
+    /// Write the table to a file: a magic number, the signature (preceded by its length) and a byte per index.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let signature: String = self.signature.to_string();
+        let mut data: Vec<u8> = Vec::with_capacity(5 + signature.len() + self.values.len());
+        data.extend_from_slice(MAGIC);
+        data.push(signature.len() as u8);
+        data.extend_from_slice(signature.as_bytes());
+        data.extend_from_slice(&self.values);
+        std::fs::write(path, data)
+            .map_err(|error| format!("cannot write {}: {}", path.display(), error))
+    }
+}
+
+/// Set of endgame tables, generated by retrograde analysis or read from files.
+#[derive(Clone, Debug, Default)]
+pub struct TablebaseSet {
+    tables: HashMap<Signature, Tablebase>,
+}
+
+impl TablebaseSet {
+    /// Make an empty set.
+    pub fn new() -> TablebaseSet {
+        TablebaseSet::default()
+    }
+
+    /// Read all the table files of a directory.
+    pub fn open(directory: &Path) -> Result<TablebaseSet, String> {
+        let mut set: TablebaseSet = TablebaseSet::new();
+        let entries = std::fs::read_dir(directory)
+            .map_err(|error| format!("cannot read {}: {}", directory.display(), error))?;
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                set.insert(Tablebase::read(&path)?);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Write all the tables to a directory, one file per signature (e.g. `KRvK.ctb`).
+    pub fn write(&self, directory: &Path) -> Result<(), String> {
+        for table in self.tables.values() {
+            table.write(&directory.join(format!("{}.{}", table.signature, EXTENSION)))?;
+        }
+        Ok(())
+    }
+
+    /// Add a table to the set.
+    pub fn insert(&mut self, table: Tablebase) {
+        self.tables.insert(table.signature.clone(), table);
+    }
+
+    /// Get the table of a signature (in any order of the colors).
+    pub fn get(&self, signature: &Signature) -> Option<&Tablebase> {
+        self.tables.get(&signature.canonical().0)
+    }
+
+    /// Get the tables of the set.
+    pub fn tables(&self) -> impl Iterator<Item = &Tablebase> {
+        self.tables.values()
+    }
+
+    /// Get the distance to mate of a position, if its table is in the set (bare kings are always a draw).
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        let signature: Signature = Signature::of(board);
+        if signature.men() == 2 {
+            return Some(Dtm::Draw);
+        }
+        self.get(&signature)?.probe(board)
+    }
+
+    /// Generate the table of a signature, and the tables reachable from it by captures and promotions.
+    pub fn generate(&mut self, signature: &Signature) -> Result<(), String> {
+        let (signature, _) = signature.canonical();
+        if self.tables.contains_key(&signature) || signature.men() <= 2 {
+            return Ok(());
+        }
+        for successor in signature.successors() {
+            self.generate(&successor)?;
+        }
+        let table: Tablebase = self.retrograde(signature)?;
+        self.insert(table);
+        Ok(())
+    }
+
+    /// Compute the table of a signature by retrograde analysis (the tables of its successors must be in the set).
+    ///
+    /// Each position counts its movements staying in the table, and gets the best result of its captures and
+    /// promotions from the other tables. Starting from checkmates, positions are then resolved by increasing
+    /// distance: a predecessor of a loss in n plies is a win in n + 1, and a position whose movements all lead to
+    /// wins of the opponent (the last one found in n plies) is a loss in n + 1 (or later, through a capture).
+    /// Positions never resolved are draws.
+    fn retrograde(&self, signature: Signature) -> Result<Tablebase, String> {
+        let layout: Layout = Layout::new(&signature);
+        let mut values: Vec<Option<Dtm>> = vec![None; layout.size];
+        let mut legal: Vec<bool> = vec![false; layout.size];
+        let mut remaining: Vec<u8> = vec![0; layout.size];
+        let mut exits: Vec<Option<Dtm>> = vec![None; layout.size];
+        // positions resolved with a distance, and wins through a capture or a promotion, by distance
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        let mut exit_wins: Vec<Vec<usize>> = Vec::new();
+        let push = |levels: &mut Vec<Vec<usize>>, plies: u16, index: usize| {
+            if levels.len() <= plies as usize {
+                levels.resize(plies as usize + 1, Vec::new());
+            }
+            levels[plies as usize].push(index);
+        };
+        for index in 0..layout.size {
+            let (squares, side_to_move) = match layout.placement(index) {
+                Some(placement) => placement,
+                None => continue,
+            };
+            let mut board: Board = layout.board(&squares, side_to_move);
+            if board.is_in_check(side_to_move.opposite()) {
+                continue;
+            }
+            legal[index] = true;
+            let moves: Vec<Movement> = board.gen_legal_moves();
+            if moves.is_empty() {
+                values[index] = Some(Dtm::Draw);
+                if board.in_check() {
+                    values[index] = Some(Dtm::Loss(0));
+                    push(&mut levels, 0, index);
+                }
+                continue;
+            }
+            // movements staying in the table are counted once per resulting position (symmetric ones are the same)
+            let mut exit: Option<Dtm> = None;
+            let mut children: Vec<usize> = Vec::new();
+            for movement in moves.iter() {
+                board.make_move(movement);
+                if movement.capture.is_none() && movement.promotion.is_none() {
+                    children.push(layout.board_index(&board, false));
+                    board.unmake_move(movement);
+                    continue;
+                }
+                let value: Option<Dtm> = self.probe(&board);
+                board.unmake_move(movement);
+                let value: Dtm = value
+                    .ok_or(format!("missing table for {}", Signature::of(&board)))?
+                    .parent();
+                if exit.is_none_or(|exit| value.rank() > exit.rank()) {
+                    exit = Some(value);
+                }
+            }
+            children.sort();
+            children.dedup();
+            remaining[index] = children.len() as u8;
+            exits[index] = exit;
+            match (exit, remaining[index]) {
+                (Some(Dtm::Win(plies)), _) => push(&mut exit_wins, plies, index),
+                (Some(Dtm::Draw), 0) => values[index] = Some(Dtm::Draw),
+                (Some(value), 0) => {
+                    values[index] = Some(value);
+                    push(&mut levels, value.plies(), index);
+                }
+                _ => (),
+            }
+        }
+        let mut plies: usize = 0;
+        while plies < std::cmp::max(levels.len(), exit_wins.len()) {
+            for index in exit_wins
+                .get_mut(plies)
+                .map(std::mem::take)
+                .unwrap_or_default()
+            {
+                if values[index].is_none() {
+                    values[index] = Some(Dtm::Win(plies as u16));
+                    push(&mut levels, plies as u16, index);
+                }
+            }
+            let level: Vec<usize> = levels
+                .get_mut(plies)
+                .map(std::mem::take)
+                .unwrap_or_default();
+            for index in level {
+                let value: Dtm = match values[index] {
+                    Some(Dtm::Draw) | None => continue,
+                    Some(value) => value,
+                };
+                let (squares, side_to_move) = match layout.placement(index) {
+                    Some(placement) => placement,
+                    None => continue,
+                };
+                let mut parents: Vec<usize> = layout
+                    .predecessors(&squares, side_to_move)
+                    .iter()
+                    .map(|predecessor| layout.index(predecessor, side_to_move.opposite()))
+                    .collect();
+                parents.sort();
+                parents.dedup();
+                for parent in parents {
+                    if !legal[parent] || values[parent].is_some() {
+                        continue;
+                    }
+                    match value {
+                        Dtm::Loss(_) => {
+                            values[parent] = Some(value.parent());
+                            push(&mut levels, value.parent().plies(), parent);
+                        }
+                        _ => {
+                            remaining[parent] -= 1;
+                            if remaining[parent] > 0 {
+                                continue;
+                            }
+                            let loss: Dtm = match exits[parent] {
+                                Some(Dtm::Loss(exit)) => {
+                                    Dtm::Loss(std::cmp::max(exit, value.parent().plies()))
+                                }
+                                None => value.parent(),
+                                Some(_) => continue,
+                            };
+                            values[parent] = Some(loss);
+                            push(&mut levels, loss.plies(), parent);
+                        }
+                    }
+                }
+            }
+            plies += 1;
+        }
+        let mut encoded: Vec<u8> = Vec::with_capacity(layout.size);
+        for value in values {
+            let value: Dtm = value.unwrap_or(Dtm::Draw);
+            if value.plies() > 254 {
+                return Err(format!(
+                    "distance to mate beyond 127 movements in {}",
+                    signature
+                ));
+            }
+            encoded.push(value.encode());
+        }
+        Ok(Tablebase {
+            signature,
+            layout,
+            values: encoded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Probe a position in a set of tables.
+    fn probe(tables: &TablebaseSet, fen: &str) -> Option<Dtm> {
+        tables.probe(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn krk_distances_to_mate() {
+        let signature: Signature = Signature::parse("KRvK").unwrap();
+        let mut tables: TablebaseSet = TablebaseSet::new();
+        tables.generate(&signature).unwrap();
+        // mate in one, checkmated, and the rook captured
+        assert_eq!(
+            probe(&tables, "k7/8/1K6/8/8/8/8/7R w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            probe(&tables, "R6k/8/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        assert_eq!(
+            probe(&tables, "k7/1R6/8/8/8/8/8/7K b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // colors are swapped for the table of the black rook
+        assert_eq!(
+            probe(&tables, "K7/8/1k6/8/8/8/8/7r b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        // the longest mate takes 16 movements
+        let table: &Tablebase = tables.get(&signature).unwrap();
+        let longest: u16 = table
+            .values
+            .iter()
+            .map(|value| match Dtm::decode(*value) {
+                Dtm::Win(plies) => plies,
+                _ => 0,
+            })
+            .max()
+            .unwrap();
+        assert_eq!(longest, 31);
+        assert_eq!(
+            probe(&tables, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        assert_eq!(probe(&tables, "4k3/8/8/8/8/8/8/Q3K3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn kpk_distances_to_mate() {
+        let mut tables: TablebaseSet = TablebaseSet::new();
+        tables.generate(&Signature::parse("KPvK").unwrap()).unwrap();
+        // the tables of the promotions are generated first
+        for code in ["KQvK", "KRvK", "KBvK", "KNvK"] {
+            assert!(tables.get(&Signature::parse(code).unwrap()).is_some());
+        }
+        // the king in front of its pawn wins (with any colors), the rook pawn cannot drive the king out of the corner
+        assert_eq!(
+            probe(&tables, "8/4k3/8/3K4/4P3/8/8/8 w - - 0 1"),
+            Some(Dtm::Win(29))
+        );
+        assert_eq!(
+            probe(&tables, "8/8/8/4p3/3k4/8/4K3/8 b - - 0 1"),
+            Some(Dtm::Win(29))
+        );
+        assert_eq!(
+            probe(&tables, "7k/8/8/8/8/8/7P/7K w - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // promoting to a queen mates in two movements
+        assert_eq!(
+            probe(&tables, "k7/2K1P3/8/8/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Win(3))
+        );
+    }
+
+    #[test]
+    fn tables_are_written_and_read_back() {
+        let mut tables: TablebaseSet = TablebaseSet::new();
+        tables.generate(&Signature::parse("KRvK").unwrap()).unwrap();
+        let directory: std::path::PathBuf =
+            std::env::temp_dir().join(format!("cheso-tablebase-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        tables.write(&directory).unwrap();
+        let read: Result<TablebaseSet, String> = TablebaseSet::open(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let read: TablebaseSet = read.unwrap();
+        let signature: Signature = Signature::parse("KRvK").unwrap();
+        assert_eq!(
+            read.get(&signature).unwrap().values,
+            tables.get(&signature).unwrap().values
+        );
+        assert_eq!(
+            probe(&read, "k7/8/1K6/8/8/8/8/7R w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            probe(&read, "K7/8/1k6/8/8/8/8/7r b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert!(Tablebase::read(&directory.join("KRvK.ctb")).is_err());
+    }
+}
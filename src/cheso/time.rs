@@ -0,0 +1,182 @@
+use crate::cheso::color::Color;
+use crate::cheso::search::Limits;
+use std::time::{Duration, Instant};
+
+/// Number of movements assumed to remain in the game when the time control does not tell it.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Maximum factor applied to the soft deadline when the search is unstable.
+const MAX_INSTABILITY: f64 = 2.5;
+
+/// Time manager.
+///
+/// Turns the clock of the side to move into two deadlines: a soft deadline, after which no new iteration is started,
+/// and a hard deadline, after which the search stops immediately.
+/// The soft deadline is extended while the search is unstable (the best movement changes or the score drops)
+/// and shrinks back as the search stabilizes, but it never goes beyond the hard deadline.
+pub struct TimeManager {
+    start: Instant,
+    soft: Duration,
+    hard: Duration,
+    instability: f64,
+}
+
+impl TimeManager {
+    /// Make a time manager for the side to move, if the limits include a clock or a fixed time per movement.
+    ///
+    /// The move overhead is subtracted from the available time, to account for communication delays with the GUI.
+    pub fn new(limits: &Limits, color: Color, overhead: Duration) -> Option<TimeManager> {
+        let start: Instant = Instant::now();
+        if let Some(movetime) = limits.movetime {
+            let time: Duration = movetime.saturating_sub(overhead);
+            return Some(TimeManager {
+                start,
+                soft: time,
+                hard: time,
+                instability: 1.0,
+            });
+        }
+        let (time, increment) = match color {
+            Color::White => (limits.wtime?, limits.winc.unwrap_or_default()),
+            Color::Black => (limits.btime?, limits.binc.unwrap_or_default()),
+        };
+        let remaining: Duration = time.saturating_sub(overhead);
+        let moves: u32 = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+        let hard: Duration = remaining.mul_f64(if moves == 1 { 0.9 } else { 0.5 });
+        let soft: Duration = std::cmp::min(remaining / moves + increment.mul_f64(0.75), hard);
+        Some(TimeManager {
+            start,
+            soft: soft.mul_f64(0.6),
+            hard: std::cmp::min(soft * 4, hard),
+            instability: 1.0,
+        })
+    }
+
+    /// Get the elapsed time since the search started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Update the soft deadline after an iteration.
+    ///
+    /// The deadline is extended when the best movement changed or the score dropped with respect to the previous
+    /// iteration, and it slowly returns to its original value otherwise.
+    pub fn update(&mut self, best_move_changed: bool, score_drop: i32) {
+        if best_move_changed {
+            self.instability *= 1.5;
+        }
+        if score_drop > 25 {
+            self.instability *= 1.0 + (std::cmp::min(score_drop, 200) as f64) / 200.0;
+        }
+        if !best_move_changed && score_drop <= 25 {
+            self.instability *= 0.9;
+        }
+        self.instability = self.instability.clamp(1.0, MAX_INSTABILITY);
+    }
+
+    /// Check if the soft deadline has been reached (so no new iteration must be started).
+    pub fn soft_limit_reached(&self) -> bool {
+        self.elapsed() >= std::cmp::min(self.soft.mul_f64(self.instability), self.hard)
+    }
+
+    /// Check if the hard deadline has been reached (so the search must stop immediately).
+    pub fn hard_limit_reached(&self) -> bool {
+        self.elapsed() >= self.hard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make a time manager for white with a clock, an increment and an optional number of movements to go.
+    fn clock(time: u64, increment: u64, movestogo: Option<u32>, overhead: u64) -> TimeManager {
+        let limits: Limits = Limits {
+            wtime: Some(Duration::from_millis(time)),
+            winc: Some(Duration::from_millis(increment)),
+            movestogo,
+            ..Limits::default()
+        };
+        TimeManager::new(&limits, Color::White, Duration::from_millis(overhead)).unwrap()
+    }
+
+    /// Get the soft and hard deadlines in milliseconds.
+    fn deadlines(time: &TimeManager) -> (u128, u128) {
+        (
+            std::cmp::min(time.soft.mul_f64(time.instability), time.hard).as_millis(),
+            time.hard.as_millis(),
+        )
+    }
+
+    #[test]
+    fn limits_without_clock() {
+        let limits: Limits = Limits {
+            depth: Some(10),
+            ..Limits::default()
+        };
+        assert!(TimeManager::new(&limits, Color::White, Duration::ZERO).is_none());
+        let limits: Limits = Limits {
+            wtime: Some(Duration::from_secs(60)),
+            ..Limits::default()
+        };
+        assert!(TimeManager::new(&limits, Color::Black, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn fixed_time_per_movement() {
+        let limits: Limits = Limits {
+            movetime: Some(Duration::from_millis(1000)),
+            ..Limits::default()
+        };
+        let time: TimeManager =
+            TimeManager::new(&limits, Color::Black, Duration::from_millis(30)).unwrap();
+        assert_eq!(deadlines(&time), (970, 970));
+        let time: TimeManager =
+            TimeManager::new(&limits, Color::Black, Duration::from_millis(5000)).unwrap();
+        assert_eq!(deadlines(&time), (0, 0));
+        assert!(time.soft_limit_reached());
+        assert!(time.hard_limit_reached());
+    }
+
+    #[test]
+    fn clock_deadlines() {
+        // a thirtieth of the clock, and four times that as the hard deadline
+        assert_eq!(deadlines(&clock(60000, 0, None, 0)), (1200, 8000));
+        // the move overhead is taken from the clock
+        assert_eq!(deadlines(&clock(60030, 0, None, 30)), (1200, 8000));
+        // three quarters of the increment are spent on each movement
+        assert_eq!(deadlines(&clock(60000, 1000, None, 0)), (1650, 11000));
+        let time: TimeManager = clock(60000, 0, None, 0);
+        assert!(!time.soft_limit_reached());
+        assert!(!time.hard_limit_reached());
+    }
+
+    #[test]
+    fn movements_to_go() {
+        assert_eq!(deadlines(&clock(10000, 0, Some(10), 0)), (600, 4000));
+        // the last movement before the time control can use most of the clock, but never all of it
+        assert_eq!(deadlines(&clock(10000, 0, Some(1), 0)), (5400, 9000));
+        assert_eq!(deadlines(&clock(10000, 0, Some(0), 0)), (5400, 9000));
+        // the hard deadline never takes more than half of the clock otherwise
+        assert_eq!(deadlines(&clock(10000, 5000, Some(2), 0)), (3000, 5000));
+    }
+
+    #[test]
+    fn instability_extends_the_soft_deadline() {
+        let mut time: TimeManager = clock(60000, 0, None, 0);
+        time.update(true, 0);
+        assert_eq!(deadlines(&time), (1800, 8000));
+        time.update(false, 100);
+        assert_eq!(deadlines(&time), (2700, 8000));
+        // the extension is bounded
+        time.update(true, 200);
+        assert_eq!(deadlines(&time), (3000, 8000));
+        // and it shrinks back as the search stabilizes, but not below the original deadline
+        time.update(false, 0);
+        assert_eq!(deadlines(&time), (2700, 8000));
+        for _ in 0..20 {
+            time.update(false, 10);
+        }
+        assert_eq!(deadlines(&time), (1200, 8000));
+    }
+}
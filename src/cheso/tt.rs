@@ -0,0 +1,161 @@
+use crate::cheso::movement::Movement;
+use crate::cheso::piece::Piece;
+use crate::cheso::square::Square;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Bound of a stored score.
+///
+/// A search can return the exact score of a position, or only a bound of it when a cutoff happens.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Entry of the transposition table.
+///
+/// Each entry stores the result of searching a position: its hash, the searched depth, the score and its bound,
+/// and the best movement found (if any).
+#[derive(Copy, Clone, Debug)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub movement: Option<Movement>,
+}
+
+/// Pieces indexed by their packed code (0 is reserved for no piece).
+static PACKED_PIECES: &[Option<Piece>] = &[
+    None,
+    Some(Piece::Pawn),
+    Some(Piece::Knight),
+    Some(Piece::Bishop),
+    Some(Piece::Rook),
+    Some(Piece::Queen),
+    Some(Piece::King),
+    None,
+];
+
+/// Pack an optional piece in three bits.
+fn pack_piece(piece: Option<Piece>) -> u64 {
+    piece.map_or(0, |piece| piece.index() as u64 + 1)
+}
+
+impl Entry {
+    /// Pack the data of the entry (everything but the hash) in a single word.
+    ///
+    /// Bits are used as follows: score (16), depth (8), bound (2), movement flag (1), origin (6),
+    /// destination (6), promotion (3) and capture (3).
+    fn pack(&self) -> u64 {
+        let bound: u64 = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let mut data: u64 = (self.score as i16 as u16 as u64)
+            | ((self.depth.clamp(-128, 127) as i8 as u8 as u64) << 16)
+            | (bound << 24);
+        if let Some(movement) = self.movement {
+            data |= (1 << 26)
+                | ((movement.from.index() as u64) << 27)
+                | ((movement.to.index() as u64) << 33)
+                | (pack_piece(movement.promotion) << 39)
+                | (pack_piece(movement.capture) << 42);
+        }
+        data
+    }
+
+    /// Unpack the data of an entry from a single word.
+    fn unpack(hash: u64, data: u64) -> Entry {
+        let movement: Option<Movement> = match (data >> 26) & 1 {
+            0 => None,
+            _ => Some(Movement {
+                from: Square::from_index(((data >> 27) & 63) as usize),
+                to: Square::from_index(((data >> 33) & 63) as usize),
+                promotion: PACKED_PIECES[((data >> 39) & 7) as usize],
+                capture: PACKED_PIECES[((data >> 42) & 7) as usize],
+            }),
+        };
+        Entry {
+            hash,
+            depth: (data >> 16) as u8 as i8 as i32,
+            score: data as u16 as i16 as i32,
+            bound: match (data >> 24) & 3 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            movement,
+        }
+    }
+}
+
+/// Transposition table.
+///
+/// A hash table of fixed size where search results are stored, indexed by the Zobrist hash of the position.
+/// A slot is always overwritten by a different position, but a position keeps its deepest search.
+///
+/// The table can be shared by several threads without locks: each slot stores the packed data of the entry
+/// and its hash xored with the data, so an entry torn by concurrent writes does not verify and is ignored.
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    /// Make a new transposition table with (approximately) the given size in megabytes.
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let size: usize = std::cmp::max(
+            1,
+            megabytes * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>(),
+        );
+        TranspositionTable {
+            entries: (0..size)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    /// Get the slot of a hash.
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+
+    /// Get the entry stored for a hash, if any.
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot: &[AtomicU64; 2] = &self.entries[self.index(hash)];
+        let key: u64 = slot[0].load(Ordering::Relaxed);
+        let data: u64 = slot[1].load(Ordering::Relaxed);
+        if key ^ data == hash && data != 0 {
+            Some(Entry::unpack(hash, data))
+        } else {
+            None
+        }
+    }
+
+    /// Store an entry, unless a deeper search of the same position is already stored.
+    ///
+    /// When the new entry has no movement, the movement of the same position is preserved.
+    pub fn store(&self, entry: Entry) {
+        let mut entry: Entry = entry;
+        if let Some(previous) = self.probe(entry.hash) {
+            if previous.depth > entry.depth {
+                return;
+            }
+            entry.movement = entry.movement.or(previous.movement);
+        }
+        let slot: &[AtomicU64; 2] = &self.entries[self.index(entry.hash)];
+        let data: u64 = entry.pack();
+        slot[0].store(entry.hash ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        for slot in &self.entries {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use crate::cheso::board::Board;
+use crate::cheso::book::Book;
+use crate::cheso::movement::Movement;
+use crate::cheso::search::{
+    is_mate_score, Limits, SearchInfo, SearchResult, Searcher, DEFAULT_MOVE_OVERHEAD, MATE,
+};
+use crate::cheso::skill::{xorshift, MAX_ELO, MAX_LEVEL, MIN_ELO};
+#[cfg(feature = "syzygy")]
+use crate::cheso::syzygy::{Tablebases, MAX_PIECES};
+use crate::cheso::tt::Bound;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default size of the transposition table, in megabytes.
+const DEFAULT_HASH: usize = 16;
+
+/// Default path of the opening book.
+const DEFAULT_BOOK_FILE: &str = "book.bin";
+
+/// Format a score as expected by the UCI protocol (`cp <centipawns>` or `mate <moves>`).
+pub fn format_score(score: i32) -> String {
+    if is_mate_score(score) {
+        let plies: i32 = MATE - score.abs();
+        let moves: i32 = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Format the information about the progress of a search as an UCI `info` command.
+pub fn format_info(info: &SearchInfo) -> String {
+    let millis: u128 = info.time.as_millis();
+    let mut text: String = format!(
+        "info depth {} multipv {} score {}",
+        info.depth,
+        info.multipv,
+        format_score(info.score)
+    );
+    match info.bound {
+        Bound::Lower => text.push_str(" lowerbound"),
+        Bound::Upper => text.push_str(" upperbound"),
+        Bound::Exact => (),
+    }
+    text.push_str(&format!(
+        " nodes {} nps {} time {}",
+        info.nodes,
+        info.nodes as u128 * 1000 / std::cmp::max(1, millis),
+        millis
+    ));
+    if !info.pv.is_empty() {
+        text.push_str(" pv");
+        for movement in &info.pv {
+            text.push_str(&format!(" {}", movement));
+        }
+    }
+    text
+}
+
+/// Destination of the responses of the engine.
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// Write a response as a line, ignoring errors (the GUI may be gone).
+fn send(output: &Output, text: &str) {
+    let mut output = output.lock().unwrap_or_else(|error| error.into_inner());
+    let _ = writeln!(output, "{}", text);
+    let _ = output.flush();
+}
+
+/// Universal Chess Interface.
+///
+/// Front end of the engine, reading commands from a GUI and writing back the responses.
+/// Searches run in a separate thread, so the GUI can stop them at any moment.
+/// Responses are written to the standard output, unless another output is given.
+/// With an own book, movements of the book are played without searching.
+pub struct Uci {
+    board: Board,
+    own_book: bool,
+    book_file: String,
+    book: Option<Book>,
+    searcher: Arc<Mutex<Searcher>>,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    output: Output,
+}
+
+impl Default for Uci {
+    fn default() -> Uci {
+        Uci::new()
+    }
+}
+
+impl Uci {
+    /// Make a new interface, with the initial position.
+    pub fn new() -> Uci {
+        Uci::with_output(Arc::new(Mutex::new(std::io::stdout())))
+    }
+
+    /// Make a new interface writing its responses to an output, with the initial position.
+    pub fn with_output(output: Output) -> Uci {
+        let searcher: Searcher = Searcher::new(DEFAULT_HASH);
+        let stop: Arc<AtomicBool> = searcher.stop_flag();
+        let ponder: Arc<AtomicBool> = searcher.ponder_flag();
+        Uci {
+            board: Board::new(),
+            own_book: false,
+            book_file: String::from(DEFAULT_BOOK_FILE),
+            book: None,
+            searcher: Arc::new(Mutex::new(searcher)),
+            stop,
+            ponder,
+            thread: None,
+            output,
+        }
+    }
+
+    /// Read and execute commands from the standard input, until `quit` or the end of the input.
+    pub fn run(&mut self) {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) if self.execute(&line) => (),
+                _ => break,
+            }
+        }
+        self.stop();
+    }
+
+    /// Execute a command, returning false when the engine must quit.
+    ///
+    /// Unknown commands are ignored, as required by the protocol.
+    pub fn execute(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                self.send("id name Cheso");
+                self.send("id author José Antonio Riaza Valverde");
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max 65536",
+                    DEFAULT_HASH
+                ));
+                self.send("option name Threads type spin default 1 min 1 max 256");
+                self.send("option name MultiPV type spin default 1 min 1 max 256");
+                self.send("option name Ponder type check default false");
+                self.send("option name MCTS type check default false");
+                self.send("option name OwnBook type check default false");
+                self.send(&format!(
+                    "option name BookFile type string default {}",
+                    DEFAULT_BOOK_FILE
+                ));
+                self.send(&format!(
+                    "option name Skill Level type spin default {} min 0 max {}",
+                    MAX_LEVEL, MAX_LEVEL
+                ));
+                self.send("option name UCI_LimitStrength type check default false");
+                self.send(&format!(
+                    "option name UCI_Elo type spin default {} min {} max {}",
+                    MAX_ELO, MIN_ELO, MAX_ELO
+                ));
+                self.send(&format!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    DEFAULT_MOVE_OVERHEAD
+                ));
+                #[cfg(feature = "syzygy")]
+                {
+                    self.send("option name SyzygyPath type string default <empty>");
+                    self.send(&format!(
+                        "option name SyzygyProbeLimit type spin default {} min 0 max {}",
+                        MAX_PIECES, MAX_PIECES
+                    ));
+                }
+                self.send("uciok");
+            }
+            Some(&"isready") => self.send("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop();
+                self.searcher().clear();
+            }
+            Some(&"setoption") => {
+                self.stop();
+                self.set_option(&tokens[1..]);
+            }
+            Some(&"position") => {
+                self.stop();
+                self.position(&tokens[1..]);
+            }
+            Some(&"go") => {
+                self.stop();
+                self.go(&tokens[1..]);
+            }
+            Some(&"ponderhit") => self.ponder.store(false, Ordering::Relaxed),
+            Some(&"stop") => self.stop(),
+            Some(&"quit") => return false,
+            _ => (),
+        }
+        true
+    }
+
+    /// Write a response as a line.
+    fn send(&self, text: &str) {
+        send(&self.output, text);
+    }
+
+    /// Get exclusive access to the searcher.
+    fn searcher(&self) -> std::sync::MutexGuard<'_, Searcher> {
+        self.searcher
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Stop the current search (if any) and wait for it to finish.
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Set the value of an option (`name <name> value <value>`).
+    fn set_option(&mut self, args: &[&str]) {
+        let value_index: usize = args
+            .iter()
+            .position(|arg| *arg == "value")
+            .unwrap_or(args.len());
+        let name: String = args[..value_index]
+            .iter()
+            .skip_while(|arg| **arg == "name")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value: String = args[std::cmp::min(value_index + 1, args.len())..].join(" ");
+        #[cfg(feature = "syzygy")]
+        if self.set_syzygy_option(&name, &value) {
+            return;
+        }
+        if name.eq_ignore_ascii_case("Hash") {
+            if let Ok(megabytes) = value.parse::<usize>() {
+                self.searcher().set_hash(std::cmp::max(1, megabytes));
+            }
+        } else if name.eq_ignore_ascii_case("Threads") {
+            if let Ok(threads) = value.parse::<usize>() {
+                self.searcher().threads = threads.clamp(1, 256);
+            }
+        } else if name.eq_ignore_ascii_case("MultiPV") {
+            if let Ok(multipv) = value.parse::<usize>() {
+                self.searcher().multipv = multipv.clamp(1, 256);
+            }
+        } else if name.eq_ignore_ascii_case("MCTS") {
+            self.searcher().monte_carlo = value.eq_ignore_ascii_case("true");
+        } else if name.eq_ignore_ascii_case("Skill Level") {
+            if let Ok(level) = value.parse::<i64>() {
+                self.searcher().skill.level = level.clamp(0, MAX_LEVEL as i64) as u8;
+            }
+        } else if name.eq_ignore_ascii_case("UCI_LimitStrength") {
+            self.searcher().skill.limit_strength = value.eq_ignore_ascii_case("true");
+        } else if name.eq_ignore_ascii_case("UCI_Elo") {
+            if let Ok(elo) = value.parse::<i64>() {
+                self.searcher().skill.elo = elo.clamp(MIN_ELO as i64, MAX_ELO as i64) as u32;
+            }
+        } else if name.eq_ignore_ascii_case("OwnBook") {
+            self.own_book = value.eq_ignore_ascii_case("true");
+            self.book = None;
+        } else if name.eq_ignore_ascii_case("BookFile") {
+            self.book_file = value;
+            self.book = None;
+        } else if name.eq_ignore_ascii_case("Move Overhead") {
+            if let Ok(millis) = value.parse::<u64>() {
+                self.searcher().move_overhead = Duration::from_millis(millis);
+            }
+        }
+    }
+
+    /// Set an option of the Syzygy tablebases, returning false when the option is not one of them.
+    #[cfg(feature = "syzygy")]
+    fn set_syzygy_option(&mut self, name: &str, value: &str) -> bool {
+        if name.eq_ignore_ascii_case("SyzygyPath") {
+            let tablebases: Tablebases = match value {
+                "" | "<empty>" => Tablebases::default(),
+                paths => Tablebases::open(paths),
+            };
+            self.send(&format!(
+                "info string found {} tablebases with up to {} pieces",
+                tablebases.len(),
+                tablebases.max_pieces()
+            ));
+            self.searcher().tablebases = Arc::new(tablebases);
+        } else if name.eq_ignore_ascii_case("SyzygyProbeLimit") {
+            if let Ok(limit) = value.parse::<usize>() {
+                self.searcher().probe_limit = std::cmp::min(limit, MAX_PIECES);
+            }
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Set up the position (`startpos` or `fen <fen>`, followed by `moves <moves>`).
+    fn position(&mut self, args: &[&str]) {
+        let moves_index: usize = args
+            .iter()
+            .position(|arg| *arg == "moves")
+            .unwrap_or(args.len());
+        let board: Result<Board, String> = match args.first() {
+            Some(&"startpos") => Ok(Board::new()),
+            Some(&"fen") => Board::from_fen(&args[1..moves_index].join(" ")),
+            _ => Err(String::from("missing position")),
+        };
+        match board {
+            Ok(board) => self.board = board,
+            Err(error) => {
+                self.send(&format!("info string {}", error));
+                return;
+            }
+        }
+        for text in args.iter().skip(moves_index + 1) {
+            match self.board.parse_move(text) {
+                Some(movement) => self.board.make_move(&movement),
+                None => {
+                    self.send(&format!("info string illegal movement {}", text));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Get a movement of the own book for the current position (if enabled), loading the book when needed.
+    fn book_move(&mut self) -> Option<Movement> {
+        if !self.own_book {
+            return None;
+        }
+        if self.book.is_none() {
+            match Book::open(&self.book_file) {
+                Ok(book) => self.book = Some(book),
+                Err(error) => {
+                    self.send(&format!("info string {}", error));
+                    self.own_book = false;
+                    return None;
+                }
+            }
+        }
+        let random: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |time| time.as_nanos() as u64 | 1);
+        self.book.as_ref()?.pick(&mut self.board, xorshift(random))
+    }
+
+    /// Parse the limits of a search (`depth`, `nodes`, `movetime`, clocks or `infinite`).
+    fn parse_limits(args: &[&str]) -> Limits {
+        let mut limits: Limits = Limits::default();
+        for (index, arg) in args.iter().enumerate() {
+            let value: Option<u64> = args.get(index + 1).and_then(|value| value.parse().ok());
+            match *arg {
+                "depth" => limits.depth = value.map(|depth| depth as i32),
+                "nodes" => limits.nodes = value,
+                "movetime" => limits.movetime = value.map(Duration::from_millis),
+                "wtime" => limits.wtime = value.map(Duration::from_millis),
+                "btime" => limits.btime = value.map(Duration::from_millis),
+                "winc" => limits.winc = value.map(Duration::from_millis),
+                "binc" => limits.binc = value.map(Duration::from_millis),
+                "movestogo" => limits.movestogo = value.map(|moves| moves as u32),
+                "infinite" => return Limits::default(),
+                _ => (),
+            }
+        }
+        limits
+    }
+
+    /// Start searching the current position in a separate thread.
+    ///
+    /// Progress is written as `info` commands, and the best movement as a `bestmove` command,
+    /// followed by the expected reply of the opponent (to ponder on) when the principal variation has one.
+    /// With `go ponder`, the position already includes the expected movement of the opponent, and the search
+    /// ignores the clock until `ponderhit`; the best movement is not written before `ponderhit` or `stop`.
+    /// With `go infinite`, the best movement is not written before `stop`, even if the search ends on its own.
+    fn go(&mut self, args: &[&str]) {
+        let limits: Limits = Uci::parse_limits(args);
+        // book movements are played at once (but not while analyzing or pondering)
+        if !args.contains(&"infinite") && !args.contains(&"ponder") {
+            if let Some(movement) = self.book_move() {
+                self.send(&format!("bestmove {}", movement));
+                return;
+            }
+        }
+        let mut board: Board = self.board.clone();
+        let searcher: Arc<Mutex<Searcher>> = self.searcher.clone();
+        let stop: Arc<AtomicBool> = self.stop.clone();
+        let ponder: Arc<AtomicBool> = self.ponder.clone();
+        let infinite: bool = args.contains(&"infinite");
+        let output: Output = self.output.clone();
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder
+            .store(args.contains(&"ponder"), Ordering::Relaxed);
+        self.thread = Some(std::thread::spawn(move || {
+            let mut searcher = searcher.lock().unwrap_or_else(|error| error.into_inner());
+            let result: SearchResult = searcher.search(&mut board, &limits, &mut |info| {
+                send(&output, &format_info(info))
+            });
+            while (infinite || ponder.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let bestmove: String = result
+                .movement
+                .as_ref()
+                .map_or(String::from("0000"), Movement::to_string);
+            match result
+                .pv
+                .get(1)
+                .filter(|_| result.pv.first() == result.movement.as_ref())
+            {
+                Some(reply) => send(&output, &format!("bestmove {} ponder {}", bestmove, reply)),
+                None => send(&output, &format!("bestmove {}", bestmove)),
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Make an interface writing to a buffer, returned along with it.
+    fn interface() -> (Uci, Arc<Mutex<Vec<u8>>>) {
+        let buffer: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        (Uci::with_output(buffer.clone()), buffer)
+    }
+
+    /// Get the lines written to a buffer.
+    fn lines(buffer: &Arc<Mutex<Vec<u8>>>) -> Vec<String> {
+        String::from_utf8_lossy(&buffer.lock().unwrap())
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    /// Wait (for a few seconds at most) until a line starting with a prefix is written, returning it.
+    fn wait_for(buffer: &Arc<Mutex<Vec<u8>>>, prefix: &str) -> Option<String> {
+        let start: Instant = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            if let Some(line) = lines(buffer)
+                .into_iter()
+                .find(|line| line.starts_with(prefix))
+            {
+                return Some(line);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn ponder_holds_the_best_move_until_ponderhit() {
+        let (mut uci, buffer) = interface();
+        uci.execute("position startpos moves e2e4");
+        uci.execute("go ponder depth 3");
+        assert!(wait_for(&buffer, "info depth 3").is_some());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!lines(&buffer)
+            .iter()
+            .any(|line| line.starts_with("bestmove")));
+        uci.execute("ponderhit");
+        let bestmove: String = wait_for(&buffer, "bestmove").unwrap();
+        // the best movement and the reply to ponder on are the first two of the principal variation
+        let pv: String = lines(&buffer)
+            .iter()
+            .rev()
+            .find(|line| line.starts_with("info depth 3 multipv 1") && !line.contains("bound"))
+            .and_then(|line| line.split(" pv ").nth(1).map(String::from))
+            .unwrap();
+        let pv: Vec<&str> = pv.split(' ').collect();
+        assert_eq!(bestmove, format!("bestmove {} ponder {}", pv[0], pv[1]));
+    }
+
+    #[test]
+    fn no_movement_is_a_null_bestmove() {
+        for fen in [
+            "8/8/8/8/8/5k2/8/5K1q w - - 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        ] {
+            let (mut uci, buffer) = interface();
+            uci.execute(&format!("position fen {}", fen));
+            uci.execute("go depth 3");
+            assert_eq!(
+                wait_for(&buffer, "bestmove").as_deref(),
+                Some("bestmove 0000")
+            );
+        }
+    }
+
+    #[test]
+    fn stop_ends_pondering() {
+        let (mut uci, buffer) = interface();
+        uci.execute("position startpos moves e2e4");
+        uci.execute("go ponder depth 2");
+        assert!(wait_for(&buffer, "info depth 2").is_some());
+        uci.execute("stop");
+        assert_eq!(
+            lines(&buffer)
+                .iter()
+                .filter(|line| line.starts_with("bestmove"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn skill_level_is_clamped() {
+        let (mut uci, _) = interface();
+        uci.execute("setoption name Skill Level value 5");
+        assert_eq!(uci.searcher().skill.level, 5);
+        uci.execute("setoption name Skill Level value 256");
+        assert_eq!(uci.searcher().skill.level, MAX_LEVEL);
+        uci.execute("setoption name Skill Level value -1");
+        assert_eq!(uci.searcher().skill.level, 0);
+        uci.execute("setoption name Skill Level value five");
+        assert_eq!(uci.searcher().skill.level, 0);
+        uci.execute("setoption name UCI_Elo value -1");
+        assert_eq!(uci.searcher().skill.elo, MIN_ELO);
+        uci.execute("setoption name UCI_Elo value 99999999999");
+        assert_eq!(uci.searcher().skill.elo, MAX_ELO);
+    }
+
+    #[test]
+    #[cfg(feature = "syzygy")]
+    fn syzygy_options() {
+        let (mut uci, buffer) = interface();
+        let tables: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+        uci.execute(&format!(
+            "setoption name SyzygyPath value /nonexistent:{}",
+            tables
+        ));
+        assert_eq!(
+            lines(&buffer).last().unwrap(),
+            "info string found 5 tablebases with up to 3 pieces"
+        );
+        assert_eq!(uci.searcher().tablebases.len(), 5);
+        uci.execute("setoption name SyzygyPath value <empty>");
+        assert_eq!(
+            lines(&buffer).last().unwrap(),
+            "info string found 0 tablebases with up to 0 pieces"
+        );
+        assert!(uci.searcher().tablebases.is_empty());
+        assert_eq!(uci.searcher().probe_limit, MAX_PIECES);
+        uci.execute("setoption name SyzygyProbeLimit value 4");
+        assert_eq!(uci.searcher().probe_limit, 4);
+        uci.execute("setoption name SyzygyProbeLimit value 9");
+        assert_eq!(uci.searcher().probe_limit, MAX_PIECES);
+        uci.execute("setoption name SyzygyProbeLimit value four");
+        assert_eq!(uci.searcher().probe_limit, MAX_PIECES);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cheso::color::Color;
+use crate::cheso::piece::Piece;
+use crate::cheso::square::Square;
+
+/// Zobrist keys.
+///
+/// Random numbers used to compute the hash of a position, by xoring the keys of its features.
+/// Keys are generated at compile time from a fixed seed, so hashes are the same in every run.
+pub struct Zobrist {
+    pieces: [[[u64; 64]; 6]; 2],
+    castling: [u64; 4],
+    en_passant: [u64; 8],
+    side: u64,
+}
+
+/// Global Zobrist keys.
+pub static ZOBRIST: Zobrist = Zobrist::new();
+
+/// Generate the next pseudo-random number of a splitmix64 sequence.
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z: u64 = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl Zobrist {
+    /// Generate the keys from a fixed seed.
+    const fn new() -> Zobrist {
+        let mut state: u64 = 0x636865736F;
+        let mut pieces: [[[u64; 64]; 6]; 2] = [[[0; 64]; 6]; 2];
+        let mut color: usize = 0;
+        while color < 2 {
+            let mut piece: usize = 0;
+            while piece < 6 {
+                let mut square: usize = 0;
+                while square < 64 {
+                    pieces[color][piece][square] = splitmix64(&mut state);
+                    square += 1;
+                }
+                piece += 1;
+            }
+            color += 1;
+        }
+        let mut castling: [u64; 4] = [0; 4];
+        let mut i: usize = 0;
+        while i < 4 {
+            castling[i] = splitmix64(&mut state);
+            i += 1;
+        }
+        let mut en_passant: [u64; 8] = [0; 8];
+        let mut i: usize = 0;
+        while i < 8 {
+            en_passant[i] = splitmix64(&mut state);
+            i += 1;
+        }
+        Zobrist {
+            pieces,
+            castling,
+            en_passant,
+            side: splitmix64(&mut state),
+        }
+    }
+
+    /// Get the key of a piece of a color located in a square.
+    pub fn piece(&self, piece: Piece, color: Color, square: &Square) -> u64 {
+        self.pieces[color.index()][piece.index()][square.index()]
+    }
+
+    /// Get the key of a castling right.
+    ///
+    /// Castling rights are indexed as white kingside, white queenside, black kingside and black queenside.
+    pub fn castling(&self, index: usize) -> u64 {
+        self.castling[index]
+    }
+
+    /// Get the key of an en passant file (from 1 to 8).
+    pub fn en_passant(&self, file: u8) -> u64 {
+        self.en_passant[(file - 1) as usize]
+    }
+
+    /// Get the key of the side to move (only used when black is to move).
+    pub fn side(&self) -> u64 {
+        self.side
+    }
+}
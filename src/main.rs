@@ -1,3 +1,372 @@
-mod cheso;
+use cheso::cheso::board::Board;
+use cheso::cheso::book::BookBuilder;
+use cheso::cheso::color::Color;
+use cheso::cheso::db::{Database, GameFilter, GameId, MoveStatistics};
+use cheso::cheso::eco::classify_game;
+use cheso::cheso::epd::{Epd, Solution};
+use cheso::cheso::movement::Movement;
+use cheso::cheso::pattern::Pattern;
+use cheso::cheso::pgn::{PgnGame, PgnReader};
+use cheso::cheso::search::{Limits, SearchResult, Searcher};
+use cheso::cheso::tablebase::{Signature, TablebaseSet};
+use cheso::cheso::uci::Uci;
+use std::time::{Duration, Instant};
 
-fn main() {}
+/// Build a Polyglot book from a PGN file.
+///
+/// Usage: `book-build <input.pgn> <output.bin> [max-ply <n>] [min-games <n>] [min-elo <n>]`.
+fn book_build(args: &[String]) -> Result<(), String> {
+    let (input, output) = match args {
+        [input, output, ..] => (input, output),
+        _ => return Err(String::from(
+            "usage: book-build <input.pgn> <output.bin> [max-ply <n>] [min-games <n>] [min-elo <n>]",
+        )),
+    };
+    let mut builder: BookBuilder = BookBuilder::new();
+    for option in args[2..].chunks(2) {
+        let value: u32 = match option.get(1).map(|value| value.parse()) {
+            Some(Ok(value)) => value,
+            _ => return Err(format!("invalid value for {}", option[0])),
+        };
+        match option[0].as_str() {
+            "max-ply" => builder.max_ply = value as usize,
+            "min-games" => builder.min_games = value,
+            "min-elo" => builder.min_elo = value,
+            _ => return Err(format!("unknown option {}", option[0])),
+        }
+    }
+    let file: std::fs::File =
+        std::fs::File::open(input).map_err(|error| format!("cannot read {}: {}", input, error))?;
+    let (mut used, mut total): (usize, usize) = (0, 0);
+    for game in PgnReader::new(std::io::BufReader::new(file)) {
+        let game: PgnGame = match game {
+            Ok(game) => game,
+            Err(error) => {
+                eprintln!("{}: skipping game at {}", input, error);
+                continue;
+            }
+        };
+        total += 1;
+        if builder.add_game(&game) {
+            used += 1;
+        }
+    }
+    builder.write(output)?;
+    println!(
+        "{} of {} games used, {} entries written to {}",
+        used,
+        total,
+        builder.entries().len(),
+        output
+    );
+    Ok(())
+}
+
+/// Generate an endgame table (and the tables it depends on) by retrograde analysis.
+///
+/// Usage: `tablebase-gen <signature> <directory>` (e.g. `tablebase-gen KBNvK tables`).
+fn tablebase_gen(args: &[String]) -> Result<(), String> {
+    let (signature, directory) = match args {
+        [signature, directory] => (
+            Signature::parse(signature)?,
+            std::path::Path::new(directory),
+        ),
+        _ => return Err(String::from("usage: tablebase-gen <signature> <directory>")),
+    };
+    let mut set: TablebaseSet = TablebaseSet::new();
+    set.generate(&signature)?;
+    std::fs::create_dir_all(directory)
+        .map_err(|error| format!("cannot create {}: {}", directory.display(), error))?;
+    set.write(directory)?;
+    for table in set.tables() {
+        println!("{} ({} positions)", table.signature(), table.len());
+    }
+    Ok(())
+}
+
+/// Search the positions of an EPD test suite, and report which ones are solved.
+///
+/// Usage: `epd-run <suite.epd> [depth <n>] [movetime <ms>] [hash <mb>] [threads <n>]`
+/// (each position is searched for one second by default).
+/// A position is solved at the first iteration from which the best movement solves it until the end of the search.
+fn epd_run(args: &[String]) -> Result<(), String> {
+    let input: &String =
+        match args.first() {
+            Some(input) => input,
+            None => return Err(String::from(
+                "usage: epd-run <suite.epd> [depth <n>] [movetime <ms>] [hash <mb>] [threads <n>]",
+            )),
+        };
+    let mut limits: Limits = Limits::default();
+    let (mut hash, mut threads): (usize, usize) = (16, 1);
+    for option in args[1..].chunks(2) {
+        let value: u64 = match option.get(1).map(|value| value.parse()) {
+            Some(Ok(value)) => value,
+            _ => return Err(format!("invalid value for {}", option[0])),
+        };
+        match option[0].as_str() {
+            "depth" => limits.depth = Some(value as i32),
+            "movetime" => limits.movetime = Some(Duration::from_millis(value)),
+            "hash" => hash = value as usize,
+            "threads" => threads = value as usize,
+            _ => return Err(format!("unknown option {}", option[0])),
+        }
+    }
+    if limits.depth.is_none() && limits.movetime.is_none() {
+        limits.movetime = Some(Duration::from_secs(1));
+    }
+    let text: String = std::fs::read_to_string(input)
+        .map_err(|error| format!("cannot read {}: {}", input, error))?;
+    let mut searcher: Searcher = Searcher::new(hash);
+    searcher.threads = threads;
+    let (mut solved, mut total, mut points, mut max_points): (usize, usize, u32, u32) =
+        (0, 0, 0, 0);
+    let mut solve_time: Duration = Duration::ZERO;
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (epd, solution): (Epd, Solution) = match Epd::parse(line).and_then(|epd| {
+            let solution: Solution = epd.solution()?;
+            Ok((epd, solution))
+        }) {
+            Ok((_, solution)) if solution.is_empty() => {
+                eprintln!("{}:{}: nothing to solve", input, number + 1);
+                continue;
+            }
+            Ok(position) => position,
+            Err(error) => {
+                eprintln!("{}:{}: {}", input, number + 1, error);
+                continue;
+            }
+        };
+        let id: String = epd
+            .id()
+            .map_or_else(|| format!("line {}", number + 1), String::from);
+        let mut board: Board = epd.board.clone();
+        let start: Instant = Instant::now();
+        let mut solved_at: Option<Duration> = None;
+        searcher.clear();
+        let result: SearchResult = searcher.search(&mut board, &limits, &mut |info| match info
+            .pv
+            .first()
+        {
+            Some(movement) if info.multipv == 1 && solution.is_solved_by(movement, info.score) => {
+                solved_at.get_or_insert(info.time);
+            }
+            _ if info.multipv == 1 => solved_at = None,
+            _ => (),
+        });
+        total += 1;
+        max_points += solution.max_points();
+        let expected: String = ["bm", "am", "dm"]
+            .iter()
+            .filter_map(|opcode| Some(format!("{} {}", opcode, epd.operation(opcode)?.join(" "))))
+            .collect::<Vec<String>>()
+            .join(", ");
+        match result.movement {
+            Some(movement) => {
+                let san: String = board.to_san(&movement);
+                points += solution.points(&movement, result.score);
+                if solution.is_solved_by(&movement, result.score) {
+                    let time: Duration = solved_at.unwrap_or_else(|| start.elapsed());
+                    solved += 1;
+                    solve_time += time;
+                    println!("{} pass {} in {:.2}s", id, san, time.as_secs_f64());
+                } else {
+                    println!("{} fail {} ({})", id, san, expected);
+                }
+            }
+            None => println!("{} fail without movement ({})", id, expected),
+        }
+    }
+    println!(
+        "{} of {} solved, score {} of {}, average solve time {:.2}s",
+        solved,
+        total,
+        points,
+        max_points,
+        solve_time.as_secs_f64() / std::cmp::max(solved, 1) as f64
+    );
+    Ok(())
+}
+
+/// Parse the position and the filter of a database query.
+///
+/// A position is given by `fen <FEN>`, optionally followed by `moves <SAN movements>` (from the initial position
+/// without FEN), and the filter by `player <name>`, `white <name>`, `black <name>`, `from <date>`, `to <date>`
+/// and `eco <code>`.
+fn db_query(args: &[String]) -> Result<(Option<Board>, GameFilter), String> {
+    let mut board: Option<Board> = None;
+    let mut filter: GameFilter = GameFilter::default();
+    for option in args.chunks(2) {
+        let value: String = match option.get(1) {
+            Some(value) => value.clone(),
+            None => return Err(format!("missing value for {}", option[0])),
+        };
+        match option[0].as_str() {
+            "fen" => board = Some(Board::from_fen(&value)?),
+            "moves" => {
+                let board: &mut Board = board.get_or_insert_with(Board::new);
+                for text in value.split_whitespace() {
+                    let movement: Movement = board
+                        .parse_san(text)
+                        .ok_or(format!("illegal movement {}", text))?;
+                    board.make_move(&movement);
+                }
+            }
+            "player" => filter.player = Some(value),
+            "white" => filter.white = Some(value),
+            "black" => filter.black = Some(value),
+            "from" => filter.date_from = Some(value),
+            "to" => filter.date_to = Some(value),
+            "eco" => filter.eco = Some(value),
+            _ => return Err(format!("unknown option {}", option[0])),
+        }
+    }
+    Ok((board, filter))
+}
+
+/// Import the games of PGN files into a database, returning the number of games imported.
+///
+/// Games that can not be parsed or added are reported and skipped.
+fn db_import(database: &mut Database, inputs: &[String]) -> Result<usize, String> {
+    let mut imported: usize = 0;
+    for input in inputs {
+        let file: std::fs::File = std::fs::File::open(input)
+            .map_err(|error| format!("cannot read {}: {}", input, error))?;
+        for game in PgnReader::new(std::io::BufReader::new(file)) {
+            match game {
+                Ok(mut game) => {
+                    // games that can not be classified are imported without an opening
+                    if game.tag("ECO").is_none() {
+                        if let Some(opening) = classify_game(&game).ok().flatten() {
+                            opening.set_tags(&mut game);
+                        }
+                    }
+                    match database.add_game(&game) {
+                        Ok(_) => imported += 1,
+                        Err(error) => eprintln!("{}: skipping game: {}", input, error),
+                    }
+                }
+                Err(error) => eprintln!("{}: skipping game at {}", input, error),
+            }
+        }
+    }
+    Ok(imported)
+}
+
+/// Print the records of games (with the ply of the position found in each game, if any).
+fn db_print_games(database: &Database, games: &[(GameId, Option<u16>)]) {
+    for (game, ply) in games {
+        if let Some(record) = database.record(*game) {
+            print!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                game, record.white, record.black, record.date, record.result, record.eco
+            );
+            match ply {
+                Some(ply) => println!("\tply {}", ply),
+                None => println!(),
+            }
+        }
+    }
+}
+
+/// Import games into a database, list its games, explore the movements played from a position,
+/// or find the positions matching a pattern.
+///
+/// Usage: `db import <directory> <file.pgn>...`, `db games <directory> [query]`, `db explore <directory> [query]`
+/// or `db find <directory> <pattern> [filter]` (see `db_query` for the options of queries, and `Pattern` for patterns).
+/// Imported games without an `ECO` tag are classified.
+fn db(args: &[String]) -> Result<(), String> {
+    let (command, directory) = match args {
+        [command, directory, ..] => (command.as_str(), std::path::Path::new(directory)),
+        _ => {
+            return Err(String::from(
+                "usage: db <import|games|explore|find> <directory> [<file.pgn>... | query | pattern [filter]]",
+            ))
+        }
+    };
+    let mut database: Database = Database::open(directory)?;
+    match command {
+        "import" => {
+            let imported: Result<usize, String> = db_import(&mut database, &args[2..]);
+            // the games added before an error are indexed as well
+            database.flush()?;
+            println!(
+                "{} games imported, {} games in the database",
+                imported?,
+                database.len()
+            );
+        }
+        "games" => {
+            let (board, filter) = db_query(&args[2..])?;
+            let games: Vec<(GameId, Option<u16>)> = match board {
+                Some(board) => database
+                    .games_reaching(&board, &filter)?
+                    .into_iter()
+                    .map(|(game, ply)| (game, Some(ply)))
+                    .collect(),
+                None => database
+                    .search(&filter)
+                    .into_iter()
+                    .map(|game| (game, None))
+                    .collect(),
+            };
+            db_print_games(&database, &games);
+        }
+        "explore" => {
+            let (board, filter) = db_query(&args[2..])?;
+            let mut board: Board = board.unwrap_or_default();
+            let statistics: Vec<MoveStatistics> = database.explore(&board, &filter)?;
+            for movement in statistics {
+                let (wins, losses): (u32, u32) = match board.side_to_move {
+                    Color::White => (movement.white_wins, movement.black_wins),
+                    Color::Black => (movement.black_wins, movement.white_wins),
+                };
+                println!(
+                    "{}\t{} games\t+{} ={} -{}\t{:.1}%",
+                    board.to_san(&movement.movement),
+                    movement.games,
+                    wins,
+                    movement.draws,
+                    losses,
+                    (2 * wins + movement.draws) as f64 * 50.0 / movement.games as f64
+                );
+            }
+        }
+        "find" => {
+            let pattern: Pattern = Pattern::parse(args.get(2).ok_or("missing pattern")?)?;
+            let (board, filter) = db_query(&args[3..])?;
+            if board.is_some() {
+                return Err(String::from("no position allowed with a pattern"));
+            }
+            let games: Vec<(GameId, Option<u16>)> = database
+                .find(&pattern, &filter)?
+                .into_iter()
+                .map(|(game, ply)| (game, Some(ply)))
+                .collect();
+            db_print_games(&database, &games);
+        }
+        _ => return Err(format!("unknown db command {}", command)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result: Result<(), String> = match args.first().map(String::as_str) {
+        Some("book-build") => book_build(&args[1..]),
+        Some("tablebase-gen") => tablebase_gen(&args[1..]),
+        Some("epd-run") => epd_run(&args[1..]),
+        Some("db") => db(&args[1..]),
+        _ => {
+            Uci::new().run();
+            Ok(())
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}